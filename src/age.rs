@@ -0,0 +1,546 @@
+use std::io;
+
+use base64::{ Engine, engine::general_purpose::{ STANDARD, STANDARD_NO_PAD } };
+use chacha20poly1305::{ ChaCha20Poly1305, KeyInit, Nonce, aead::Aead };
+use hkdf::Hkdf;
+use hmac::{ Hmac, Mac };
+use rand::{ RngCore, rngs::OsRng };
+use serde::{ Deserialize, Serialize };
+use sha2::Sha256;
+use x25519_dalek::{ EphemeralSecret, PublicKey, StaticSecret };
+
+use crate::vault_entry::VaultEntry;
+
+const VERSION_LINE: &str = "age-encryption.org/v1";
+const RECIPIENT_HRP: &str = "age";
+const IDENTITY_HRP: &str = "age-secret-key-";
+const X25519_INFO: &[u8] = b"age-encryption.org/v1/X25519";
+const SCRYPT_SALT_LABEL: &[u8] = b"age-encryption.org/v1/scrypt";
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+
+/// Plaintext bytes per chunk of the payload.
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+/// Characters per line of a stanza body.
+const BODY_COLUMNS: usize = 64;
+/// scrypt work factor of new files, the one of the age tool.
+const SCRYPT_LOG_N: u8 = 18;
+/// Largest work factor accepted when decrypting, 2 GiB of memory.
+const MAX_SCRYPT_LOG_N: u8 = 21;
+
+/// Who can open an age file: the holders of X25519 identities, or of a passphrase.
+/// age does not allow both in one file.
+pub enum Recipients {
+    Keys(Vec<PublicKey>),
+    Passphrase(String),
+}
+
+/// What opens an age file: X25519 identities, or its passphrase.
+pub enum Identities {
+    Keys(Vec<StaticSecret>),
+    Passphrase(String),
+}
+
+/// What an age export of entries holds once decrypted.
+#[derive(Debug, Deserialize, Serialize)]
+struct SharedEntries {
+    entries: Vec<VaultEntry>,
+}
+
+/// A recipient line of the header, `-> type args`, and the wrapped file key under it.
+struct Stanza {
+    kind: String,
+    args: Vec<String>,
+    body: Vec<u8>,
+}
+
+struct Header {
+    stanzas: Vec<Stanza>,
+    mac: Vec<u8>,
+    /// The header up to and including `---`, which the MAC covers.
+    mac_input: Vec<u8>,
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Parses an `age1…` public key.
+pub fn parse_recipient(text: &str) -> io::Result<PublicKey> {
+    let invalid = || invalid_data(format!("{text} is not an age X25519 recipient"));
+
+    let (hrp, data) = bech32::decode(text.trim()).map_err(|_| invalid())?;
+    if hrp.to_lowercase() != RECIPIENT_HRP {
+        return Err(invalid());
+    }
+    let bytes: [u8; 32] = data.try_into().map_err(|_| invalid())?;
+
+    Ok(PublicKey::from(bytes))
+}
+
+/// Reads the recipients of a recipients file, one `age1…` key per line, `#` starting a
+/// comment.
+pub fn parse_recipients(text: &str) -> io::Result<Vec<PublicKey>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_recipient)
+        .collect()
+}
+
+/// Reads the `AGE-SECRET-KEY-1…` lines of an identity file, as written by age-keygen.
+pub fn parse_identities(text: &str) -> io::Result<Vec<StaticSecret>> {
+    let mut identities = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = || invalid_data(format!("line {} of the identity file is not an age X25519 identity", number + 1));
+        let (hrp, data) = bech32::decode(line).map_err(|_| invalid())?;
+        if hrp.to_lowercase() != IDENTITY_HRP {
+            return Err(invalid());
+        }
+        let bytes: [u8; 32] = data.try_into().map_err(|_| invalid())?;
+        identities.push(StaticSecret::from(bytes));
+    }
+
+    if identities.is_empty() {
+        return Err(invalid_data("the identity file holds no identity"));
+    }
+
+    Ok(identities)
+}
+
+fn hkdf(ikm: &[u8], salt: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    key
+}
+
+fn header_mac(file_key: &[u8; 16], mac_input: &[u8]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&hkdf(file_key, &[], b"header")).expect("HMAC takes any key size");
+    mac.update(mac_input);
+    mac
+}
+
+/// Encrypts or decrypts the file key under a stanza, with the all-zero nonce of age.
+fn wrap(key: &[u8; 32], file_key: &[u8; 16]) -> Vec<u8> {
+    ChaCha20Poly1305::new(key.into())
+        .encrypt(&Nonce::default(), file_key.as_slice())
+        .expect("encryption does not fail")
+}
+
+fn unwrap(key: &[u8; 32], body: &[u8]) -> Option<[u8; 16]> {
+    if body.len() != 16 + TAG_SIZE {
+        return None;
+    }
+
+    ChaCha20Poly1305::new(key.into())
+        .decrypt(&Nonce::default(), body)
+        .ok()?
+        .try_into()
+        .ok()
+}
+
+fn scrypt_key(passphrase: &str, salt: &[u8], log_n: u8) -> io::Result<[u8; 32]> {
+    let params = scrypt::Params::new(log_n, 8, 1, 32).map_err(|e| invalid_data(format!("invalid scrypt parameters: {e}")))?;
+
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), &[SCRYPT_SALT_LABEL, salt].concat(), &params, &mut key)
+        .map_err(|e| invalid_data(format!("scrypt failed: {e}")))?;
+
+    Ok(key)
+}
+
+fn x25519_stanza(recipient: &PublicKey, file_key: &[u8; 16]) -> Stanza {
+    let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+    let share = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(recipient);
+
+    let salt = [share.as_bytes().as_slice(), recipient.as_bytes()].concat();
+    let key = hkdf(shared.as_bytes(), &salt, X25519_INFO);
+
+    Stanza {
+        kind: "X25519".to_string(),
+        args: vec![STANDARD_NO_PAD.encode(share.as_bytes())],
+        body: wrap(&key, file_key),
+    }
+}
+
+fn unwrap_x25519(stanza: &Stanza, identity: &StaticSecret) -> io::Result<Option<[u8; 16]>> {
+    let share: [u8; 32] = match stanza.args.as_slice() {
+        [share] => STANDARD_NO_PAD.decode(share).ok().and_then(|share| share.try_into().ok()),
+        _ => None,
+    }.ok_or_else(|| invalid_data("malformed X25519 stanza"))?;
+
+    let share = PublicKey::from(share);
+    let shared = identity.diffie_hellman(&share);
+    if !shared.was_contributory() {
+        return Err(invalid_data("malformed X25519 stanza"));
+    }
+
+    let recipient = PublicKey::from(identity);
+    let salt = [share.as_bytes().as_slice(), recipient.as_bytes()].concat();
+
+    Ok(unwrap(&hkdf(shared.as_bytes(), &salt, X25519_INFO), &stanza.body))
+}
+
+fn scrypt_stanza(passphrase: &str, file_key: &[u8; 16]) -> io::Result<Stanza> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = scrypt_key(passphrase, &salt, SCRYPT_LOG_N)?;
+
+    Ok(Stanza {
+        kind: "scrypt".to_string(),
+        args: vec![STANDARD_NO_PAD.encode(salt), SCRYPT_LOG_N.to_string()],
+        body: wrap(&key, file_key),
+    })
+}
+
+fn unwrap_scrypt(stanza: &Stanza, passphrase: &str) -> io::Result<Option<[u8; 16]>> {
+    let [salt, log_n] = stanza.args.as_slice() else {
+        return Err(invalid_data("malformed scrypt stanza"));
+    };
+    let salt = STANDARD_NO_PAD.decode(salt).ok().filter(|salt| salt.len() == 16);
+    let log_n = log_n.parse::<u8>().ok().filter(|n| !log_n.starts_with('0') && *n > 0);
+    let (Some(salt), Some(log_n)) = (salt, log_n) else {
+        return Err(invalid_data("malformed scrypt stanza"));
+    };
+    if log_n > MAX_SCRYPT_LOG_N {
+        return Err(invalid_data(format!("the file asks for scrypt with a work factor of 2^{log_n}, too costly to open")));
+    }
+
+    Ok(unwrap(&scrypt_key(passphrase, &salt, log_n)?, &stanza.body))
+}
+
+/// The nonce of the chunk `counter` of the payload, flagged when it is the last one.
+fn chunk_nonce(counter: u64, last: bool) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Encrypts `plaintext` into an age v1 file, readable by the age tools.
+pub fn encrypt(plaintext: &[u8], recipients: &Recipients) -> io::Result<Vec<u8>> {
+    let mut file_key = [0u8; 16];
+    OsRng.fill_bytes(&mut file_key);
+
+    let stanzas = match recipients {
+        Recipients::Keys(keys) if keys.is_empty() => return Err(invalid_data("no recipient to encrypt to")),
+        Recipients::Keys(keys) => keys.iter().map(|key| x25519_stanza(key, &file_key)).collect(),
+        Recipients::Passphrase(passphrase) => vec![scrypt_stanza(passphrase, &file_key)?],
+    };
+
+    let mut output = format!("{VERSION_LINE}\n").into_bytes();
+    for stanza in &stanzas {
+        let mut line = format!("-> {}", stanza.kind);
+        for arg in &stanza.args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        output.extend_from_slice(line.as_bytes());
+        output.push(b'\n');
+
+        // The body ends with a line shorter than the others, empty if need be.
+        let body = STANDARD_NO_PAD.encode(&stanza.body);
+        for line in body.as_bytes().chunks(BODY_COLUMNS) {
+            output.extend_from_slice(line);
+            output.push(b'\n');
+        }
+        if body.len() % BODY_COLUMNS == 0 {
+            output.push(b'\n');
+        }
+    }
+    output.extend_from_slice(b"---");
+    let mac = header_mac(&file_key, &output).finalize().into_bytes();
+    output.extend_from_slice(format!(" {}\n", STANDARD_NO_PAD.encode(mac)).as_bytes());
+
+    let mut nonce = [0u8; 16];
+    OsRng.fill_bytes(&mut nonce);
+    output.extend_from_slice(&nonce);
+
+    let cipher = ChaCha20Poly1305::new(&hkdf(&file_key, &nonce, b"payload").into());
+    let chunks: Vec<&[u8]> = if plaintext.is_empty() { vec![&[]] } else { plaintext.chunks(CHUNK_SIZE).collect() };
+    for (counter, chunk) in chunks.iter().enumerate() {
+        let last = counter + 1 == chunks.len();
+        let ciphertext = cipher
+            .encrypt(&chunk_nonce(counter as u64, last), *chunk)
+            .map_err(|_| io::Error::other("encryption failed"))?;
+        output.extend_from_slice(&ciphertext);
+    }
+
+    Ok(output)
+}
+
+/// The binary content of an ASCII armored file, or the file itself.
+fn dearmor(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) if text.trim_start().starts_with(ARMOR_BEGIN) => text.trim(),
+        _ => return Ok(bytes.to_vec()),
+    };
+
+    let body = text
+        .strip_prefix(ARMOR_BEGIN)
+        .and_then(|text| text.strip_suffix(ARMOR_END))
+        .ok_or_else(|| invalid_data("malformed armor"))?;
+    let encoded: String = body.split_whitespace().collect();
+
+    STANDARD.decode(encoded).map_err(|_| invalid_data("malformed armor"))
+}
+
+/// Splits `bytes` at the next newline.
+fn next_line(bytes: &[u8]) -> io::Result<(&str, &[u8])> {
+    let end = bytes.iter().position(|b| *b == b'\n').ok_or_else(|| invalid_data("truncated header"))?;
+    let line = std::str::from_utf8(&bytes[..end]).map_err(|_| invalid_data("malformed header"))?;
+
+    Ok((line, &bytes[end + 1..]))
+}
+
+/// Parses the header, returning it and the payload after it.
+fn read_header(bytes: &[u8]) -> io::Result<(Header, &[u8])> {
+    let (version, mut rest) = next_line(bytes).map_err(|_| invalid_data("not an age file"))?;
+    if version != VERSION_LINE {
+        return Err(invalid_data("not an age v1 file"));
+    }
+
+    let mut stanzas = Vec::new();
+    loop {
+        let offset = bytes.len() - rest.len();
+        let (line, after) = next_line(rest)?;
+        rest = after;
+
+        if let Some(mac) = line.strip_prefix("--- ") {
+            let mac = STANDARD_NO_PAD.decode(mac).map_err(|_| invalid_data("malformed header MAC"))?;
+            let mac_input = bytes[..offset + 3].to_vec();
+            return Ok((Header { stanzas, mac, mac_input }, rest));
+        }
+
+        let mut words = line.strip_prefix("-> ").ok_or_else(|| invalid_data("malformed header"))?.split(' ');
+        let kind = words.next().filter(|kind| !kind.is_empty()).ok_or_else(|| invalid_data("malformed stanza"))?;
+        let args = words.map(str::to_string).collect();
+
+        let mut body = String::new();
+        loop {
+            let (line, after) = next_line(rest)?;
+            rest = after;
+            body.push_str(line);
+            if line.len() < BODY_COLUMNS {
+                break;
+            }
+        }
+        let body = STANDARD_NO_PAD.decode(body).map_err(|_| invalid_data("malformed stanza body"))?;
+
+        stanzas.push(Stanza { kind: kind.to_string(), args, body });
+    }
+}
+
+/// Whether the file is encrypted with a passphrase rather than to recipients.
+pub fn is_passphrase_encrypted(bytes: &[u8]) -> bool {
+    dearmor(bytes)
+        .ok()
+        .and_then(|bytes| read_header(&bytes).ok().map(|(header, _)| header.stanzas.iter().any(|s| s.kind == "scrypt")))
+        .unwrap_or(false)
+}
+
+/// Decrypts an age v1 file, binary or ASCII armored.
+pub fn decrypt(bytes: &[u8], identities: &Identities) -> io::Result<Vec<u8>> {
+    let bytes = dearmor(bytes)?;
+    let (header, payload) = read_header(&bytes)?;
+
+    let has_scrypt = header.stanzas.iter().any(|stanza| stanza.kind == "scrypt");
+    if has_scrypt && header.stanzas.len() > 1 {
+        return Err(invalid_data("a passphrase protected file cannot have other recipients"));
+    }
+
+    let mut file_key = None;
+    match identities {
+        Identities::Keys(_) if has_scrypt => {
+            return Err(invalid_data("the file is protected with a passphrase, not encrypted to an identity"));
+        }
+        Identities::Keys(keys) => {
+            for stanza in header.stanzas.iter().filter(|stanza| stanza.kind == "X25519") {
+                for key in keys {
+                    if file_key.is_none() {
+                        file_key = unwrap_x25519(stanza, key)?;
+                    }
+                }
+            }
+        }
+        Identities::Passphrase(_) if !has_scrypt => {
+            return Err(invalid_data("the file is encrypted to recipients, an identity file is needed to open it"));
+        }
+        Identities::Passphrase(passphrase) => {
+            file_key = unwrap_scrypt(&header.stanzas[0], passphrase)?;
+        }
+    }
+
+    let Some(file_key) = file_key else {
+        return Err(invalid_data(match identities {
+            Identities::Keys(_) => "the file is not encrypted to any of the identities",
+            Identities::Passphrase(_) => "wrong passphrase",
+        }));
+    };
+
+    header_mac(&file_key, &header.mac_input)
+        .verify_slice(&header.mac)
+        .map_err(|_| invalid_data("the header was modified"))?;
+
+    if payload.len() < 16 {
+        return Err(invalid_data("truncated payload"));
+    }
+    let (nonce, mut payload) = payload.split_at(16);
+    let cipher = ChaCha20Poly1305::new(&hkdf(&file_key, nonce, b"payload").into());
+
+    let mut plaintext = Vec::new();
+    let mut counter = 0u64;
+    loop {
+        let size = payload.len().min(CHUNK_SIZE + TAG_SIZE);
+        let (chunk, rest) = payload.split_at(size);
+        let last = rest.is_empty();
+        if size < TAG_SIZE || (last && size == TAG_SIZE && counter > 0) {
+            return Err(invalid_data("truncated payload"));
+        }
+
+        let decrypted = cipher
+            .decrypt(&chunk_nonce(counter, last), chunk)
+            .map_err(|_| invalid_data("the payload was modified or truncated"))?;
+        plaintext.extend_from_slice(&decrypted);
+
+        if last {
+            return Ok(plaintext);
+        }
+        payload = rest;
+        counter += 1;
+    }
+}
+
+/// Encrypts `entries` for `recipients`, as JSON inside the age file. Only the current
+/// passwords are shared, not the previous ones nor a pending rotation.
+pub fn write_entries(entries: &[VaultEntry], recipients: &Recipients) -> io::Result<Vec<u8>> {
+    let entries = entries
+        .iter()
+        .map(|entry| VaultEntry { password_history: Vec::new(), pending_rotation: None, ..entry.clone() })
+        .collect();
+    let json = serde_json::to_vec(&SharedEntries { entries }).map_err(io::Error::other)?;
+    encrypt(&json, recipients)
+}
+
+/// Decrypts entries written by `write_entries`.
+pub fn read_entries(bytes: &[u8], identities: &Identities) -> io::Result<Vec<VaultEntry>> {
+    let json = decrypt(bytes, identities)?;
+    let shared: SharedEntries = serde_json::from_slice(&json)
+        .map_err(|e| invalid_data(format!("the age file does not hold exported entries: {e}")))?;
+
+    Ok(shared.entries)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_entry::PasswordVersion;
+
+    // Written by the reference implementation, the age crate: a binary file encrypted to
+    // the identity, and an armored one with the passphrase and a work factor of 10.
+    const IDENTITY: &str = include_str!("../testdata/age-identity.txt");
+    const X25519_FILE: &[u8] = include_bytes!("../testdata/age-x25519.age");
+    const SCRYPT_FILE: &[u8] = include_bytes!("../testdata/age-scrypt.age");
+    const PASSPHRASE: &str = "fixture passphrase";
+
+    /// The plaintext of `X25519_FILE`, two chunks long.
+    fn plaintext() -> Vec<u8> {
+        (0..70_000).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn identities() -> Identities {
+        Identities::Keys(parse_identities(IDENTITY).unwrap())
+    }
+
+    #[test]
+    fn decrypts_reference_x25519_file() {
+        assert!(!is_passphrase_encrypted(X25519_FILE));
+        assert_eq!(decrypt(X25519_FILE, &identities()).unwrap(), plaintext());
+
+        let other = Identities::Keys(vec![StaticSecret::random_from_rng(OsRng)]);
+        let error = decrypt(X25519_FILE, &other).unwrap_err();
+        assert_eq!(error.to_string(), "the file is not encrypted to any of the identities");
+    }
+
+    #[test]
+    fn decrypts_reference_scrypt_file() {
+        assert!(is_passphrase_encrypted(SCRYPT_FILE));
+        assert_eq!(decrypt(SCRYPT_FILE, &Identities::Passphrase(PASSPHRASE.to_string())).unwrap(), b"a short secret\n");
+
+        let error = decrypt(SCRYPT_FILE, &Identities::Passphrase("wrong".to_string())).unwrap_err();
+        assert_eq!(error.to_string(), "wrong passphrase");
+        assert!(decrypt(SCRYPT_FILE, &identities()).is_err());
+    }
+
+    #[test]
+    fn detects_modified_and_truncated_payloads() {
+        let mut modified = X25519_FILE.to_vec();
+        let last = modified.len() - 1;
+        modified[last] ^= 1;
+        let error = decrypt(&modified, &identities()).unwrap_err();
+        assert_eq!(error.to_string(), "the payload was modified or truncated");
+
+        // Without its last chunk, the first one is taken for the last and fails.
+        let truncated = &X25519_FILE[..X25519_FILE.len() - (70_000 - CHUNK_SIZE) - TAG_SIZE];
+        let error = decrypt(truncated, &identities()).unwrap_err();
+        assert_eq!(error.to_string(), "the payload was modified or truncated");
+    }
+
+    #[test]
+    fn detects_a_modified_header() {
+        let mac_line = X25519_FILE.windows(5).position(|w| w == b"\n--- ").unwrap() + 5;
+
+        let mut modified = X25519_FILE.to_vec();
+        modified[mac_line] = if modified[mac_line] == b'A' { b'B' } else { b'A' };
+        let error = decrypt(&modified, &identities()).unwrap_err();
+        assert_eq!(error.to_string(), "the header was modified");
+    }
+
+    #[test]
+    fn round_trips_to_recipients() {
+        let identity = StaticSecret::random_from_rng(OsRng);
+        let other = StaticSecret::random_from_rng(OsRng);
+        let recipients = Recipients::Keys(vec![PublicKey::from(&other), PublicKey::from(&identity)]);
+
+        for plaintext in [Vec::new(), b"short".to_vec(), plaintext(), vec![7; CHUNK_SIZE]] {
+            let encrypted = encrypt(&plaintext, &recipients).unwrap();
+            assert_eq!(decrypt(&encrypted, &Identities::Keys(vec![identity.clone()])).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn shares_only_current_passwords() {
+        let mut entry = VaultEntry::new("GitHub".to_string(), None, b"old".to_vec());
+        entry.retire_password();
+        entry.password = b"current".to_vec();
+        entry.pending_rotation = Some(PasswordVersion { password: b"next".to_vec(), derivation: None, time: 1 });
+
+        let identity = StaticSecret::random_from_rng(OsRng);
+        let encrypted = write_entries(&[entry], &Recipients::Keys(vec![PublicKey::from(&identity)])).unwrap();
+        let [shared] = read_entries(&encrypted, &Identities::Keys(vec![identity])).unwrap().try_into().unwrap();
+        assert_eq!(shared.password, b"current");
+        assert!(shared.password_history.is_empty() && shared.pending_rotation.is_none());
+    }
+
+    #[test]
+    fn parses_keys() {
+        let recipient = "age1xnh08xnhkjy4wqckzu0ynlx4ng05x6jaa9uede3wmppnergw4sfq7l7pvk";
+        let [identity] = parse_identities(IDENTITY).unwrap().try_into().ok().unwrap();
+        assert_eq!(parse_recipient(recipient).unwrap(), PublicKey::from(&identity));
+
+        assert_eq!(parse_recipients(&format!("# team\n{recipient}\n\n")).unwrap().len(), 1);
+        assert!(parse_recipient("age1notakey").is_err());
+        assert!(parse_identities("# only a comment\n").is_err());
+    }
+}
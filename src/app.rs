@@ -1,24 +1,52 @@
-use crossterm::event::{ self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers };
-use ratatui::{ DefaultTerminal, Frame, layout::{ Constraint, Layout }, widgets::{ Block } };
-use serde::{ Deserialize, Serialize };
+use crossterm::{
+    clipboard::CopyToClipboard,
+    event::{ self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers },
+};
+use ratatui::{ DefaultTerminal, Frame, layout::{ Constraint, Layout }, widgets::{ Block, Paragraph } };
 use strum::{ Display, EnumIter, FromRepr };
-use std::{ env };
-use std::{ fs::{ File }, io::{ self, Read }, path::PathBuf };
+use std::{ io, path::PathBuf };
 
 use crate::{
+    audit_log::AuditEventKind,
+    config::{ Config, DEFAULT_MIN_MASTER_SCORE },
+    expiry::{ self, ExpiringEntry },
+    import,
+    rotation::{ self, DueEntry, RotationPolicy },
+    merge::Side,
+    passphrase::WordList,
     ui::{
+        audit_log::{ AuditLogWidget, AuditLogWidgetState },
         center_left::{ CenterLeftWidget, CenterLeftWidgetState },
+        center_right::{ CenterRightWidget, CenterRightWidgetState },
+        entry_form::{ EntryForm, EntryFormState, FormField },
+        expiry_banner::ExpiryBanner,
+        generator_dialog::{ GeneratorDialog, GeneratorDialogState },
+        rotation::{ RotationWidget, RotationWidgetState },
+        security_report::{ SecurityReportWidget, SecurityReportWidgetState },
         top::{ TopWidget, TopWidgetState },
     },
+    query::{ Query, QueryError },
+    search::SearchResult,
+    security_report::{ ReportSettings, SecurityReport },
     vault::Vault,
+    vault_entry::VaultEntry,
 };
 
+/// Index of the security report tab in the top menu.
+pub const AUDIT_TAB: usize = 2;
+/// Index of the rotation tab in the top menu.
+pub const ROTATION_TAB: usize = 3;
+/// Index of the audit log tab in the top menu.
+pub const LOG_TAB: usize = 4;
+const TAB_COUNT: usize = 5;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppState {
     AskMasterPassword,
     NoConfigFound,
     CreateNewVault,
     OpenExistingVault,
+    VaultUnlocked,
     Exit,
 }
 
@@ -31,54 +59,63 @@ enum SelectedTab {
     Configuration,
 }
 
-enum FileType {
-    Config,
-    Vault,
-}
-
-fn get_path(file_type: FileType) -> PathBuf {
-    let file_name = match file_type {
-        FileType::Config => "config.json",
-        FileType::Vault => "vault.bin",
-    };
-
-    #[cfg(target_os = "windows")]
-    {
-        PathBuf::from(env::var("LOCALAPPDATA").expect("Could not get LOCALAPPDATA"))
-            .join("PasswordManager")
-            .join(file_name)
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        PathBuf::from(env::var("HOME").expect("Could not get HOME"))
-            .join("Library")
-            .join("Application Support")
-            .join("PasswordManager")
-            .join(file_name)
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        PathBuf::from(env::var("HOME").expect("Could not get HOME"))
-            .join(".local")
-            .join("share")
-            .join("PasswordManager")
-            .join(file_name)
-    }
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct Config {
-    vault_path: PathBuf,
-}
-
 pub enum Message {
     NextTab,
     PreviousTab,
     SetFocusedWidget(FocusedWidget),
     UpdateState(AppState),
     EnterDir(PathBuf),
+    PasswordInput(char),
+    PasswordBackspace,
+    SubmitPassword,
+    SearchInput(char),
+    SearchBackspace,
+    ClearSearch,
+    ToggleExpiringFilter,
+    DismissExpiryBanner,
+    SelectNextEntry,
+    SelectPreviousEntry,
+    SelectNextConflict,
+    SelectPreviousConflict,
+    ResolveConflict(Side),
+    ToggleReveal,
+    CopyPassword,
+    SelectNextFinding,
+    SelectPreviousFinding,
+    ReloadSecurityReport,
+    SelectNextDueEntry,
+    SelectPreviousDueEntry,
+    StartRotation,
+    ConfirmRotation,
+    CancelRotation,
+    CopyPendingPassword,
+    SelectNextAuditEvent,
+    SelectPreviousAuditEvent,
+    ReloadAuditLog,
+    ExportAuditLog,
+    OpenEntryForm,
+    EditEntry,
+    DeleteEntry,
+    CloseEntryForm,
+    SubmitEntryForm,
+    FormInput(char),
+    FormBackspace,
+    FormNextField,
+    FormPreviousField,
+    FormTogglePassword,
+    FormToggleDerivation,
+    FormBumpCounter,
+    OpenGenerator,
+    CloseGenerator,
+    AcceptGenerated,
+    Regenerate,
+    GeneratorNextOption,
+    GeneratorPreviousOption,
+    GeneratorAdjust(isize),
+    GeneratorToggle,
+    /// A character typed in the symbol set or separator, `None` for backspace.
+    GeneratorTextInput(Option<char>),
+    SuggestPassphrase,
     Quit,
 }
 
@@ -97,8 +134,17 @@ impl FocusedWidget {
     ) -> Option<Message> {
         return match self {
             FocusedWidget::Top => TopWidget::handle_key_event(key_event),
+            _ if app_state.top_state.selected_tab == AUDIT_TAB => {
+                SecurityReportWidget::handle_key_event(key_event, app_state)
+            }
+            _ if app_state.top_state.selected_tab == ROTATION_TAB => {
+                RotationWidget::handle_key_event(key_event, app_state)
+            }
+            _ if app_state.top_state.selected_tab == LOG_TAB => {
+                AuditLogWidget::handle_key_event(key_event, app_state)
+            }
             FocusedWidget::CenterLeft => CenterLeftWidget::handle_key_event(key_event, app_state),
-            FocusedWidget::CenterRight => None,
+            FocusedWidget::CenterRight => CenterRightWidget::handle_key_event(key_event, app_state),
         };
     }
 }
@@ -108,38 +154,446 @@ pub struct App {
     pub state: AppState,
     pub top_state: TopWidgetState,
     pub center_left_state: CenterLeftWidgetState,
+    pub center_right_state: CenterRightWidgetState,
+    pub security_report_state: SecurityReportWidgetState,
+    pub rotation_state: RotationWidgetState,
+    pub audit_log_state: AuditLogWidgetState,
+    /// The add entry form, shown in place of the entry details while open.
+    pub entry_form: Option<EntryFormState>,
+    pub generator_state: GeneratorDialogState,
     pub focused_widget: FocusedWidget,
     pub vault: Option<Vault>,
+    /// Where the vault is opened from or created at, see `config::resolve_vault_path`.
+    pub vault_path: PathBuf,
+    /// Last error or notice, shown in the bottom bar.
+    pub status: Option<String>,
+    /// Entries expired or expiring soon at unlock, shown above the vault until dismissed.
+    pub expiry_banner: Vec<ExpiringEntry>,
     config: Option<Config>,
 }
 
 impl App {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let config_path = get_path(FileType::Config);
-
-        let (state, config) = if config_path.exists() {
-            let mut file: File = File::open(config_path)?;
-
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)?;
-
-            let config: Config = serde_json::from_str(&contents)?;
-
-            (AppState::AskMasterPassword, Some(config))
+    pub fn new(vault_path: PathBuf, config: Option<Config>) -> Result<Self, Box<dyn std::error::Error>> {
+        let (state, focused_widget) = if Vault::file_exists(&vault_path) {
+            (AppState::AskMasterPassword, FocusedWidget::CenterLeft)
         } else {
-            (AppState::NoConfigFound, None)
+            (AppState::NoConfigFound, FocusedWidget::Top)
         };
 
         Ok(App {
             vault: None,
-            state: state,
-            config: config,
+            vault_path,
+            status: None,
+            expiry_banner: Vec::new(),
+            state,
+            config,
             top_state: TopWidgetState::new(),
             center_left_state: CenterLeftWidgetState::new(),
-            focused_widget: FocusedWidget::Top,
+            center_right_state: CenterRightWidgetState::new(),
+            security_report_state: SecurityReportWidgetState::new(),
+            rotation_state: RotationWidgetState::new(),
+            audit_log_state: AuditLogWidgetState::new(),
+            entry_form: None,
+            generator_state: GeneratorDialogState::new(),
+            focused_widget,
         })
     }
 
+    /// Unlocks or creates the vault at `vault_path` with the typed master password.
+    fn submit_password(&mut self) {
+        let master_password = std::mem::take(&mut self.center_left_state.password_input);
+        self.center_left_state.suggested_passphrase = None;
+
+        let result = match self.state {
+            AppState::AskMasterPassword => Vault::open_existing(&self.vault_path, &master_password),
+            AppState::CreateNewVault => {
+                Vault::create_at(&self.vault_path, &master_password, self.min_master_score())
+            }
+            _ => {
+                return;
+            }
+        };
+
+        match result {
+            Ok(vault) => {
+                // The first vault created becomes the default for the next launches.
+                if self.state == AppState::CreateNewVault && self.config.is_none() {
+                    let config = Config {
+                        vault_path: self.vault_path.clone(),
+                        min_master_score: DEFAULT_MIN_MASTER_SCORE,
+                        rotation: RotationPolicy::default(),
+                        expiry_warning_days: expiry::DEFAULT_WARNING_DAYS,
+                        pass_decrypt_command: None,
+                    };
+                    if let Err(e) = config.save() {
+                        self.status = Some(format!("Could not save config: {e}"));
+                    }
+                    self.config = Some(config);
+                }
+
+                if vault.is_read_only() {
+                    self.status = Some(
+                        "Opened read-only: another process has this vault open for writing".to_string()
+                    );
+                } else if !vault.conflicts().is_empty() {
+                    self.status = Some(
+                        format!("{} merge conflicts to resolve, Ctrl+→ to review them", vault.conflicts().len())
+                    );
+                }
+
+                self.expiry_banner = expiry::expiring_entries(vault.get_entries(), Some(self.expiry_warning_days()));
+                self.vault = Some(vault);
+                self.state = AppState::VaultUnlocked;
+                self.focused_widget = FocusedWidget::CenterLeft;
+            }
+            Err(e) => {
+                self.status = Some(format!("{}: {e}", self.vault_path.display()));
+            }
+        }
+    }
+
+    pub fn min_master_score(&self) -> u8 {
+        self.config.as_ref().map_or(DEFAULT_MIN_MASTER_SCORE, |c| c.min_master_score)
+    }
+
+    pub fn expiry_warning_days(&self) -> u64 {
+        self.config.as_ref().map_or(expiry::DEFAULT_WARNING_DAYS, |c| c.expiry_warning_days)
+    }
+
+    fn resolve_conflict(&mut self, side: Side) {
+        let Some(vault) = self.vault.as_mut() else {
+            return;
+        };
+
+        if vault.conflicts().is_empty() {
+            return;
+        }
+
+        let index = self.center_right_state.conflict_state
+            .selected()
+            .unwrap_or(0)
+            .min(vault.conflicts().len() - 1);
+
+        self.status = Some(match vault.resolve_conflict(index, side) {
+            Ok(()) => format!("{} merge conflicts left", vault.conflicts().len()),
+            Err(e) => format!("Could not save the vault: {e}"),
+        });
+    }
+
+    /// The entry highlighted in the search results, if any.
+    pub fn selected_entry(&self) -> Option<&VaultEntry> {
+        let results = self.search_results().ok()?;
+        let selected = self.center_left_state.list_state.selected()?.min(results.len().checked_sub(1)?);
+
+        self.vault.as_ref()?.get_entries().get(results[selected].index)
+    }
+
+    /// Shows or hides the password of the selected entry. Showing it is audited.
+    fn toggle_reveal(&mut self) {
+        let Some(entry) = self.selected_entry().cloned() else {
+            return;
+        };
+
+        if self.center_right_state.revealed.as_deref() == Some(entry.id.as_str()) {
+            self.center_right_state.revealed = None;
+            return;
+        }
+
+        let vault = self.vault.as_ref().expect("an entry is selected");
+        match vault.record(AuditEventKind::Reveal, Some(&entry), None) {
+            Ok(()) => {
+                self.center_right_state.revealed = Some(entry.id);
+            }
+            Err(e) => {
+                self.status = Some(format!("Could not write the audit log: {e}"));
+            }
+        }
+    }
+
+    /// Copies the password of the selected entry through the terminal (OSC 52).
+    fn copy_password(&mut self) {
+        let Some(entry) = self.selected_entry().cloned() else {
+            return;
+        };
+
+        let vault = self.vault.as_ref().expect("an entry is selected");
+        if let Err(e) = vault.record(AuditEventKind::Copy, Some(&entry), None) {
+            self.status = Some(format!("Could not write the audit log: {e}"));
+            return;
+        }
+
+        self.status = Some(
+            match crossterm::execute!(io::stdout(), CopyToClipboard::to_clipboard_from(&entry.password)) {
+                Ok(()) => format!("Copied the password of {}", entry.service),
+                Err(e) => format!("Could not copy the password: {e}"),
+            }
+        );
+    }
+
+    fn submit_entry_form(&mut self) {
+        let (Some(form), Some(vault)) = (self.entry_form.as_mut(), self.vault.as_mut()) else {
+            return;
+        };
+
+        let entry = match form.to_entry() {
+            Ok(entry) => entry,
+            Err(e) => {
+                form.error = Some(e);
+                return;
+            }
+        };
+
+        let service = entry.service.clone();
+        let (result, done) = if form.original.is_some() {
+            (vault.update_entry(entry), "Updated")
+        } else {
+            (vault.add_entry(entry), "Added")
+        };
+
+        match result {
+            Ok(()) => {
+                self.entry_form = None;
+                self.status = Some(format!("{done} {service}"));
+            }
+            Err(e) => {
+                form.error = Some(format!("Could not save the vault: {e}"));
+            }
+        }
+    }
+
+    /// Shows or hides the password in the entry form. Showing the stored password of an
+    /// edited entry is audited like revealing it.
+    fn toggle_form_password(&mut self) {
+        let Some(form) = self.entry_form.as_mut() else {
+            return;
+        };
+
+        if
+            !form.show_password &&
+            let (Some(original), Some(vault)) = (&form.original, &self.vault) &&
+            let Err(e) = vault.record(AuditEventKind::Reveal, Some(original), None)
+        {
+            self.status = Some(format!("Could not write the audit log: {e}"));
+            return;
+        }
+
+        form.show_password = !form.show_password;
+    }
+
+    /// Recomputes the password of a derived entry in the form after its site, username or
+    /// counter changed.
+    fn refresh_derived_password(&mut self) {
+        let (Some(form), Some(vault)) = (self.entry_form.as_mut(), self.vault.as_ref()) else {
+            return;
+        };
+
+        form.sync_derivation();
+        let Some(params) = &form.derivation else {
+            return;
+        };
+
+        match vault.derive_password(params) {
+            Ok(password) => {
+                form.set_value(FormField::Password, password);
+                form.error = None;
+            }
+            Err(e) => {
+                form.set_value(FormField::Password, String::new());
+                form.error = Some(e);
+            }
+        }
+    }
+
+    /// Fills the new master password with a passphrase that follows the passphrase
+    /// settings of the generator.
+    fn suggest_passphrase(&mut self) {
+        let generator = &self.generator_state;
+        match generator.passphrase.generate(WordList::builtin(generator.word_list)) {
+            Ok(passphrase) => {
+                self.center_left_state.password_input = passphrase.clone();
+                self.center_left_state.suggested_passphrase = Some(passphrase);
+            }
+            Err(e) => {
+                self.status = Some(e);
+            }
+        }
+    }
+
+    /// Puts the generated password in the add entry form, opening it if needed.
+    fn accept_generated(&mut self) {
+        let Ok(password) = self.generator_state.preview.clone() else {
+            return;
+        };
+
+        if self.vault.is_none() {
+            return;
+        }
+
+        let form = self.entry_form.get_or_insert_with(EntryFormState::new);
+        form.derivation = None;
+        form.set_value(FormField::Password, password);
+        form.show_password = true;
+
+        self.generator_state.open = false;
+        self.generator_state.regenerate();
+        self.focused_widget = FocusedWidget::CenterRight;
+    }
+
+    pub fn rotation_policy(&self) -> RotationPolicy {
+        self.config.as_ref().map(|c| c.rotation.clone()).unwrap_or_default()
+    }
+
+    /// Entries of the rotation tab, pending rotations first.
+    pub fn due_entries(&self) -> Vec<DueEntry> {
+        match &self.vault {
+            Some(vault) => rotation::due_entries(vault.get_entries(), &self.rotation_policy()),
+            None => Vec::new(),
+        }
+    }
+
+    /// The entry highlighted in the rotation tab.
+    fn selected_due_entry(&self) -> Option<VaultEntry> {
+        let due = self.due_entries();
+        let selected = self.rotation_state.list_state.selected()?.min(due.len().checked_sub(1)?);
+
+        self.vault.as_ref()?.get_entries().get(due[selected].index).cloned()
+    }
+
+    /// Generates the next password of the selected entry with the generator settings.
+    fn start_rotation(&mut self) {
+        let Some(entry) = self.selected_due_entry() else {
+            return;
+        };
+        let vault = self.vault.as_mut().expect("an entry is selected");
+
+        self.status = Some(match vault.start_rotation(&entry.id, &self.generator_state.policy) {
+            Ok(()) => format!("New password ready for {}, copy it with c and confirm with y once changed", entry.service),
+            Err(e) => format!("Could not rotate {}: {e}", entry.service),
+        });
+    }
+
+    /// Confirms or cancels the pending rotation of the selected entry.
+    fn finish_rotation(&mut self, confirm: bool) {
+        let Some(entry) = self.selected_due_entry() else {
+            return;
+        };
+        let vault = self.vault.as_mut().expect("an entry is selected");
+
+        let (result, done) = if confirm {
+            (vault.confirm_rotation(&entry.id), "Rotated")
+        } else {
+            (vault.cancel_rotation(&entry.id), "Cancelled the rotation of")
+        };
+
+        self.status = Some(match result {
+            Ok(()) => format!("{done} {}", entry.service),
+            Err(e) => format!("{}: {e}", entry.service),
+        });
+    }
+
+    /// Asks to press `d` again, then deletes the selected entry.
+    fn delete_selected_entry(&mut self) {
+        let Some(entry) = self.selected_entry().cloned() else {
+            return;
+        };
+        if self.center_right_state.pending_delete.take().as_deref() != Some(entry.id.as_str()) {
+            self.status = Some(format!("Press d again to delete {}", import::label(&entry)));
+            self.center_right_state.pending_delete = Some(entry.id);
+            return;
+        }
+
+        let vault = self.vault.as_mut().expect("an entry is selected");
+        self.status = Some(match vault.delete_entry(&entry.id) {
+            Ok(()) => format!("Deleted {}", import::label(&entry)),
+            Err(e) => format!("Could not delete {}: {e}", entry.service),
+        });
+    }
+
+    /// Copies the new password of a pending rotation, to paste it on the site.
+    fn copy_pending_password(&mut self) {
+        let Some(entry) = self.selected_due_entry() else {
+            return;
+        };
+        let Some(pending) = &entry.pending_rotation else {
+            self.status = Some(format!("No rotation is pending for {}, start one with t", entry.service));
+            return;
+        };
+
+        let vault = self.vault.as_ref().expect("an entry is selected");
+        if let Err(e) = vault.record(AuditEventKind::Copy, Some(&entry), Some("pending password".to_string())) {
+            self.status = Some(format!("Could not write the audit log: {e}"));
+            return;
+        }
+
+        self.status = Some(
+            match crossterm::execute!(io::stdout(), CopyToClipboard::to_clipboard_from(&pending.password)) {
+                Ok(()) => format!("Copied the new password of {}", entry.service),
+                Err(e) => format!("Could not copy the password: {e}"),
+            }
+        );
+    }
+
+    /// Refreshes the content of the tab just opened.
+    fn load_tab(&mut self) {
+        match self.top_state.selected_tab {
+            AUDIT_TAB => self.reload_security_report(),
+            LOG_TAB => self.reload_audit_log(),
+            _ => {}
+        }
+    }
+
+    fn reload_security_report(&mut self) {
+        self.security_report_state.report = self.vault
+            .as_ref()
+            .map(|vault| {
+                let mut report = SecurityReport::build(vault.get_entries(), ReportSettings::default());
+                report.add_plaintext_exports(vault.plaintext_exports());
+                report
+            });
+        self.security_report_state.list_state.select(Some(0));
+    }
+
+    fn reload_audit_log(&mut self) {
+        self.audit_log_state.report = self.vault.as_ref().map(|vault| vault.read_audit_log().map_err(|e| e.to_string()));
+        self.audit_log_state.list_state.select(Some(0));
+    }
+
+    fn export_audit_log(&mut self) {
+        let Some(vault) = self.vault.as_ref() else {
+            return;
+        };
+
+        let mut file_name = vault.path().file_name().unwrap_or_default().to_os_string();
+        file_name.push(".audit.json");
+        let path = vault.path().with_file_name(file_name);
+
+        self.status = Some(match vault.export_audit_log(&path) {
+            Ok(report) => format!("Exported {} events to {}", report.events.len(), path.display()),
+            Err(e) => format!("Could not export the audit log: {e}"),
+        });
+        self.reload_audit_log();
+    }
+
+    /// Entries of the unlocked vault matching the search bar, best match first.
+    pub fn search_results(&self) -> Result<Vec<SearchResult>, QueryError> {
+        let query = Query::parse(&self.center_left_state.search_query)?;
+
+        let Some(vault) = &self.vault else {
+            return Ok(Vec::new());
+        };
+
+        let entries = vault.get_entries();
+        let mut results = query.find(entries);
+
+        // The expiring filter keeps entries with an expiry date, soonest first.
+        if self.center_left_state.expiring_only {
+            results.retain(|result| entries[result.index].expires.is_some());
+            results.sort_by_key(|result| entries[result.index].expires);
+        }
+
+        Ok(results)
+    }
+
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
         while self.state != AppState::Exit {
             terminal.draw(|frame| self.draw(frame))?;
@@ -160,16 +614,53 @@ impl App {
             Constraint::Length(3),
         ]).areas(frame.area());
 
+        let (banner, vault_area) = if self.expiry_banner.is_empty() {
+            (None, center)
+        } else {
+            let [banner, rest] = Layout::vertical([
+                Constraint::Length(ExpiryBanner::height(&self.expiry_banner)),
+                Constraint::Fill(1),
+            ]).areas(center);
+            (Some(banner), rest)
+        };
+
         let [center_left, center_right] = Layout::horizontal([
             Constraint::Percentage(33),
             Constraint::Fill(1),
-        ]).areas(center);
+        ]).areas(vault_area);
 
         frame.render_stateful_widget(TopWidget, top, self);
-        frame.render_stateful_widget(CenterLeftWidget, center_left, self);
+        if self.top_state.selected_tab == AUDIT_TAB {
+            frame.render_stateful_widget(SecurityReportWidget, center, self);
+        } else if self.top_state.selected_tab == ROTATION_TAB {
+            frame.render_stateful_widget(RotationWidget, center, self);
+        } else if self.top_state.selected_tab == LOG_TAB {
+            frame.render_stateful_widget(AuditLogWidget, center, self);
+        } else {
+            if let Some(banner) = banner {
+                frame.render_stateful_widget(ExpiryBanner, banner, self);
+            }
+            frame.render_stateful_widget(CenterLeftWidget, center_left, self);
+            if self.entry_form.is_some() {
+                frame.render_stateful_widget(EntryForm, center_right, self);
+            } else {
+                frame.render_stateful_widget(CenterRightWidget, center_right, self);
+            }
+        }
 
-        frame.render_widget(Block::bordered().title("center right"), center_right);
-        frame.render_widget(Block::bordered().title("bottom"), bottom);
+        if self.generator_state.open {
+            frame.render_stateful_widget(GeneratorDialog, GeneratorDialog::area(frame.area()), self);
+        }
+        frame.render_widget(
+            Paragraph::new(self.status.clone().unwrap_or_default()).block(
+                Block::bordered().title(match &self.vault {
+                    Some(vault) if vault.is_read_only() => format!("{} [read-only]", vault.path().display()),
+                    Some(vault) => vault.path().display().to_string(),
+                    None => self.vault_path.display().to_string(),
+                })
+            ),
+            bottom
+        );
     }
 
     fn handle_events(&mut self) -> io::Result<Option<Message>> {
@@ -188,6 +679,11 @@ impl App {
             KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
                 Some(Message::Quit)
             }
+            _ if self.generator_state.open => GeneratorDialog::handle_key_event(key_event, self),
+            _ if self.entry_form.is_some() && self.focused_widget == FocusedWidget::CenterRight => {
+                EntryForm::handle_key_event(key_event)
+            }
+            KeyCode::Esc if !self.expiry_banner.is_empty() => Some(Message::DismissExpiryBanner),
             _ => {
                 return self.focused_widget.handle_key_event(key_event, self);
             }
@@ -195,12 +691,18 @@ impl App {
     }
 
     fn update(&mut self, message: Message) -> Option<Message> {
+        if !matches!(message, Message::DeleteEntry) {
+            self.center_right_state.pending_delete = None;
+        }
+
         match message {
             Message::NextTab => {
-                self.top_state.selected_tab = (self.top_state.selected_tab + 1) % 2;
+                self.top_state.selected_tab = (self.top_state.selected_tab + 1) % TAB_COUNT;
+                self.load_tab();
             }
             Message::PreviousTab => {
-                self.top_state.selected_tab = (self.top_state.selected_tab + 2 - 1) % 2;
+                self.top_state.selected_tab = (self.top_state.selected_tab + TAB_COUNT - 1) % TAB_COUNT;
+                self.load_tab();
             }
             Message::SetFocusedWidget(widget) => {
                 self.focused_widget = widget;
@@ -211,6 +713,182 @@ impl App {
             Message::EnterDir(dir_path) => {
                 self.center_left_state.current_dir = dir_path;
             }
+            Message::PasswordInput(c) => {
+                self.center_left_state.password_input.push(c);
+            }
+            Message::PasswordBackspace => {
+                self.center_left_state.password_input.pop();
+            }
+            Message::SubmitPassword => {
+                self.submit_password();
+            }
+            Message::SearchInput(c) => {
+                self.center_left_state.search_query.push(c);
+                self.center_left_state.list_state.select(Some(0));
+            }
+            Message::SearchBackspace => {
+                self.center_left_state.search_query.pop();
+                self.center_left_state.list_state.select(Some(0));
+            }
+            Message::ClearSearch => {
+                self.center_left_state.search_query.clear();
+                self.center_left_state.list_state.select(Some(0));
+            }
+            Message::ToggleExpiringFilter => {
+                self.center_left_state.expiring_only = !self.center_left_state.expiring_only;
+                self.center_left_state.list_state.select(Some(0));
+            }
+            Message::DismissExpiryBanner => {
+                self.expiry_banner.clear();
+            }
+            Message::SelectNextEntry => {
+                self.center_left_state.list_state.select_next();
+            }
+            Message::SelectPreviousEntry => {
+                self.center_left_state.list_state.select_previous();
+            }
+            Message::SelectNextConflict => {
+                self.center_right_state.conflict_state.select_next();
+            }
+            Message::SelectPreviousConflict => {
+                self.center_right_state.conflict_state.select_previous();
+            }
+            Message::ResolveConflict(side) => {
+                self.resolve_conflict(side);
+            }
+            Message::ToggleReveal => {
+                self.toggle_reveal();
+            }
+            Message::CopyPassword => {
+                self.copy_password();
+            }
+            Message::SelectNextFinding => {
+                self.security_report_state.list_state.select_next();
+            }
+            Message::SelectPreviousFinding => {
+                self.security_report_state.list_state.select_previous();
+            }
+            Message::ReloadSecurityReport => {
+                self.reload_security_report();
+            }
+            Message::SelectNextDueEntry => {
+                self.rotation_state.list_state.select_next();
+            }
+            Message::SelectPreviousDueEntry => {
+                self.rotation_state.list_state.select_previous();
+            }
+            Message::StartRotation => {
+                self.start_rotation();
+            }
+            Message::ConfirmRotation => {
+                self.finish_rotation(true);
+            }
+            Message::CancelRotation => {
+                self.finish_rotation(false);
+            }
+            Message::CopyPendingPassword => {
+                self.copy_pending_password();
+            }
+            Message::SelectNextAuditEvent => {
+                self.audit_log_state.list_state.select_next();
+            }
+            Message::SelectPreviousAuditEvent => {
+                self.audit_log_state.list_state.select_previous();
+            }
+            Message::ReloadAuditLog => {
+                self.reload_audit_log();
+            }
+            Message::ExportAuditLog => {
+                self.export_audit_log();
+            }
+            Message::OpenEntryForm => {
+                self.entry_form = Some(EntryFormState::new());
+                self.focused_widget = FocusedWidget::CenterRight;
+            }
+            Message::EditEntry => {
+                if let Some(entry) = self.selected_entry() {
+                    self.entry_form = Some(EntryFormState::edit(entry));
+                    self.focused_widget = FocusedWidget::CenterRight;
+                }
+            }
+            Message::DeleteEntry => {
+                self.delete_selected_entry();
+            }
+            Message::CloseEntryForm => {
+                self.entry_form = None;
+            }
+            Message::SubmitEntryForm => {
+                self.submit_entry_form();
+            }
+            Message::FormInput(c) => {
+                if let Some(form) = self.entry_form.as_mut() {
+                    form.input(c);
+                }
+                self.refresh_derived_password();
+            }
+            Message::FormBackspace => {
+                if let Some(form) = self.entry_form.as_mut() {
+                    form.backspace();
+                }
+                self.refresh_derived_password();
+            }
+            Message::FormNextField => {
+                if let Some(form) = self.entry_form.as_mut() {
+                    form.next_field();
+                }
+            }
+            Message::FormPreviousField => {
+                if let Some(form) = self.entry_form.as_mut() {
+                    form.previous_field();
+                }
+            }
+            Message::FormTogglePassword => {
+                self.toggle_form_password();
+            }
+            Message::FormToggleDerivation => {
+                if let Some(form) = self.entry_form.as_mut() {
+                    form.toggle_derivation(&self.generator_state.policy);
+                }
+                self.refresh_derived_password();
+            }
+            Message::FormBumpCounter => {
+                if let Some(params) = self.entry_form.as_mut().and_then(|f| f.derivation.as_mut()) {
+                    params.counter += 1;
+                }
+                self.refresh_derived_password();
+            }
+            Message::OpenGenerator => {
+                self.generator_state.open = true;
+                self.generator_state.rules = self.entry_form.as_ref().and_then(EntryFormState::password_rules);
+                self.generator_state.regenerate();
+            }
+            Message::CloseGenerator => {
+                self.generator_state.open = false;
+            }
+            Message::AcceptGenerated => {
+                self.accept_generated();
+            }
+            Message::Regenerate => {
+                self.generator_state.regenerate();
+            }
+            Message::GeneratorNextOption => {
+                self.generator_state.list_state.select_next();
+            }
+            Message::GeneratorPreviousOption => {
+                self.generator_state.list_state.select_previous();
+            }
+            Message::GeneratorAdjust(delta) => {
+                self.generator_state.adjust(delta);
+            }
+            Message::GeneratorToggle => {
+                self.generator_state.toggle();
+            }
+            Message::GeneratorTextInput(input) => {
+                self.generator_state.edit_text(input);
+            }
+            Message::SuggestPassphrase => {
+                self.suggest_passphrase();
+            }
             Message::Quit => {
                 self.state = AppState::Exit;
             }
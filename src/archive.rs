@@ -0,0 +1,156 @@
+use std::io;
+
+use aes_gcm::{ Aes256Gcm, KeyInit, Nonce, aead::{ Aead, Payload } };
+use argon2::{ Algorithm, Argon2, Params, Version };
+use base64::{ Engine, engine::general_purpose::STANDARD };
+use rand::{ RngCore, rngs::OsRng };
+use serde::{ Deserialize, Serialize };
+
+use crate::{ timestamp, vault_entry::VaultEntry };
+
+const FORMAT: &str = "pmgr-archive";
+const VERSION: u32 = 1;
+
+/// Argon2id cost of new archives, above the vault's own as an archive may be copied
+/// anywhere.
+const MEMORY_KIB: u32 = 128 * 1024;
+const ITERATIONS: u32 = 3;
+const PARALLELISM: u32 = 1;
+/// Largest Argon2 memory accepted when reading an archive, so that a crafted file cannot
+/// exhaust the memory of the machine.
+const MAX_MEMORY_KIB: u32 = 2 * 1024 * 1024;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct KdfParams {
+    algorithm: String,
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+/// Everything in front of the ciphertext, authenticated with it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct ArchiveHeader {
+    format: String,
+    version: u32,
+    created: u64,
+    kdf: KdfParams,
+    cipher: String,
+    nonce: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Archive {
+    #[serde(flatten)]
+    header: ArchiveHeader,
+    data: String,
+}
+
+/// What an archive holds once decrypted. Merge conflicts and the audit log stay with
+/// the vault they belong to.
+#[derive(Debug, Deserialize, Serialize)]
+struct ArchiveData {
+    entries: Vec<VaultEntry>,
+}
+
+/// The entries of a decrypted archive, and when it was written.
+#[derive(Debug)]
+pub struct ArchiveContents {
+    pub created: u64,
+    pub entries: Vec<VaultEntry>,
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn derive_key(passphrase: &str, kdf: &KdfParams) -> io::Result<[u8; 32]> {
+    if kdf.algorithm != "argon2id" {
+        return Err(invalid_data(format!("unsupported key derivation function {}", kdf.algorithm)));
+    }
+    if kdf.memory_kib > MAX_MEMORY_KIB {
+        return Err(invalid_data(format!("the archive asks for {} MiB of memory to open", kdf.memory_kib / 1024)));
+    }
+
+    let salt = STANDARD.decode(&kdf.salt).map_err(|_| invalid_data("malformed salt"))?;
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| invalid_data(format!("invalid Argon2 parameters: {e}")))?;
+
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| invalid_data(format!("Argon2 failed: {e}")))?;
+
+    Ok(key)
+}
+
+/// Encrypts `entries` into a portable archive: JSON naming its own Argon2id salt and
+/// parameters, so that `passphrase` alone opens it, on any machine and whatever the
+/// master password of the vault it goes into.
+pub fn write(entries: &[VaultEntry], passphrase: &str) -> io::Result<Vec<u8>> {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let header = ArchiveHeader {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        created: timestamp::now(),
+        kdf: KdfParams {
+            algorithm: "argon2id".to_string(),
+            salt: STANDARD.encode(salt),
+            memory_kib: MEMORY_KIB,
+            iterations: ITERATIONS,
+            parallelism: PARALLELISM,
+        },
+        cipher: "aes-256-gcm".to_string(),
+        nonce: STANDARD.encode(nonce),
+    };
+
+    let key = derive_key(passphrase, &header.kdf)?;
+    let plaintext = serde_json::to_vec(&ArchiveData { entries: entries.to_vec() }).map_err(io::Error::other)?;
+    let aad = serde_json::to_vec(&header).map_err(io::Error::other)?;
+
+    let ciphertext = Aes256Gcm::new_from_slice(&key)
+        .expect("the key has the AES-256 size")
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plaintext, aad: &aad })
+        .map_err(|_| io::Error::other("encryption failed"))?;
+
+    let archive = Archive { header, data: STANDARD.encode(ciphertext) };
+    serde_json::to_vec_pretty(&archive).map_err(io::Error::other)
+}
+
+/// Decrypts an archive written by `write`.
+pub fn read(bytes: &[u8], passphrase: &str) -> io::Result<ArchiveContents> {
+    let archive: Archive = serde_json::from_slice(bytes).map_err(|e| invalid_data(format!("not an archive: {e}")))?;
+    let header = &archive.header;
+
+    if header.format != FORMAT {
+        return Err(invalid_data("not an archive of this password manager"));
+    }
+    if header.version > VERSION {
+        return Err(invalid_data(format!("archive version {} is newer than this program", header.version)));
+    }
+    if header.cipher != "aes-256-gcm" {
+        return Err(invalid_data(format!("unsupported cipher {}", header.cipher)));
+    }
+
+    let nonce = STANDARD.decode(&header.nonce).map_err(|_| invalid_data("malformed nonce"))?;
+    if nonce.len() != 12 {
+        return Err(invalid_data("malformed nonce"));
+    }
+    let ciphertext = STANDARD.decode(&archive.data).map_err(|_| invalid_data("malformed data"))?;
+
+    let key = derive_key(passphrase, &header.kdf)?;
+    let aad = serde_json::to_vec(header).map_err(io::Error::other)?;
+    let plaintext = Aes256Gcm::new_from_slice(&key)
+        .expect("the key has the AES-256 size")
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &aad })
+        .map_err(|_| invalid_data("wrong passphrase, or the archive was modified"))?;
+
+    let data: ArchiveData = serde_json::from_slice(&plaintext).map_err(|e| invalid_data(format!("invalid archive: {e}")))?;
+
+    Ok(ArchiveContents { created: header.created, entries: data.entries })
+}
@@ -0,0 +1,470 @@
+use std::env;
+use std::fs::{ File, OpenOptions };
+use std::io::{ self, Read, Seek, SeekFrom, Write };
+use std::path::{ Path, PathBuf };
+
+use aes_gcm::{ Aes256Gcm, KeyInit, Nonce, aead::{ Aead, Payload } };
+use rand::{ RngCore, rngs::OsRng };
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+use strum::Display;
+use x25519_dalek::{ EphemeralSecret, PublicKey, StaticSecret };
+
+use crate::{ timestamp, vault_entry::VaultEntry };
+
+const MAGIC: &str = "PMGR-AUDIT 1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Deserialize, Serialize)]
+pub enum AuditEventKind {
+    #[strum(to_string = "unlock")]
+    Unlock,
+    #[strum(to_string = "failed unlock")]
+    FailedUnlock,
+    #[strum(to_string = "reveal")]
+    Reveal,
+    #[strum(to_string = "copy")]
+    Copy,
+    #[strum(to_string = "add")]
+    Add,
+    #[strum(to_string = "edit")]
+    Edit,
+    #[strum(to_string = "delete")]
+    Delete,
+    #[strum(to_string = "export")]
+    Export,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuditEvent {
+    pub time: u64,
+    /// `user@host` of the process that recorded the event.
+    pub actor: String,
+    pub kind: AuditEventKind,
+    pub entry_id: Option<String>,
+    pub entry: Option<String>,
+    pub detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditEventKind, entry: Option<&VaultEntry>, detail: Option<String>) -> Self {
+        Self {
+            time: timestamp::now(),
+            actor: actor(),
+            kind,
+            entry_id: entry.map(|e| e.id.clone()),
+            entry: entry.map(|e| e.service.clone()),
+            detail,
+        }
+    }
+}
+
+fn actor() -> String {
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    let host = env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    format!("{user}@{host}")
+}
+
+/// One line of the log file. The event is sealed to the log public key with an
+/// ephemeral X25519 key, so appending does not need the master password.
+#[derive(Debug, Deserialize, Serialize)]
+struct Record {
+    seq: u64,
+    /// SHA-256 of the previous line, or of the header line for the first record.
+    prev: String,
+    epk: String,
+    nonce: String,
+    data: String,
+}
+
+/// Position of the last record, remembered in the vault to detect a truncated log.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Checkpoint {
+    pub seq: u64,
+    pub hash: String,
+}
+
+/// An event with its position in the log.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoggedEvent {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AuditReport {
+    pub events: Vec<LoggedEvent>,
+    /// Integrity problems found while reading, empty when the log is intact.
+    pub problems: Vec<String>,
+}
+
+impl AuditReport {
+    pub fn is_intact(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Append-only, encrypted and hash-chained log of vault accesses, stored next to the
+/// vault as `<vault>.audit`.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+pub fn log_path(vault_path: &Path) -> PathBuf {
+    let mut file_name = vault_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".audit");
+    vault_path.with_file_name(file_name)
+}
+
+fn hash_line(line: &str) -> String {
+    hex::encode(Sha256::digest(line.as_bytes()))
+}
+
+fn record_key(shared: &[u8; 32], epk: &PublicKey, public_key: &PublicKey) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"pmgr-audit");
+    hasher.update(shared);
+    hasher.update(epk.as_bytes());
+    hasher.update(public_key.as_bytes());
+    hasher.finalize().into()
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Secret key of a new log, kept in the vault. Only its public key is written to the log.
+pub fn generate_secret() -> [u8; 32] {
+    StaticSecret::random_from_rng(OsRng).to_bytes()
+}
+
+impl AuditLog {
+    pub fn for_vault(vault_path: &Path) -> Self {
+        Self { path: log_path(vault_path) }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+
+    /// Starts a new log for the key pair of `secret`, replacing any previous log.
+    pub fn create(&self, secret: &[u8; 32]) -> io::Result<()> {
+        let public_key = PublicKey::from(&StaticSecret::from(*secret));
+        let mut file = File::create(&self.path)?;
+        writeln!(file, "{MAGIC} {}", hex::encode(public_key.as_bytes()))
+    }
+
+    /// Reads the header line and the lines after it.
+    fn read_lines(file: &mut File) -> io::Result<(String, Vec<String>)> {
+        let mut contents = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut contents)?;
+
+        let mut lines = contents.lines().map(str::to_string);
+        let header = lines.next().ok_or_else(|| invalid_data("empty audit log"))?;
+
+        Ok((header, lines.collect()))
+    }
+
+    fn public_key(header: &str) -> io::Result<PublicKey> {
+        let key = header
+            .strip_prefix(MAGIC)
+            .map(str::trim)
+            .ok_or_else(|| invalid_data("not an audit log"))?;
+
+        let bytes: [u8; 32] = hex
+            ::decode(key)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| invalid_data("invalid audit log key"))?;
+
+        Ok(PublicKey::from(bytes))
+    }
+
+    /// Checks that the log can be appended to: that it exists, belongs to the key pair of
+    /// `secret` and ends with a readable record.
+    pub fn check(&self, secret: &[u8; 32]) -> io::Result<()> {
+        if !self.exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is missing", self.path.display())));
+        }
+
+        let mut file = File::open(&self.path)?;
+        let (header, lines) = Self::read_lines(&mut file)?;
+        if Self::public_key(&header)? != PublicKey::from(&StaticSecret::from(*secret)) {
+            return Err(invalid_data("the audit log does not belong to this vault"));
+        }
+        if let Some(line) = lines.last() && serde_json::from_str::<Record>(line).is_err() {
+            return Err(invalid_data("the last audit log record is corrupted"));
+        }
+
+        Ok(())
+    }
+
+    /// Moves a log that cannot be appended to out of the way, to
+    /// `<vault>.audit.damaged-<time>`, and returns where it went.
+    pub fn set_aside(&self) -> io::Result<PathBuf> {
+        let mut file_name = self.path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(format!(".damaged-{}", timestamp::now()));
+        let destination = self.path.with_file_name(file_name);
+
+        std::fs::rename(&self.path, &destination)?;
+        Ok(destination)
+    }
+
+    /// Seals `event` and appends it to the log.
+    ///
+    /// The log file is locked for the duration of the append so that several processes
+    /// can record events on the same vault.
+    pub fn append(&self, event: &AuditEvent) -> io::Result<Checkpoint> {
+        let mut file = OpenOptions::new().read(true).append(true).open(&self.path)?;
+        file.lock()?;
+
+        let (header, lines) = Self::read_lines(&mut file)?;
+        let public_key = Self::public_key(&header)?;
+
+        let (seq, prev) = match lines.last() {
+            Some(line) => {
+                let last: Record = serde_json
+                    ::from_str(line)
+                    .map_err(|_| invalid_data("the last audit log record is corrupted"))?;
+                (last.seq + 1, hash_line(line))
+            }
+            None => (1, hash_line(&header)),
+        };
+
+        let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+        let epk = PublicKey::from(&ephemeral);
+        let shared = ephemeral.diffie_hellman(&public_key);
+        let key = record_key(shared.as_bytes(), &epk, &public_key);
+
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+
+        let plaintext = serde_json::to_vec(event).map_err(io::Error::other)?;
+        let aad = format!("{seq}:{prev}");
+        let data = Aes256Gcm::new_from_slice(&key)
+            .expect("32 byte key")
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plaintext, aad: aad.as_bytes() })
+            .map_err(|_| io::Error::other("Encryption error"))?;
+
+        let record = Record {
+            seq,
+            prev,
+            epk: hex::encode(epk.as_bytes()),
+            nonce: hex::encode(nonce),
+            data: hex::encode(data),
+        };
+        let line = serde_json::to_string(&record).map_err(io::Error::other)?;
+        writeln!(file, "{line}")?;
+
+        Ok(Checkpoint { seq, hash: hash_line(&line) })
+    }
+
+    /// Last record of the log, as stored in the vault to detect truncation.
+    pub fn checkpoint(&self) -> io::Result<Checkpoint> {
+        let mut file = File::open(&self.path)?;
+        let (header, lines) = Self::read_lines(&mut file)?;
+
+        Ok(match lines.last() {
+            Some(line) => {
+                let seq = serde_json::from_str::<Record>(line).map_or(0, |r| r.seq);
+                Checkpoint { seq, hash: hash_line(line) }
+            }
+            None => Checkpoint { seq: 0, hash: hash_line(&header) },
+        })
+    }
+
+    /// Decrypts the whole log with the secret kept in the vault and checks its integrity:
+    /// the hash chain, the authenticity of each record and, against `checkpoint`, that no
+    /// records were cut from the end.
+    pub fn read(&self, secret: &[u8; 32], checkpoint: Option<&Checkpoint>) -> io::Result<AuditReport> {
+        let mut file = File::open(&self.path)?;
+        let (header, lines) = Self::read_lines(&mut file)?;
+
+        let secret = StaticSecret::from(*secret);
+        let public_key = Self::public_key(&header)?;
+        if PublicKey::from(&secret) != public_key {
+            return Err(invalid_data("the audit log does not belong to this vault"));
+        }
+
+        let mut report = AuditReport::default();
+        let mut expected_prev = hash_line(&header);
+        let mut checkpoint_found = checkpoint.is_none_or(|c| c.seq == 0);
+
+        for (index, line) in lines.iter().enumerate() {
+            let expected_seq = (index as u64) + 1;
+
+            let Ok(record) = serde_json::from_str::<Record>(line) else {
+                report.problems.push(format!("record {expected_seq} is not readable"));
+                expected_prev = hash_line(line);
+                continue;
+            };
+
+            if record.seq != expected_seq {
+                report.problems.push(format!("record {expected_seq} is numbered {}", record.seq));
+            }
+            if record.prev != expected_prev {
+                report.problems.push(format!("the chain is broken before record {}", record.seq));
+            }
+            if let Some(c) = checkpoint.filter(|c| c.seq == record.seq) {
+                if c.hash != hash_line(line) {
+                    report.problems.push(
+                        format!("record {} differs from the vault checkpoint, the log was truncated or rewritten", record.seq)
+                    );
+                }
+                checkpoint_found = true;
+            }
+
+            match Self::open_record(&secret, &record) {
+                Some(event) => report.events.push(LoggedEvent { seq: record.seq, event }),
+                None => report.problems.push(format!("record {} was tampered with", record.seq)),
+            }
+
+            expected_prev = hash_line(line);
+        }
+
+        if !checkpoint_found {
+            let expected = checkpoint.map_or(0, |c| c.seq);
+            report.problems.push(
+                format!("the log was truncated: {} records, at least {expected} expected", lines.len())
+            );
+        }
+
+        Ok(report)
+    }
+
+    fn open_record(secret: &StaticSecret, record: &Record) -> Option<AuditEvent> {
+        let epk: [u8; 32] = hex::decode(&record.epk).ok()?.try_into().ok()?;
+        let epk = PublicKey::from(epk);
+        let nonce = hex::decode(&record.nonce).ok().filter(|n| n.len() == 12)?;
+        let data = hex::decode(&record.data).ok()?;
+
+        let shared = secret.diffie_hellman(&epk);
+        let key = record_key(shared.as_bytes(), &epk, &PublicKey::from(secret));
+        let aad = format!("{}:{}", record.seq, record.prev);
+
+        let plaintext = Aes256Gcm::new_from_slice(&key)
+            .ok()?
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &data, aad: aad.as_bytes() })
+            .ok()?;
+
+        serde_json::from_slice(&plaintext).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A log with three events in a fresh directory, its secret and last checkpoint.
+    fn log_with_events(name: &str) -> (AuditLog, [u8; 32], Checkpoint) {
+        let dir = env::temp_dir().join(format!("pmgr-audit-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let log = AuditLog::for_vault(&dir.join("vault.bin"));
+        let secret = generate_secret();
+        log.create(&secret).unwrap();
+
+        let mut checkpoint = Checkpoint::default();
+        for kind in [AuditEventKind::Unlock, AuditEventKind::Reveal, AuditEventKind::Export] {
+            checkpoint = log.append(&AuditEvent::new(kind, None, Some(format!("{kind}")))).unwrap();
+        }
+        assert_eq!(log.checkpoint().unwrap(), checkpoint);
+
+        (log, secret, checkpoint)
+    }
+
+    fn rewrite_lines(log: &AuditLog, change: impl FnOnce(&mut Vec<String>)) {
+        let contents = std::fs::read_to_string(&log.path).unwrap();
+        let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+        change(&mut lines);
+        std::fs::write(&log.path, lines.join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn reads_back_appended_events() {
+        let (log, secret, checkpoint) = log_with_events("intact");
+
+        let report = log.read(&secret, Some(&checkpoint)).unwrap();
+        assert!(report.is_intact(), "{:?}", report.problems);
+        let kinds: Vec<AuditEventKind> = report.events.iter().map(|e| e.event.kind).collect();
+        assert_eq!(kinds, [AuditEventKind::Unlock, AuditEventKind::Reveal, AuditEventKind::Export]);
+        assert_eq!(report.events[2].seq, 3);
+        assert_eq!(report.events[1].event.detail.as_deref(), Some("reveal"));
+        assert!(log.check(&secret).is_ok());
+    }
+
+    #[test]
+    fn detects_a_tampered_record() {
+        let (log, secret, checkpoint) = log_with_events("tampered");
+        rewrite_lines(&log, |lines| {
+            let mut record: Record = serde_json::from_str(&lines[2]).unwrap();
+            let flipped = if record.data.starts_with('0') { "1" } else { "0" };
+            record.data.replace_range(..1, flipped);
+            lines[2] = serde_json::to_string(&record).unwrap();
+        });
+
+        let problems = log.read(&secret, Some(&checkpoint)).unwrap().problems;
+        assert!(problems.contains(&"record 2 was tampered with".to_string()), "{problems:?}");
+        assert!(problems.contains(&"the chain is broken before record 3".to_string()), "{problems:?}");
+    }
+
+    #[test]
+    fn detects_a_removed_record() {
+        let (log, secret, checkpoint) = log_with_events("removed");
+        rewrite_lines(&log, |lines| {
+            lines.remove(2);
+        });
+
+        let problems = log.read(&secret, Some(&checkpoint)).unwrap().problems;
+        assert!(problems.contains(&"record 2 is numbered 3".to_string()), "{problems:?}");
+        assert!(problems.contains(&"the chain is broken before record 3".to_string()), "{problems:?}");
+    }
+
+    #[test]
+    fn detects_truncation_against_the_checkpoint() {
+        let (log, secret, checkpoint) = log_with_events("truncated");
+        rewrite_lines(&log, |lines| {
+            lines.pop();
+        });
+
+        let report = log.read(&secret, Some(&checkpoint)).unwrap();
+        assert_eq!(report.events.len(), 2);
+        assert_eq!(report.problems, ["the log was truncated: 2 records, at least 3 expected"]);
+
+        // Without the checkpoint kept in the vault, a cut end cannot be told apart.
+        assert!(log.read(&secret, None).unwrap().is_intact());
+    }
+
+    #[test]
+    fn detects_a_rewritten_last_record() {
+        let (log, secret, checkpoint) = log_with_events("rewritten");
+        rewrite_lines(&log, |lines| {
+            lines.pop();
+        });
+        log.append(&AuditEvent::new(AuditEventKind::Unlock, None, None)).unwrap();
+
+        let problems = log.read(&secret, Some(&checkpoint)).unwrap().problems;
+        assert_eq!(problems, ["record 3 differs from the vault checkpoint, the log was truncated or rewritten"]);
+    }
+
+    #[test]
+    fn refuses_the_log_of_another_vault() {
+        let (log, _, checkpoint) = log_with_events("other-key");
+        let other = generate_secret();
+
+        assert!(log.read(&other, Some(&checkpoint)).is_err());
+        assert!(log.check(&other).is_err());
+    }
+}
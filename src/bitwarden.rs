@@ -0,0 +1,836 @@
+use std::collections::HashMap;
+use std::io;
+
+use aes::Aes256;
+use aes::cipher::{ BlockDecryptMut, BlockEncryptMut, KeyIvInit, block_padding::Pkcs7 };
+use argon2::{ Algorithm, Argon2, Params, Version };
+use base64::{ Engine, engine::general_purpose::STANDARD };
+use hkdf::Hkdf;
+use hmac::{ Hmac, Mac };
+use rand::{ RngCore, rngs::OsRng };
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+
+use crate::{
+    import::{ self, ImportBatch },
+    timestamp,
+    vault_entry::{ self, CustomField, EntryKind, PasswordVersion, VaultEntry },
+};
+
+const ITEM_LOGIN: u8 = 1;
+const ITEM_SECURE_NOTE: u8 = 2;
+const ITEM_CARD: u8 = 3;
+const ITEM_IDENTITY: u8 = 4;
+const ITEM_SSH_KEY: u8 = 5;
+
+const FIELD_TEXT: u8 = 0;
+const FIELD_HIDDEN: u8 = 1;
+const FIELD_BOOLEAN: u8 = 2;
+
+const KDF_PBKDF2: u8 = 0;
+const KDF_ARGON2ID: u8 = 1;
+
+/// PBKDF2 iterations of the password protected exports we write, the Bitwarden default.
+const PBKDF2_ITERATIONS: u32 = 600_000;
+
+/// Custom fields holding what one side has no field for: the URIs of a login after
+/// the first, and the tags of an entry.
+const URL_FIELD: &str = "URL";
+const TAGS_FIELD: &str = "Tags";
+/// Custom field of a TOTP secret that is not a plain base32 secret, e.g. `steam://`.
+const TOTP_FIELD: &str = "TOTP";
+/// Tag of the items marked as favorite in Bitwarden.
+const FAVORITE_TAG: &str = "favorite";
+
+/// An unencrypted export, also the plaintext of a password protected one.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    encrypted: bool,
+    #[serde(default)]
+    folders: Vec<Folder>,
+    /// Organization exports have collections instead of folders.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    collections: Vec<Folder>,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Folder {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    id: Option<String>,
+    organization_id: Option<String>,
+    folder_id: Option<String>,
+    #[serde(rename = "type")]
+    kind: u8,
+    #[serde(default)]
+    reprompt: u8,
+    name: Option<String>,
+    notes: Option<String>,
+    #[serde(default)]
+    favorite: bool,
+    fields: Option<Vec<Field>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    login: Option<Login>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secure_note: Option<SecureNote>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    card: Option<Card>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    identity: Option<Identity>,
+    collection_ids: Option<Vec<String>>,
+    password_history: Option<Vec<PasswordHistory>>,
+    creation_date: Option<String>,
+    revision_date: Option<String>,
+    deleted_date: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Field {
+    name: Option<String>,
+    value: Option<String>,
+    /// Text, hidden, boolean, or linked to another field of the item.
+    #[serde(rename = "type")]
+    kind: u8,
+    linked_id: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Login {
+    uris: Option<Vec<Uri>>,
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Uri {
+    #[serde(rename = "match")]
+    match_type: Option<u8>,
+    uri: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SecureNote {
+    #[serde(rename = "type")]
+    kind: u8,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Card {
+    cardholder_name: Option<String>,
+    brand: Option<String>,
+    number: Option<String>,
+    exp_month: Option<String>,
+    exp_year: Option<String>,
+    code: Option<String>,
+}
+
+/// A detail of a card or an identity: the name of the custom field holding it in the
+/// vault, whether that field is protected, and its value.
+type Detail<'a> = (&'static str, bool, &'a mut Option<String>);
+
+impl Card {
+    fn details(&mut self) -> [Detail<'_>; 6] {
+        [
+            ("Cardholder name", false, &mut self.cardholder_name),
+            ("Brand", false, &mut self.brand),
+            ("Number", true, &mut self.number),
+            ("Expiration month", false, &mut self.exp_month),
+            ("Expiration year", false, &mut self.exp_year),
+            ("Security code", true, &mut self.code),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Identity {
+    title: Option<String>,
+    first_name: Option<String>,
+    middle_name: Option<String>,
+    last_name: Option<String>,
+    address1: Option<String>,
+    address2: Option<String>,
+    address3: Option<String>,
+    city: Option<String>,
+    state: Option<String>,
+    postal_code: Option<String>,
+    country: Option<String>,
+    company: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    ssn: Option<String>,
+    /// The username of the entry.
+    username: Option<String>,
+    passport_number: Option<String>,
+    license_number: Option<String>,
+}
+
+impl Identity {
+    fn details(&mut self) -> [Detail<'_>; 17] {
+        [
+            ("Title", false, &mut self.title),
+            ("First name", false, &mut self.first_name),
+            ("Middle name", false, &mut self.middle_name),
+            ("Last name", false, &mut self.last_name),
+            ("Address 1", false, &mut self.address1),
+            ("Address 2", false, &mut self.address2),
+            ("Address 3", false, &mut self.address3),
+            ("City", false, &mut self.city),
+            ("State", false, &mut self.state),
+            ("Postal code", false, &mut self.postal_code),
+            ("Country", false, &mut self.country),
+            ("Company", false, &mut self.company),
+            ("Email", false, &mut self.email),
+            ("Phone", false, &mut self.phone),
+            ("Social security number", true, &mut self.ssn),
+            ("Passport number", true, &mut self.passport_number),
+            ("License number", true, &mut self.license_number),
+        ]
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PasswordHistory {
+    /// When the password was replaced.
+    last_used_date: Option<String>,
+    password: Option<String>,
+}
+
+/// An export encrypted with a password rather than the account key, so that it can be
+/// imported into any account.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProtectedExport {
+    encrypted: bool,
+    password_protected: bool,
+    salt: String,
+    kdf_type: u8,
+    kdf_iterations: u32,
+    /// Argon2id memory in MiB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kdf_memory: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kdf_parallelism: Option<u32>,
+    /// A random UUID, which only decrypts with the right password.
+    #[serde(rename = "encKeyValidation_DO_NOT_EDIT")]
+    key_validation: String,
+    /// The unencrypted export.
+    data: String,
+}
+
+/// Keys of a password protected export, derived like Bitwarden does: PBKDF2-SHA256 or
+/// Argon2id of the password, then HKDF-Expand into an AES key and an HMAC key.
+struct Keys {
+    encryption: [u8; 32],
+    mac: [u8; 32],
+}
+
+impl Keys {
+    fn derive(password: &str, export: &ProtectedExport) -> io::Result<Self> {
+        let mut key = [0u8; 32];
+
+        match export.kdf_type {
+            KDF_PBKDF2 => {
+                pbkdf2::pbkdf2_hmac::<Sha256>(
+                    password.as_bytes(),
+                    export.salt.as_bytes(),
+                    export.kdf_iterations,
+                    &mut key
+                );
+            }
+            KDF_ARGON2ID => {
+                let params = Params::new(
+                    export.kdf_memory.unwrap_or(64) * 1024,
+                    export.kdf_iterations,
+                    export.kdf_parallelism.unwrap_or(4),
+                    Some(32)
+                ).map_err(|e| invalid_data(format!("invalid Argon2 parameters: {e}")))?;
+
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password.as_bytes(), &Sha256::digest(export.salt.as_bytes()), &mut key)
+                    .map_err(|e| invalid_data(format!("Argon2 failed: {e}")))?;
+            }
+            kdf => {
+                return Err(invalid_data(format!("unsupported key derivation function {kdf}")));
+            }
+        }
+
+        let hkdf = Hkdf::<Sha256>::from_prk(&key).expect("32 bytes is a valid pseudorandom key");
+        let mut keys = Keys { encryption: [0; 32], mac: [0; 32] };
+        hkdf.expand(b"enc", &mut keys.encryption).expect("32 bytes is a valid output length");
+        hkdf.expand(b"mac", &mut keys.mac).expect("32 bytes is a valid output length");
+
+        Ok(keys)
+    }
+
+    fn hmac(&self, iv: &[u8], data: &[u8]) -> Hmac<Sha256> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.mac).expect("HMAC takes any key size");
+        mac.update(iv);
+        mac.update(data);
+        mac
+    }
+
+    /// An encrypted string of type 2, `2.iv|data|mac` in base64: AES-256-CBC, then
+    /// HMAC-SHA256 over the IV and the ciphertext.
+    fn encrypt(&self, plaintext: &[u8]) -> String {
+        let mut iv = [0u8; 16];
+        OsRng.fill_bytes(&mut iv);
+
+        let data = cbc::Encryptor::<Aes256>::new_from_slices(&self.encryption, &iv)
+            .expect("the key and IV have the AES-256 sizes")
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+        let mac = self.hmac(&iv, &data).finalize().into_bytes();
+
+        format!("2.{}|{}|{}", STANDARD.encode(iv), STANDARD.encode(&data), STANDARD.encode(mac))
+    }
+
+    /// `None` when the MAC does not match, i.e. the password is wrong.
+    fn decrypt(&self, encrypted: &str) -> io::Result<Option<Vec<u8>>> {
+        let malformed = || invalid_data("malformed encrypted data");
+
+        let parts = encrypted.strip_prefix("2.").ok_or_else(|| invalid_data("unsupported encryption type"))?;
+        let parts: Vec<Vec<u8>> = parts
+            .split('|')
+            .map(|part| STANDARD.decode(part))
+            .collect::<Result<_, _>>()
+            .map_err(|_| malformed())?;
+        let [iv, data, mac] = parts.as_slice() else {
+            return Err(malformed());
+        };
+
+        if self.hmac(iv, data).verify_slice(mac).is_err() {
+            return Ok(None);
+        }
+
+        cbc::Decryptor::<Aes256>::new_from_slices(&self.encryption, iv)
+            .map_err(|_| malformed())?
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .map(Some)
+            .map_err(|_| malformed())
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Whether the export is encrypted with a password, which `import` then needs.
+pub fn is_password_protected(json: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(json)
+        .is_ok_and(|export| export.get("passwordProtected").and_then(serde_json::Value::as_bool) == Some(true))
+}
+
+/// Reads the items of a Bitwarden JSON export, unencrypted or password protected.
+pub fn import(json: &str, password: Option<&str>) -> io::Result<ImportBatch> {
+    let export: serde_json::Value = serde_json::from_str(json).map_err(|e| invalid_data(format!("invalid JSON: {e}")))?;
+
+    let (export, format): (Export, _) = if export.get("encrypted").and_then(serde_json::Value::as_bool) == Some(true) {
+        if !is_password_protected(json) {
+            return Err(
+                invalid_data(
+                    "the export is encrypted with the Bitwarden account key, export the vault again \
+                     with a password or unencrypted"
+                )
+            );
+        }
+
+        let protected: ProtectedExport = serde_json::from_value(export)
+            .map_err(|e| invalid_data(format!("invalid password protected export: {e}")))?;
+        let password = password.ok_or_else(|| invalid_data("the export is protected with a password"))?;
+        let keys = Keys::derive(password, &protected)?;
+
+        if keys.decrypt(&protected.key_validation)?.is_none() {
+            return Err(invalid_data("wrong password"));
+        }
+        let data = keys.decrypt(&protected.data)?.ok_or_else(|| invalid_data("the export was modified"))?;
+
+        let export = serde_json::from_slice(&data).map_err(|e| invalid_data(format!("invalid export: {e}")))?;
+        (export, "password protected Bitwarden JSON")
+    } else {
+        let export = serde_json::from_value(export).map_err(|e| invalid_data(format!("invalid export: {e}")))?;
+        (export, "Bitwarden JSON")
+    };
+
+    let folders: HashMap<&str, &str> = export.folders
+        .iter()
+        .chain(&export.collections)
+        .map(|folder| (folder.id.as_str(), folder.name.as_str()))
+        .collect();
+
+    let mut batch = ImportBatch { format: format.to_string(), ..ImportBatch::default() };
+    for item in &export.items {
+        let name = item.name.clone().unwrap_or_else(|| "untitled item".to_string());
+
+        match read_item(item, &folders) {
+            Ok(entry) => batch.entries.push(entry),
+            Err(reason) => batch.skipped.push(format!("{name}: {reason}")),
+        }
+    }
+
+    Ok(batch)
+}
+
+/// Moves the non-empty details to custom fields.
+fn details_to_fields(details: &mut [Detail]) -> Vec<CustomField> {
+    details
+        .iter_mut()
+        .filter_map(|(name, protected, value)| {
+            let value = value.take().filter(|v| !v.is_empty())?;
+            Some(CustomField { name: name.to_string(), value, protected: *protected })
+        })
+        .collect()
+}
+
+/// Fills the details from the custom fields of the same name, and returns the other
+/// custom fields.
+fn fields_to_details<'a>(fields: &'a [CustomField], details: &mut [Detail]) -> Vec<&'a CustomField> {
+    fields
+        .iter()
+        .filter(|field| {
+            match details.iter_mut().find(|(name, _, value)| *name == field.name && value.is_none()) {
+                Some((_, _, value)) => {
+                    **value = Some(field.value.clone());
+                    false
+                }
+                None => true,
+            }
+        })
+        .collect()
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.clone().filter(|v| !v.trim().is_empty())
+}
+
+fn read_item(item: &Item, folders: &HashMap<&str, &str>) -> Result<VaultEntry, String> {
+    if item.deleted_date.is_some() {
+        return Err("in the trash".to_string());
+    }
+
+    let mut username = None;
+    let mut password = None;
+    let mut totp = None;
+    let mut uris = Vec::new();
+    let mut custom_fields = Vec::new();
+
+    let kind = match item.kind {
+        ITEM_LOGIN => {
+            if let Some(login) = &item.login {
+                username = non_empty(&login.username);
+                password = login.password.clone();
+                totp = non_empty(&login.totp);
+                uris = login.uris
+                    .iter()
+                    .flatten()
+                    .filter_map(|uri| non_empty(&uri.uri))
+                    .collect();
+            }
+            EntryKind::Login
+        }
+        ITEM_SECURE_NOTE => EntryKind::SecureNote,
+        ITEM_CARD => {
+            let mut card = item.card.clone().unwrap_or_default();
+            custom_fields = details_to_fields(&mut card.details());
+            EntryKind::Card
+        }
+        ITEM_IDENTITY => {
+            let mut identity = item.identity.clone().unwrap_or_default();
+            username = non_empty(&identity.username);
+            custom_fields = details_to_fields(&mut identity.details());
+            EntryKind::Identity
+        }
+        ITEM_SSH_KEY => {
+            return Err("SSH key item, not supported".to_string());
+        }
+        kind => {
+            return Err(format!("item of unknown type {kind}"));
+        }
+    };
+
+    let mut uris = uris.into_iter();
+    let url = uris.next();
+    let service = non_empty(&item.name)
+        .or_else(|| url.as_deref().and_then(vault_entry::host_of))
+        .ok_or("no name nor URL")?;
+
+    let mut entry = VaultEntry::new(service, username, password.unwrap_or_default().into_bytes());
+    entry.kind = kind;
+    entry.url = url;
+    entry.notes = non_empty(&item.notes);
+
+    let folder = item.folder_id
+        .as_deref()
+        .or_else(|| item.collection_ids.iter().flatten().next().map(String::as_str))
+        .and_then(|id| folders.get(id));
+    entry.folder = folder.map(|name| name.trim_matches('/').to_string()).filter(|name| !name.is_empty());
+
+    for field in item.fields.iter().flatten() {
+        let name = field.name.clone().unwrap_or_default();
+        let value = field.value.clone().unwrap_or_default();
+
+        match field.kind {
+            FIELD_TEXT if name == TAGS_FIELD => {
+                entry.tags.extend(value.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string));
+            }
+            FIELD_TEXT | FIELD_BOOLEAN => custom_fields.push(CustomField { name, value, protected: false }),
+            FIELD_HIDDEN => custom_fields.push(CustomField { name, value, protected: true }),
+            // Linked fields only point at another field of the item, e.g. its username.
+            _ => {}
+        }
+    }
+    if item.favorite && !entry.tags.iter().any(|t| t.eq_ignore_ascii_case(FAVORITE_TAG)) {
+        entry.tags.push(FAVORITE_TAG.to_string());
+    }
+
+    custom_fields.extend(uris.map(|uri| CustomField { name: URL_FIELD.to_string(), value: uri, protected: false }));
+
+    match totp {
+        Some(totp) if totp.starts_with("otpauth://") => entry.totp = Some(totp),
+        Some(totp) if totp.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '=') => {
+            let secret = totp.replace([' ', '='], "").to_uppercase();
+            entry.totp = Some(vault_entry::totp_uri(&secret, &entry.service, entry.username.as_deref().unwrap_or("")));
+        }
+        Some(totp) => custom_fields.push(CustomField { name: TOTP_FIELD.to_string(), value: totp, protected: true }),
+        None => {}
+    }
+    entry.custom_fields = custom_fields;
+
+    entry.password_history = item.password_history
+        .iter()
+        .flatten()
+        .filter_map(|version| {
+            Some(PasswordVersion {
+                password: version.password.clone()?.into_bytes(),
+                derivation: None,
+                time: version.last_used_date.as_deref().and_then(timestamp::parse_datetime).unwrap_or(0),
+            })
+        })
+        .collect();
+    entry.password_history.sort_by_key(|version| version.time);
+
+    let created = item.creation_date.as_deref().and_then(timestamp::parse_datetime);
+    let modified = item.revision_date.as_deref().and_then(timestamp::parse_datetime);
+    if let Some(time) = created.or(modified) {
+        entry.created = time;
+    }
+    if let Some(time) = modified.or(created) {
+        entry.modified = time;
+    }
+
+    Ok(entry)
+}
+
+/// A Bitwarden id, a UUID, from the 32 hexadecimal digits of an entry id.
+fn uuid(id: &str) -> String {
+    let hex = if id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        id.to_lowercase()
+    } else {
+        vault_entry::generate_id()
+    };
+
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+fn write_item(entry: &VaultEntry, folder_id: Option<String>) -> Item {
+    let mut item = Item {
+        id: Some(uuid(&entry.id)),
+        folder_id,
+        name: Some(entry.service.clone()),
+        notes: entry.notes.clone(),
+        favorite: entry.tags.iter().any(|t| t.eq_ignore_ascii_case(FAVORITE_TAG)),
+        creation_date: Some(timestamp::format_datetime(entry.created)),
+        revision_date: Some(timestamp::format_datetime(entry.modified)),
+        ..Item::default()
+    };
+
+    let mut fields: Vec<&CustomField> = entry.custom_fields.iter().collect();
+    match entry.kind {
+        EntryKind::Login => {
+            let (extra_uris, others): (Vec<&CustomField>, _) = fields.into_iter().partition(|f| f.name == URL_FIELD);
+            fields = others;
+
+            let mut totp = entry.totp.clone();
+            if totp.is_none() && let Some(index) = fields.iter().position(|f| f.name == TOTP_FIELD) {
+                totp = Some(fields.remove(index).value.clone());
+            }
+
+            let uris = entry.url
+                .iter()
+                .chain(extra_uris.iter().map(|f| &f.value))
+                .map(|uri| Uri { match_type: None, uri: Some(uri.clone()) })
+                .collect();
+
+            item.kind = ITEM_LOGIN;
+            item.login = Some(Login {
+                uris: Some(uris),
+                username: entry.username.clone(),
+                password: Some(String::from_utf8_lossy(&entry.password).into_owned()),
+                totp,
+            });
+        }
+        EntryKind::SecureNote => {
+            item.kind = ITEM_SECURE_NOTE;
+            item.secure_note = Some(SecureNote { kind: 0 });
+        }
+        EntryKind::Card => {
+            let mut card = Card::default();
+            fields = fields_to_details(&entry.custom_fields, &mut card.details());
+
+            item.kind = ITEM_CARD;
+            item.card = Some(card);
+        }
+        EntryKind::Identity => {
+            let mut identity = Identity { username: entry.username.clone(), ..Identity::default() };
+            fields = fields_to_details(&entry.custom_fields, &mut identity.details());
+
+            item.kind = ITEM_IDENTITY;
+            item.identity = Some(identity);
+        }
+    }
+
+    let tags: Vec<&str> = entry.tags
+        .iter()
+        .filter(|t| !t.eq_ignore_ascii_case(FAVORITE_TAG))
+        .map(String::as_str)
+        .collect();
+    let mut fields: Vec<Field> = fields
+        .into_iter()
+        .map(|field| Field {
+            name: Some(field.name.clone()),
+            value: Some(field.value.clone()),
+            kind: if field.protected { FIELD_HIDDEN } else { FIELD_TEXT },
+            linked_id: None,
+        })
+        .collect();
+    if !tags.is_empty() {
+        fields.push(Field { name: Some(TAGS_FIELD.to_string()), value: Some(tags.join(", ")), kind: FIELD_TEXT, linked_id: None });
+    }
+    item.fields = Some(fields).filter(|fields| !fields.is_empty());
+
+    // Bitwarden lists the most recently replaced password first.
+    item.password_history = Some(
+        entry.password_history
+            .iter()
+            .rev()
+            .map(|version| PasswordHistory {
+                last_used_date: Some(timestamp::format_datetime(version.time)),
+                password: Some(String::from_utf8_lossy(&version.password).into_owned()),
+            })
+            .collect()
+    ).filter(|history: &Vec<_>| !history.is_empty());
+
+    item
+}
+
+/// Writes `entries` as a Bitwarden JSON export, protected with `password` if given, so
+/// that Bitwarden asks for it when importing. See `left_out` for what is lost.
+pub fn export(entries: &[VaultEntry], password: Option<&str>) -> io::Result<String> {
+    let mut folders: Vec<Folder> = Vec::new();
+    let mut items = Vec::new();
+
+    for entry in entries {
+        let folder_id = entry.folder.as_ref().map(|name| {
+            match folders.iter().find(|folder| &folder.name == name) {
+                Some(folder) => folder.id.clone(),
+                None => {
+                    let id = uuid(&vault_entry::generate_id());
+                    folders.push(Folder { id: id.clone(), name: name.clone() });
+                    id
+                }
+            }
+        });
+
+        items.push(write_item(entry, folder_id));
+    }
+
+    let export = Export { encrypted: false, folders, collections: Vec::new(), items };
+    let json = serde_json::to_string_pretty(&export).map_err(io::Error::other)?;
+
+    match password {
+        Some(password) => protect(&json, password, PBKDF2_ITERATIONS),
+        None => Ok(json),
+    }
+}
+
+/// Encrypts an unencrypted export `json` with `password`, derived with PBKDF2.
+fn protect(json: &str, password: &str, kdf_iterations: u32) -> io::Result<String> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+
+    let mut protected = ProtectedExport {
+        encrypted: true,
+        password_protected: true,
+        salt: STANDARD.encode(salt),
+        kdf_type: KDF_PBKDF2,
+        kdf_iterations,
+        kdf_memory: None,
+        kdf_parallelism: None,
+        key_validation: String::new(),
+        data: String::new(),
+    };
+    let keys = Keys::derive(password, &protected)?;
+    protected.key_validation = keys.encrypt(uuid(&vault_entry::generate_id()).as_bytes());
+    protected.data = keys.encrypt(json.as_bytes());
+
+    serde_json::to_string_pretty(&protected).map_err(io::Error::other)
+}
+
+/// What the export leaves out of each entry, Bitwarden having no place for it, e.g.
+/// `GitHub (alice): 2 attachments, expiry date`.
+pub fn left_out(entries: &[VaultEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let mut missing = Vec::new();
+            match entry.attachments.len() {
+                0 => {}
+                1 => missing.push("1 attachment".to_string()),
+                count => missing.push(format!("{count} attachments")),
+            }
+            if entry.expires.is_some() {
+                missing.push("expiry date".to_string());
+            }
+            if entry.pending_rotation.is_some() {
+                missing.push("pending rotation".to_string());
+            }
+            if entry.password_rules.is_some() {
+                missing.push("password rules".to_string());
+            }
+
+            (!missing.is_empty()).then(|| format!("{}: {}", import::label(entry), missing.join(", ")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<VaultEntry> {
+        let mut login = VaultEntry::new("GitHub".to_string(), Some("alice".to_string()), b"s3cret-\xc3\xa9".to_vec());
+        login.url = Some("https://github.com/login".to_string());
+        login.notes = Some("work account".to_string());
+        login.folder = Some("Work/Dev".to_string());
+        login.tags = vec!["code".to_string(), FAVORITE_TAG.to_string()];
+        login.totp = Some("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP".to_string());
+        login.custom_fields = vec![
+            CustomField { name: "Recovery".to_string(), value: "abcd-efgh".to_string(), protected: true },
+            CustomField { name: URL_FIELD.to_string(), value: "https://gist.github.com".to_string(), protected: false },
+        ];
+        login.password_history = vec![
+            PasswordVersion { password: b"first".to_vec(), derivation: None, time: 1_600_000_000 },
+            PasswordVersion { password: b"second".to_vec(), derivation: None, time: 1_650_000_000 },
+        ];
+        login.created = 1_500_000_000;
+        login.modified = 1_700_000_000;
+
+        let mut card = VaultEntry::new("Visa".to_string(), None, Vec::new());
+        card.kind = EntryKind::Card;
+        card.folder = Some("Work/Dev".to_string());
+        card.custom_fields = vec![
+            CustomField { name: "Number".to_string(), value: "4111111111111111".to_string(), protected: true },
+            CustomField { name: "Security code".to_string(), value: "123".to_string(), protected: true },
+        ];
+
+        vec![login, card]
+    }
+
+    fn check_round_trip(batch: &ImportBatch, original: &[VaultEntry]) {
+        assert!(batch.skipped.is_empty(), "{:?}", batch.skipped);
+        assert_eq!(batch.entries.len(), original.len());
+
+        for (read, written) in batch.entries.iter().zip(original) {
+            assert_eq!(read.service, written.service);
+            assert_eq!(read.kind, written.kind);
+            assert_eq!(read.username, written.username);
+            assert_eq!(read.password, written.password);
+            assert_eq!(read.url, written.url);
+            assert_eq!(read.notes, written.notes);
+            assert_eq!(read.folder, written.folder);
+            assert_eq!(read.tags, written.tags);
+            assert_eq!(read.totp, written.totp);
+            assert_eq!(read.custom_fields, written.custom_fields);
+            assert_eq!(read.password_history, written.password_history);
+            assert_eq!(read.created, written.created);
+            assert_eq!(read.modified, written.modified);
+        }
+    }
+
+    #[test]
+    fn round_trips_unencrypted_export() {
+        let entries = entries();
+        let json = export(&entries, None).unwrap();
+
+        assert!(!is_password_protected(&json));
+        let batch = import(&json, None).unwrap();
+        assert_eq!(batch.format, "Bitwarden JSON");
+        check_round_trip(&batch, &entries);
+    }
+
+    #[test]
+    fn round_trips_password_protected_export() {
+        let entries = entries();
+        // `export` uses 600000 iterations, too slow for an unoptimised test build.
+        let json = protect(&export(&entries, None).unwrap(), "export password", 1000).unwrap();
+
+        assert!(is_password_protected(&json));
+        assert!(!json.contains("alice") && !json.contains("GitHub"));
+        assert_eq!(import(&json, None).unwrap_err().to_string(), "the export is protected with a password");
+        assert_eq!(import(&json, Some("wrong")).unwrap_err().to_string(), "wrong password");
+
+        let batch = import(&json, Some("export password")).unwrap();
+        assert_eq!(batch.format, "password protected Bitwarden JSON");
+        check_round_trip(&batch, &entries);
+
+        // Flipping a bit of the ciphertext must fail the MAC, not decrypt to garbage.
+        let mut protected: ProtectedExport = serde_json::from_str(&json).unwrap();
+        let mut parts: Vec<String> = protected.data.split('|').map(str::to_string).collect();
+        let mut data = STANDARD.decode(&parts[1]).unwrap();
+        data[0] ^= 1;
+        parts[1] = STANDARD.encode(data);
+        protected.data = parts.join("|");
+        let tampered = serde_json::to_string(&protected).unwrap();
+        assert_eq!(import(&tampered, Some("export password")).unwrap_err().to_string(), "the export was modified");
+    }
+
+    #[test]
+    fn imports_argon2id_protected_export() {
+        let entries = entries();
+        let mut protected = ProtectedExport {
+            encrypted: true,
+            password_protected: true,
+            salt: "c2FsdHNhbHRzYWx0c2FsdA==".to_string(),
+            kdf_type: KDF_ARGON2ID,
+            kdf_iterations: 2,
+            kdf_memory: Some(1),
+            kdf_parallelism: Some(1),
+            key_validation: String::new(),
+            data: String::new(),
+        };
+        let keys = Keys::derive("export password", &protected).unwrap();
+        protected.key_validation = keys.encrypt(b"e8f0c9a6-3b4d-4f8e-9c2a-7d1b5e6f0a3c");
+        protected.data = keys.encrypt(export(&entries, None).unwrap().as_bytes());
+        let json = serde_json::to_string(&protected).unwrap();
+
+        check_round_trip(&import(&json, Some("export password")).unwrap(), &entries);
+        assert_eq!(import(&json, Some("wrong")).unwrap_err().to_string(), "wrong password");
+    }
+
+    #[test]
+    fn refuses_account_encrypted_export() {
+        let json = r#"{"encrypted": true, "encKeyValidation_DO_NOT_EDIT": "2.a|b|c", "items": []}"#;
+        assert!(import(json, None).unwrap_err().to_string().contains("Bitwarden account key"));
+    }
+}
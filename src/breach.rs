@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write };
+use std::path::Path;
+
+use sha1::{ Digest, Sha1 };
+
+use crate::vault_entry::VaultEntry;
+
+/// Starts a compact index: the magic, then records of a SHA-1 hash and a big endian
+/// occurrence count, sorted by hash.
+const INDEX_MAGIC: &[u8; 8] = b"PMGRPWN1";
+const RECORD_SIZE: u64 = 24;
+
+pub type Sha1Hash = [u8; 20];
+
+pub fn sha1(password: &[u8]) -> Sha1Hash {
+    Sha1::digest(password).into()
+}
+
+/// Reads a `HASH:COUNT` line of the Pwned Passwords SHA-1 file.
+fn parse_line(line: &str) -> Option<(Sha1Hash, u64)> {
+    let (hash, count) = line.trim_end().split_once(':')?;
+
+    let mut parsed = [0u8; 20];
+    hex::decode_to_slice(hash, &mut parsed).ok()?;
+
+    Some((parsed, count.parse().ok()?))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A local copy of the Have I Been Pwned passwords, either the downloaded text file
+/// ordered by hash or a compact index built from it. Both are searched in place.
+pub enum PwnedPasswords {
+    Text { reader: BufReader<File>, len: u64 },
+    Index { file: File, records: u64 },
+}
+
+impl PwnedPasswords {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        let mut magic = [0u8; 8];
+        let is_index = len >= magic.len() as u64 && {
+            file.read_exact(&mut magic)?;
+            &magic == INDEX_MAGIC
+        };
+
+        if is_index {
+            let data = len - INDEX_MAGIC.len() as u64;
+            if !data.is_multiple_of(RECORD_SIZE) {
+                return Err(invalid_data(format!("{} is a truncated index", path.display())));
+            }
+
+            return Ok(PwnedPasswords::Index { file, records: data / RECORD_SIZE });
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        let mut reader = BufReader::new(file);
+        let mut first = String::new();
+        reader.read_line(&mut first)?;
+        if len > 0 && parse_line(&first).is_none() {
+            return Err(
+                invalid_data(
+                    format!("{} is neither a Pwned Passwords SHA-1 file nor an index", path.display())
+                )
+            );
+        }
+
+        Ok(PwnedPasswords::Text { reader, len })
+    }
+
+    /// How many times the password with this hash appeared in breaches, `0` if never.
+    pub fn occurrences(&mut self, hash: &Sha1Hash) -> io::Result<u64> {
+        match self {
+            PwnedPasswords::Text { reader, len } => search_text(reader, *len, hash),
+            PwnedPasswords::Index { file, records } => search_index(file, *records, hash),
+        }
+    }
+}
+
+/// First line starting at or after `position`, with its start offset.
+fn line_from(reader: &mut BufReader<File>, position: u64) -> io::Result<Option<(u64, String)>> {
+    let mut start = position;
+    let mut line = String::new();
+
+    if position > 0 {
+        reader.seek(SeekFrom::Start(position - 1))?;
+        let mut skipped = Vec::new();
+        start = position - 1 + reader.read_until(b'\n', &mut skipped)? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some((start, line)))
+}
+
+/// Binary search over byte offsets of the text file: the wanted line, if present, always
+/// starts within `low..high`.
+fn search_text(reader: &mut BufReader<File>, len: u64, hash: &Sha1Hash) -> io::Result<u64> {
+    let (mut low, mut high) = (0, len);
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+
+        let Some((start, line)) = line_from(reader, middle)? else {
+            high = middle;
+            continue;
+        };
+        if start >= high {
+            high = middle;
+            continue;
+        }
+
+        let (found, count) = parse_line(&line).ok_or_else(|| {
+            invalid_data(format!("unexpected line at byte {start} of the Pwned Passwords file"))
+        })?;
+
+        match found.cmp(hash) {
+            std::cmp::Ordering::Equal => {
+                return Ok(count);
+            }
+            std::cmp::Ordering::Less => {
+                low = start + line.len() as u64;
+            }
+            std::cmp::Ordering::Greater => {
+                high = middle;
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+fn search_index(file: &mut File, records: u64, hash: &Sha1Hash) -> io::Result<u64> {
+    let (mut low, mut high) = (0, records);
+    let mut record = [0u8; RECORD_SIZE as usize];
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+
+        file.seek(SeekFrom::Start(INDEX_MAGIC.len() as u64 + middle * RECORD_SIZE))?;
+        file.read_exact(&mut record)?;
+
+        match record[..20].cmp(hash) {
+            std::cmp::Ordering::Equal => {
+                return Ok(u32::from_be_bytes(record[20..].try_into().unwrap()) as u64);
+            }
+            std::cmp::Ordering::Less => {
+                low = middle + 1;
+            }
+            std::cmp::Ordering::Greater => {
+                high = middle;
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+/// Converts the Pwned Passwords SHA-1 text file into a compact index, about half its
+/// size. Counts above `u32::MAX` are capped. Returns the number of hashes written.
+pub fn build_index(source: &Path, destination: &Path) -> io::Result<u64> {
+    let reader = BufReader::new(File::open(source)?);
+    let mut writer = BufWriter::new(File::create(destination)?);
+    writer.write_all(INDEX_MAGIC)?;
+
+    let mut previous: Option<Sha1Hash> = None;
+    let mut written = 0;
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+
+        let (hash, count) = parse_line(&line).ok_or_else(|| {
+            invalid_data(format!("line {} of {} is not HASH:COUNT", number + 1, source.display()))
+        })?;
+
+        if previous.is_some_and(|previous| previous >= hash) {
+            return Err(
+                invalid_data(
+                    format!("{} is not ordered by hash at line {}", source.display(), number + 1)
+                )
+            );
+        }
+        previous = Some(hash);
+
+        writer.write_all(&hash)?;
+        writer.write_all(&(count.min(u32::MAX as u64) as u32).to_be_bytes())?;
+        written += 1;
+    }
+
+    writer.flush()?;
+
+    Ok(written)
+}
+
+pub struct BreachedEntry<'a> {
+    pub entry: &'a VaultEntry,
+    pub occurrences: u64,
+}
+
+/// Looks up every password of `entries`. Passwords are only hashed in memory, each
+/// distinct one looked up once.
+pub fn check_entries<'a>(
+    pwned: &mut PwnedPasswords,
+    entries: &'a [VaultEntry]
+) -> io::Result<Vec<BreachedEntry<'a>>> {
+    let mut counts: HashMap<Sha1Hash, u64> = HashMap::new();
+    let mut breached = Vec::new();
+
+    for entry in entries {
+        if entry.password.is_empty() {
+            continue;
+        }
+
+        let hash = sha1(&entry.password);
+        let occurrences = match counts.get(&hash) {
+            Some(&count) => count,
+            None => {
+                let count = pwned.occurrences(&hash)?;
+                counts.insert(hash, count);
+                count
+            }
+        };
+
+        if occurrences > 0 {
+            breached.push(BreachedEntry { entry, occurrences });
+        }
+    }
+
+    Ok(breached)
+}
@@ -0,0 +1,1587 @@
+use std::error::Error;
+use std::io::{ self, BufRead, IsTerminal };
+use std::path::{ Path, PathBuf };
+
+use x25519_dalek::PublicKey;
+
+use crate::{
+    age::{ self, Identities, Recipients },
+    archive,
+    audit_log::AuditEventKind,
+    bitwarden,
+    config::{ Config, DEFAULT_MIN_MASTER_SCORE },
+    csv_import::{ self, ColumnMapping, CsvFormat, CsvPreset },
+    cxf,
+    env_file::{ self, Variable },
+    breach::{ self, PwnedPasswords },
+    derived::{ self, DerivationParams },
+    expiry,
+    generator::{ CharClass, PasswordPolicy },
+    import::{ self, ImportBatch },
+    kdbx,
+    key_derivation,
+    merge,
+    pass_import,
+    paper,
+    passphrase::{ BuiltinList, PassphrasePolicy, WordList },
+    password_rules::PasswordRules,
+    plaintext_export,
+    query::Query,
+    rotation,
+    security_report::{ ReportSettings, SecurityReport },
+    strength::{ self, AttackScenario },
+    timestamp,
+    vault::Vault,
+    vault_entry,
+};
+
+const USAGE: &str = "\
+Usage: password-manager [--vault <PATH>] [COMMAND]
+
+Without a command the interactive interface is started.
+
+Options:
+  --vault <PATH>    Vault file to use. Defaults to $PM_VAULT, then to the
+                    vault_path of the config file, then to vault.bin in the
+                    data directory.
+
+Commands:
+  find <QUERY>...   List the entries matching a search query, e.g.
+                    find tag:work username:alice -folder:archive modified:<90d
+  import <FORMAT> <FILE> [OPTIONS]
+                    Add the entries of another password manager's export.
+                    Entries with the same account and password as one in the
+                    vault are skipped, as are those with the same account and
+                    another password unless --add-duplicates is given.
+      --dry-run           Only show the entries that would be imported
+      --add-duplicates    Import the same accounts with other passwords too
+    Formats:
+      age                 age file written by export age, merged like an
+                          archive. Without an identity file, its passphrase
+                          is read like the master password before it.
+        --identity <PATH> age-keygen identity file of a recipient
+      archive             Archive written by export archive, its passphrase
+                          read like the master password before it. Its entries
+                          are merged like with merge.
+        --replace         Replace every entry of the vault instead
+      csv                 CSV with a header row, recognised as an export of
+                          chrome, firefox, bitwarden, lastpass or keepassxc
+        --preset <NAME>   Read it as the export of one of those
+        --map <MAPPING>   Read other CSV files, e.g. --map
+                          service=Title,username=Login,password=Secret,url=Site
+                          Fields: service, username, password, url, notes,
+                          folder, tags, created, modified
+      env                 .env file of a project, one entry per variable in a
+                          folder, named after it, with the comments above it
+                          as notes. Entries of the folder named after a
+                          variable are updated instead.
+        --folder <PATH>   Folder of the project, by default the name of the
+                          directory of the file
+      kdbx                KeePass KDBX 4 database, its password read like the
+                          master password before it
+        --key-file <PATH> Key file of the database
+      bitwarden           Bitwarden JSON export, unencrypted or protected with
+                          a password read like the master password before it
+      cxf                 Credential Exchange Format JSON document. Passkeys,
+                          files and other credentials the vault cannot hold
+                          are listed
+      pass                Directory of a pass store, decrypted beforehand or
+                          with a command run on each .gpg file. The first line
+                          is the password, then key: value lines
+        --decrypt-command <COMMAND>
+                          e.g. 'gpg --quiet --decrypt {}', {} being the file,
+                          by default pass_decrypt_command of the config file
+  export <FORMAT> <FILE> [OPTIONS]
+                    Write every entry to a file in the format of another
+                    password manager, listing what the format cannot hold.
+    Formats:
+      age                 The entries matching a query given after the file,
+                          e.g. tag:shared, or every entry, encrypted for
+                          other people with age, without previous passwords
+                          or pending rotations, to import with import age
+        --recipient <KEY> age1 public key to encrypt to, repeatable
+        --recipients-file <PATH>
+                          File of public keys, one per line
+        --passphrase      Encrypt with a passphrase asked for twice instead
+      archive             Every entry, encrypted with a passphrase of its own
+                          asked for twice, to open on any machine whatever the
+                          master password
+      bitwarden           Bitwarden JSON, written like csv and json unless
+                          protected with a password
+        --password        Protect the export with a password, asked for twice
+        --force           As for csv and json
+      env                 The entries of a folder as a .env file, in the order
+                          of the vault, their notes as comments. Values that
+                          need quoting are quoted with a warning. Written
+                          like csv and json.
+        --folder <PATH>   As for import env
+        --force           As for csv and json
+      paper               Emergency kit to print: the vault file as QR codes
+                          and base32 text with checksums, its parameters, and
+                          a recovery secret revealing the master password, to
+                          cut off and keep apart. Written like csv and json
+        --force           As for csv and json
+      cxf                 Credential Exchange Format JSON, to move to another
+                          password manager. Written like csv and json
+        --force           As for csv and json
+      csv, json           Unencrypted, e.g. for a one-off migration, - being
+                          stdout. The master password is asked for again, the
+                          file is readable by its owner only and the export is
+                          listed by report from then on. The columns of csv
+                          are those of import csv --map, plus totp.
+        --force           Write to a terminal, over an existing file or in a
+                          directory other users can read or write
+  restore <FILE> [--recovery-secret]
+                    Rebuild the vault from an emergency kit, FILE holding
+                    the content of its QR codes or its chunks typed in,
+                    each starting with its PMGR1 line. The vault path must
+                    be free. --recovery-secret reads the recovery secret
+                    instead of the master password, and prints the latter.
+  merge <VAULT>     Merge another copy of the vault into this one. Entries
+                    changed in both are reported as conflicts to resolve
+                    in the interactive interface.
+  generate [OPTIONS]
+                    Print a random password or passphrase, and its entropy on
+                    stderr.
+      --length <N>        Number of characters (default 20)
+      --no-lower, --no-upper, --no-digits, --no-symbols
+                          Leave a character class out
+      --min-lower <N>, --min-upper <N>, --min-digits <N>, --min-symbols <N>
+                          Minimum characters of a class (default 1)
+      --symbols <SET>     Symbols to choose from
+      --no-look-alikes    Leave out characters such as 0/O and 1/l/I
+      --rules <RULES>     Follow site rules in the passwordrules syntax, e.g.
+                          'minlength: 8; maxlength: 16; allowed: [-_];'
+      --site <DOMAIN>     Follow the bundled rules of a site, if any
+      --passphrase        Print a passphrase of random words instead
+      --words <N>         Words in the passphrase (default 6)
+      --separator <SEP>   Between the words (default -)
+      --capitalize <CASE> lower, title, upper or random
+      --digit             Append a random digit to one of the words
+      --wordlist <LIST>   large (default), short, or the path of a word
+                          list file, one word per line or in the EFF
+                          diceware format
+      --count <N>         Number of passwords to print
+  derive <SITE> [OPTIONS]
+                    Print the password derived from the master password for a
+                    site, the same on every machine, without using the vault.
+      --login <LOGIN>     Username the password is for
+      --counter <N>       Bump to get a new password (default 1)
+      --length <N>        Number of characters, 5 to 35 (default 16)
+      --no-lower, --no-upper, --no-digits, --no-symbols
+                          Leave a character class out
+  strength          Estimate the strength of a password read from the
+                    terminal or stdin.
+  report [OPTIONS]  Check the entries for reused, weak and old passwords,
+                    missing two-factor authentication and duplicates.
+                    Exits with 1 when something was found.
+      --json, --markdown  Output format (default Markdown)
+      --min-score <N>     Report passwords scoring below N out of 4
+                          (default 3)
+      --max-age <DAYS>    Report passwords unchanged for longer (default 365)
+  rotation [--days <N>]
+                    List the entries whose password is older than the
+                    rotation policy of the config file, or than N days, and
+                    those with a rotation waiting for confirmation. Exits
+                    with 1 when there are any.
+  rotate [--confirm | --cancel] <QUERY>...
+                    Print a new password for the one entry matching the
+                    query and keep it pending, the current password staying
+                    in use until the change is confirmed with --confirm.
+                    --cancel forgets the new password.
+  expiring [--days <N>]
+                    List the entries expired or expiring within N days, by
+                    default expiry_warning_days of the config file or 14,
+                    soonest first. Exits with 1 when there are any.
+  breach <FILE>     Look every password up in a local copy of the Have I Been
+                    Pwned SHA-1 file, ordered by hash, or in an index built
+                    from it. Exits with 1 when a password was breached.
+  breach --build-index <FILE> <INDEX>
+                    Build a compact index from the Pwned Passwords file.
+  log [--json]      Show the audit log of the vault, or export it as JSON.
+                    Exits with 1 if the log was tampered with or truncated.
+  help              Show this message";
+
+/// Splits the options that apply to every command from the command and its arguments.
+pub fn parse_global_options(args: &[String]) -> Result<(Option<PathBuf>, Vec<String>), String> {
+    let mut vault = None;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if !rest.is_empty() {
+            rest.push(arg.clone());
+        } else if arg == "--vault" {
+            let path = args.next().ok_or("--vault expects a path")?;
+            vault = Some(PathBuf::from(path));
+        } else if let Some(path) = arg.strip_prefix("--vault=") {
+            vault = Some(PathBuf::from(path));
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+
+    Ok((vault, rest))
+}
+
+pub fn usage() -> &'static str {
+    USAGE
+}
+
+/// Runs a command line invocation and returns the process exit code.
+pub fn run(vault_path: &Path, config: Option<&Config>, args: &[String]) -> i32 {
+    let result = match args[0].as_str() {
+        "find" => find(vault_path, &args[1..]),
+        "merge" => merge(vault_path, &args[1..]),
+        "import" => import(vault_path, config, &args[1..]),
+        "export" => export(vault_path, config, &args[1..]),
+        "restore" => restore(vault_path, &args[1..]),
+        "report" => report(vault_path, &args[1..]),
+        "rotation" => rotation(vault_path, config, &args[1..]),
+        "rotate" => rotate(vault_path, &args[1..]),
+        "expiring" => expiring(vault_path, config, &args[1..]),
+        "breach" => breach(vault_path, &args[1..]),
+        "log" => log(vault_path, &args[1..]),
+        "generate" => generate(&args[1..]),
+        "derive" => derive(&args[1..]),
+        "strength" => strength(),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(0)
+        }
+        command => {
+            eprintln!("error: unknown command `{command}`\n\n{USAGE}");
+            Ok(2)
+        }
+    };
+
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        1
+    })
+}
+
+fn unlock(vault_path: &Path) -> Result<Vault, Box<dyn Error>> {
+    if !Vault::file_exists(vault_path) {
+        return Err(format!("no vault at {}", vault_path.display()).into());
+    }
+
+    let master_password = read_password("Master password: ")?;
+
+    Ok(Vault::open_existing(vault_path, &master_password)?)
+}
+
+/// Prompts on the terminal, or reads one line from stdin when it is piped so scripts
+/// can supply the password.
+fn read_password(prompt: &str) -> io::Result<String> {
+    if io::stdin().is_terminal() {
+        return rpassword::prompt_password(prompt);
+    }
+
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Prints the matching entries, one per line. Exits with 1 when nothing matched and
+/// 2 on a syntax error, like grep.
+fn find(vault_path: &Path, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let input = args.join(" ");
+
+    let query = match Query::parse(&input) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("error: invalid query\n{}", e.annotate(&input));
+            return Ok(2);
+        }
+    };
+
+    let vault = unlock(vault_path)?;
+    let entries = vault.get_entries();
+    let results = query.find(entries);
+
+    for result in &results {
+        let entry = &entries[result.index];
+        let modified = if entry.modified == 0 {
+            String::new()
+        } else {
+            timestamp::format_date(entry.modified)
+        };
+
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            entry.service,
+            entry.username.as_deref().unwrap_or(""),
+            entry.url.as_deref().unwrap_or(""),
+            entry.folder.as_deref().unwrap_or(""),
+            modified
+        );
+    }
+
+    Ok(if results.is_empty() { 1 } else { 0 })
+}
+
+fn merge(vault_path: &Path, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let [other_path] = args else {
+        eprintln!("error: merge expects the path of the other vault\n\n{USAGE}");
+        return Ok(2);
+    };
+
+    let mut vault = unlock(vault_path)?;
+    // The other copy is only read, it is neither locked nor written to.
+    if !Vault::file_exists(Path::new(other_path)) {
+        return Err(format!("no vault at {other_path}").into());
+    }
+    let master_password = read_password(&format!("Master password for {other_path}: "))?;
+    let other = Vault::open_read_only(Path::new(other_path), &master_password)?;
+
+    let outcome = vault.merge_from(&other)?;
+
+    println!(
+        "Merged {other_path} into {}: {} added, {} updated, {} deleted, {} conflicts",
+        vault_path.display(),
+        outcome.added,
+        outcome.updated,
+        outcome.deleted,
+        outcome.conflicts.len()
+    );
+
+    for conflict in &outcome.conflicts {
+        println!(
+            "  conflict: {} ({})",
+            conflict.local.service,
+            conflict.changed_fields().join(", ")
+        );
+    }
+
+    if !vault.conflicts().is_empty() {
+        println!("Open the vault to resolve the {} pending conflicts.", vault.conflicts().len());
+    }
+
+    Ok(0)
+}
+
+/// Adds the entries of an export from another password manager, or previews them with
+/// --dry-run. Passwords are never printed.
+fn import(vault_path: &Path, config: Option<&Config>, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let Some((format, args)) = args.split_first() else {
+        eprintln!("error: import expects a format and a file\n\n{USAGE}");
+        return Ok(2);
+    };
+
+    let mut file = None;
+    let mut dry_run = false;
+    let mut add_duplicates = false;
+    let mut csv_format = CsvFormat::Detect;
+    let mut key_file = None;
+    let mut decrypt_command = config.and_then(|c| c.pass_decrypt_command.clone());
+    let mut replace = false;
+    let mut identity_file = None;
+    let mut folder = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => {
+                dry_run = true;
+            }
+            "--add-duplicates" => {
+                add_duplicates = true;
+            }
+            "--preset" if format == "csv" => {
+                match args.next().and_then(|name| name.to_lowercase().parse::<CsvPreset>().ok()) {
+                    Some(preset) => csv_format = CsvFormat::Preset(preset),
+                    None => {
+                        eprintln!("error: --preset expects one of {}\n\n{USAGE}", CsvPreset::names());
+                        return Ok(2);
+                    }
+                }
+            }
+            "--map" if format == "csv" => {
+                match args.next().map(|spec| ColumnMapping::parse(spec)) {
+                    Some(Ok(mapping)) => csv_format = CsvFormat::Custom(Box::new(mapping)),
+                    Some(Err(e)) => {
+                        eprintln!("error: invalid --map: {e}");
+                        return Ok(2);
+                    }
+                    None => {
+                        eprintln!("error: --map expects field=column pairs\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            "--key-file" if format == "kdbx" => {
+                match args.next() {
+                    Some(path) => key_file = Some(PathBuf::from(path)),
+                    None => {
+                        eprintln!("error: --key-file expects a path\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            "--replace" if format == "archive" => {
+                replace = true;
+            }
+            "--folder" if format == "env" => {
+                match args.next() {
+                    Some(path) => folder = Some(path.clone()),
+                    None => {
+                        eprintln!("error: --folder expects a folder\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            "--identity" if format == "age" => {
+                match args.next() {
+                    Some(path) => identity_file = Some(PathBuf::from(path)),
+                    None => {
+                        eprintln!("error: --identity expects a path\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            "--decrypt-command" if format == "pass" => {
+                match args.next() {
+                    Some(command) => decrypt_command = Some(command.clone()),
+                    None => {
+                        eprintln!("error: --decrypt-command expects a command\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            _ if file.is_none() && !arg.starts_with("--") => {
+                file = Some(PathBuf::from(arg));
+            }
+            _ => {
+                eprintln!("error: unknown option `{arg}`\n\n{USAGE}");
+                return Ok(2);
+            }
+        }
+    }
+
+    let Some(file) = file else {
+        eprintln!("error: import expects the file to import\n\n{USAGE}");
+        return Ok(2);
+    };
+
+    if format == "env" {
+        return import_env(vault_path, &file, folder, dry_run);
+    }
+    if format == "archive" {
+        return import_archive(vault_path, &file, dry_run, replace);
+    }
+    if format == "age" {
+        return import_age(vault_path, &file, identity_file.as_deref(), dry_run);
+    }
+
+    let batch = match format.as_str() {
+        "csv" => csv_import::import(std::fs::File::open(&file)?, csv_format)?,
+        "kdbx" => {
+            let data = std::fs::read(&file)?;
+            let key_file = key_file.map(std::fs::read).transpose()?;
+            let password = read_password("KeePass password: ")?;
+            // A key file alone unlocks databases without a password.
+            let password = Some(password.as_str()).filter(|p| !p.is_empty() || key_file.is_none());
+
+            kdbx::import(&data, password, key_file.as_deref())?
+        }
+        "pass" => pass_import::import(&file, decrypt_command.as_deref())?,
+        "cxf" => cxf::import(&std::fs::read_to_string(&file)?)?,
+        "bitwarden" => {
+            let json = std::fs::read_to_string(&file)?;
+            let password = if bitwarden::is_password_protected(&json) {
+                Some(read_password("Bitwarden export password: ")?)
+            } else {
+                None
+            };
+
+            bitwarden::import(&json, password.as_deref())?
+        }
+        format => {
+            eprintln!("error: unknown import format `{format}`\n\n{USAGE}");
+            return Ok(2);
+        }
+    };
+
+    let mut vault = unlock(vault_path)?;
+    import_batch(&mut vault, batch, &file, dry_run, add_duplicates)
+}
+
+/// Shows what an import does and, unless `dry_run`, adds the entries to the vault.
+fn import_batch(
+    vault: &mut Vault,
+    batch: ImportBatch,
+    file: &Path,
+    dry_run: bool,
+    add_duplicates: bool
+) -> Result<i32, Box<dyn Error>> {
+    eprintln!("Read {} entries from {} as {}", batch.entries.len(), file.display(), batch.format);
+    for reason in &batch.skipped {
+        eprintln!("skipped {reason}");
+    }
+    for missing in &batch.left_out {
+        eprintln!("left out of {missing}");
+    }
+
+    let candidates = import::find_duplicates(batch.entries, vault.get_entries());
+    let duplicates = candidates.iter().filter(|c| !c.imported(add_duplicates)).count();
+
+    if dry_run {
+        for candidate in &candidates {
+            println!("{}", candidate.preview(add_duplicates));
+        }
+        eprintln!(
+            "Dry run: {} entries would be imported, {duplicates} duplicates skipped",
+            candidates.len() - duplicates
+        );
+        return Ok(0);
+    }
+
+    let entries: Vec<_> = candidates
+        .into_iter()
+        .filter(|c| c.imported(add_duplicates))
+        .map(|c| c.entry)
+        .collect();
+    let count = entries.len();
+
+    vault.import_entries(entries, &file.display().to_string())?;
+    eprintln!("Imported {count} entries, {duplicates} duplicates skipped");
+
+    Ok(0)
+}
+
+/// Prompts twice for a new password, e.g. to protect an export.
+fn read_new_password(prompt: &str, repeat_prompt: &str) -> Result<String, Box<dyn Error>> {
+    let password = read_password(prompt)?;
+    if password.is_empty() {
+        return Err("the password cannot be empty".into());
+    }
+    if read_password(repeat_prompt)? != password {
+        return Err("the passwords do not match".into());
+    }
+
+    Ok(password)
+}
+
+/// Writes the entries of the vault to an encrypted archive or in the format of another
+/// password manager, passwords included, and records the export in the audit log.
+fn export(vault_path: &Path, config: Option<&Config>, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let Some((format, args)) = args.split_first() else {
+        eprintln!("error: export expects a format and a file\n\n{USAGE}");
+        return Ok(2);
+    };
+
+    let plaintext = format == "csv" || format == "json" || format == "cxf";
+    let mut file = None;
+    let mut protect = false;
+    let mut force = false;
+    let mut recipients = Vec::new();
+    let mut query_words = Vec::new();
+    let mut folder = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--password" if format == "bitwarden" => {
+                protect = true;
+            }
+            "--force" if plaintext || format == "bitwarden" || format == "paper" || format == "env" => {
+                force = true;
+            }
+            "--folder" if format == "env" => {
+                match args.next() {
+                    Some(path) => folder = Some(path.clone()),
+                    None => {
+                        eprintln!("error: --folder expects a folder\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            "--passphrase" if format == "age" => {
+                protect = true;
+            }
+            "--recipient" if format == "age" => {
+                match args.next() {
+                    Some(recipient) => recipients.push(age::parse_recipient(recipient)?),
+                    None => {
+                        eprintln!("error: --recipient expects an age1 public key\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            "--recipients-file" if format == "age" => {
+                match args.next() {
+                    Some(path) => recipients.extend(age::parse_recipients(&std::fs::read_to_string(path)?)?),
+                    None => {
+                        eprintln!("error: --recipients-file expects a path\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            _ if file.is_none() && !arg.starts_with("--") => {
+                file = Some(PathBuf::from(arg));
+            }
+            _ if format == "age" && file.is_some() => {
+                query_words.push(arg.clone());
+            }
+            _ => {
+                eprintln!("error: unknown option `{arg}`\n\n{USAGE}");
+                return Ok(2);
+            }
+        }
+    }
+
+    let Some(file) = file else {
+        eprintln!("error: export expects the file to write\n\n{USAGE}");
+        return Ok(2);
+    };
+    // Without --password, a Bitwarden export is as readable as a CSV one.
+    if plaintext || (format == "bitwarden" && !protect) {
+        return export_plaintext(vault_path, format, &file, force);
+    }
+    if format == "env" {
+        return export_env(vault_path, &file, folder, force);
+    }
+    if format == "paper" {
+        return export_paper(vault_path, &file, force);
+    }
+    if format == "age" {
+        if protect != recipients.is_empty() {
+            eprintln!("error: export age expects either recipients or --passphrase\n\n{USAGE}");
+            return Ok(2);
+        }
+        return export_age(vault_path, config, &file, recipients, &query_words);
+    }
+    if format != "archive" && format != "bitwarden" {
+        eprintln!("error: unknown export format `{format}`\n\n{USAGE}");
+        return Ok(2);
+    }
+
+    let vault = unlock(vault_path)?;
+    let entries = vault.get_entries();
+
+    let (contents, description) = if format == "archive" {
+        let passphrase = read_strong_passphrase(config, "Archive passphrase: ", "Repeat the archive passphrase: ")?;
+
+        (archive::write(entries, &passphrase)?, "encrypted archive")
+    } else {
+        let password = read_new_password("Export password: ", "Repeat the export password: ")?;
+        for missing in bitwarden::left_out(entries) {
+            eprintln!("left out of {missing}");
+        }
+
+        (bitwarden::export(entries, Some(&password))?.into_bytes(), "password protected bitwarden JSON")
+    };
+
+    std::fs::write(&file, contents)?;
+    vault.record(
+        AuditEventKind::Export,
+        None,
+        Some(format!("{} entries as {description} to {}", entries.len(), file.display()))
+    )?;
+    eprintln!("Exported {} entries to {}", entries.len(), file.display());
+
+    Ok(0)
+}
+
+/// Prompts twice for the passphrase of an export, as strong as a master password must be.
+fn read_strong_passphrase(config: Option<&Config>, prompt: &str, repeat_prompt: &str) -> Result<String, Box<dyn Error>> {
+    let passphrase = read_new_password(prompt, repeat_prompt)?;
+    let min_score = config.map_or(DEFAULT_MIN_MASTER_SCORE, |c| c.min_master_score);
+    let estimate = strength::estimate(&passphrase, &[]);
+    if estimate.score < min_score {
+        return Err(format!("the passphrase is {}, choose a stronger one", estimate.label()).into());
+    }
+
+    Ok(passphrase)
+}
+
+/// Encrypts the entries matching `query_words`, or every entry, to age `recipients`, or
+/// with a passphrase when there are none.
+fn export_age(
+    vault_path: &Path,
+    config: Option<&Config>,
+    file: &Path,
+    recipients: Vec<PublicKey>,
+    query_words: &[String]
+) -> Result<i32, Box<dyn Error>> {
+    let input = query_words.join(" ");
+    let query = match Query::parse(&input) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("error: invalid query\n{}", e.annotate(&input));
+            return Ok(2);
+        }
+    };
+
+    let vault = unlock(vault_path)?;
+    let entries: Vec<vault_entry::VaultEntry> = query
+        .find(vault.get_entries())
+        .iter()
+        .map(|result| vault.get_entries()[result.index].clone())
+        .collect();
+    if entries.is_empty() {
+        return Err("no entry matches the query, nothing was exported".into());
+    }
+
+    let (recipients, description) = if recipients.is_empty() {
+        (Recipients::Passphrase(read_strong_passphrase(config, "age passphrase: ", "Repeat the age passphrase: ")?), "age file with a passphrase".to_string())
+    } else {
+        let description = format!("age file for {} recipients", recipients.len());
+        (Recipients::Keys(recipients), description)
+    };
+
+    std::fs::write(file, age::write_entries(&entries, &recipients)?)?;
+    vault.record(
+        AuditEventKind::Export,
+        None,
+        Some(format!("{} entries as {description} to {}", entries.len(), file.display()))
+    )?;
+    for entry in &entries {
+        eprintln!("  {}", entry.service);
+    }
+    eprintln!("Exported {} entries to {}", entries.len(), file.display());
+
+    Ok(0)
+}
+
+/// Writes every entry unencrypted as CSV, JSON, CXF or Bitwarden JSON, `-` being stdout. The master password
+/// is asked for again, and unless `force` is set, the export is refused to a terminal,
+/// over an existing file and in a directory other users can access. The export is
+/// recorded in the vault so the security report keeps mentioning it.
+fn export_plaintext(vault_path: &Path, format: &str, file: &Path, force: bool) -> Result<i32, Box<dyn Error>> {
+    if !force && let Some(reason) = plaintext_export::unsafe_destination(file) {
+        return Err(format!("refusing to write passwords in clear: {reason}, use --force to write anyway").into());
+    }
+
+    let mut vault = unlock(vault_path)?;
+    if vault.is_read_only() {
+        return Err("the vault is open in another process, the export could not be recorded".into());
+    }
+    let confirmation = read_password("Master password again, to export it unencrypted: ")?;
+    if !vault.verify_master_password(&confirmation) {
+        return Err("wrong master password, nothing was exported".into());
+    }
+
+    let entries = vault.get_entries();
+    let count = entries.len();
+    let contents = match format {
+        "csv" => plaintext_export::to_csv(entries)?,
+        "json" => plaintext_export::to_json(entries)?,
+        "bitwarden" => {
+            for missing in bitwarden::left_out(entries) {
+                eprintln!("left out of {missing}");
+            }
+            bitwarden::export(entries, None)?.into_bytes()
+        }
+        _ => {
+            for missing in cxf::left_out(entries) {
+                eprintln!("left out of {missing}");
+            }
+            cxf::export(entries)?.into_bytes()
+        }
+    };
+
+    let destination = if file == Path::new("-") {
+        "stdout".to_string()
+    } else {
+        std::path::absolute(file)?.display().to_string()
+    };
+    // Recorded first, so that an export cut short, e.g. by a closed pipe, is reported too.
+    vault.record_plaintext_export(format, &destination, count)?;
+    plaintext_export::write(file, &contents)?;
+    eprintln!("Exported {count} entries unencrypted to {destination}, delete the file once done with it");
+
+    Ok(0)
+}
+
+/// Writes the entries of a folder as a `.env` file in the order of the vault, their
+/// notes as comments. Entries whose name is not a variable name are left out. The values
+/// are in clear, so the file is written like by `export_plaintext`.
+fn export_env(vault_path: &Path, file: &Path, folder: Option<String>, force: bool) -> Result<i32, Box<dyn Error>> {
+    let folder = env_folder(file, folder)?;
+    if !force && let Some(reason) = plaintext_export::unsafe_destination(file) {
+        return Err(format!("refusing to write values in clear: {reason}, use --force to write anyway").into());
+    }
+
+    let mut vault = unlock(vault_path)?;
+    if vault.is_read_only() {
+        return Err("the vault is open in another process, the export could not be recorded".into());
+    }
+    let confirmation = read_password("Master password again, to export values unencrypted: ")?;
+    if !vault.verify_master_password(&confirmation) {
+        return Err("wrong master password, nothing was exported".into());
+    }
+
+    let mut variables = Vec::new();
+    for entry in vault.get_entries().iter().filter(|e| e.folder.as_deref() == Some(folder.as_str())) {
+        if !env_file::is_valid_key(&entry.service) {
+            eprintln!("left out {}: not a valid variable name", import::label(entry));
+            continue;
+        }
+
+        variables.push(Variable {
+            key: entry.service.clone(),
+            value: String::from_utf8_lossy(&entry.password).into_owned(),
+            comments: entry.notes.iter().flat_map(|notes| notes.lines()).map(str::to_string).collect(),
+        });
+    }
+    if variables.is_empty() {
+        return Err(format!("no entry of the folder {folder} to export").into());
+    }
+
+    let (contents, warnings) = env_file::write(&variables);
+    for warning in warnings {
+        eprintln!("warning: {warning}");
+    }
+
+    let destination = if file == Path::new("-") {
+        "stdout".to_string()
+    } else {
+        std::path::absolute(file)?.display().to_string()
+    };
+    // Recorded first, like by `export_plaintext`.
+    vault.record_plaintext_export(&format!("env of {folder}"), &destination, variables.len())?;
+    plaintext_export::write(file, contents.as_bytes())?;
+    eprintln!("Exported {} variables of {folder} unencrypted to {destination}", variables.len());
+
+    Ok(0)
+}
+
+/// Writes the emergency kit of the vault, a text document to print, see `paper::render`.
+/// With its recovery secret it opens the vault, so it is written with the safeguards of
+/// a plaintext export and recorded like one.
+fn export_paper(vault_path: &Path, file: &Path, force: bool) -> Result<i32, Box<dyn Error>> {
+    if !force && let Some(reason) = plaintext_export::unsafe_destination(file) {
+        return Err(format!("refusing to write the emergency kit: {reason}, use --force to write anyway").into());
+    }
+    if !Vault::file_exists(vault_path) {
+        return Err(format!("no vault at {}", vault_path.display()).into());
+    }
+
+    let master_password = read_password("Master password: ")?;
+    let mut vault = Vault::open_existing(vault_path, &master_password)?;
+    if vault.is_read_only() {
+        return Err("the vault is open in another process, the export could not be recorded".into());
+    }
+    let bytes = std::fs::read(vault.path())?;
+    let name = vault_path.file_name().unwrap_or_default().to_string_lossy();
+    let count = vault.get_entries().len();
+    let document = paper::render(&bytes, &master_password, &name, count)?;
+
+    let destination = if file == Path::new("-") {
+        "stdout".to_string()
+    } else {
+        std::path::absolute(file)?.display().to_string()
+    };
+    // Recorded first, like by `export_plaintext`.
+    vault.record_plaintext_export("emergency kit", &destination, count)?;
+    plaintext_export::write(file, document.as_bytes())?;
+    eprintln!("Wrote the emergency kit to {destination}, print it, then delete the file");
+
+    Ok(0)
+}
+
+/// Rebuilds the vault from the chunks of an emergency kit, scanned or typed into a text
+/// file, at the vault path, where there must be no vault yet.
+fn restore(vault_path: &Path, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let (file, use_recovery_secret) = match args {
+        [file] => (file, false),
+        [file, flag] | [flag, file] if flag == "--recovery-secret" => (file, true),
+        _ => {
+            eprintln!("error: restore expects the text file of the chunks\n\n{USAGE}");
+            return Ok(2);
+        }
+    };
+
+    if Vault::file_exists(vault_path) {
+        return Err(format!("{} already exists, give another path with --vault", vault_path.display()).into());
+    }
+
+    let reassembled = paper::reassemble(&std::fs::read_to_string(file)?)?;
+    eprintln!("Reassembled {} bytes, SHA-256 {}", reassembled.vault.len(), reassembled.fingerprint());
+
+    let master_password = if use_recovery_secret {
+        let master_password = reassembled.recover_master_password(&read_password("Recovery secret: ")?)?;
+        eprintln!("The master password is: {master_password}");
+        master_password
+    } else {
+        read_password("Master password: ")?
+    };
+
+    let vault = Vault::restore_at(vault_path, &reassembled.vault, &master_password)?;
+    eprintln!("Restored {} entries to {}", vault.get_entries().len(), vault_path.display());
+
+    Ok(0)
+}
+
+/// Merges the entries of an archive into the vault, or replaces the entries of the vault
+/// with them.
+fn import_archive(vault_path: &Path, file: &Path, dry_run: bool, replace: bool) -> Result<i32, Box<dyn Error>> {
+    let bytes = std::fs::read(file)?;
+    let passphrase = read_password("Archive passphrase: ")?;
+    let contents = archive::read(&bytes, &passphrase)?;
+
+    let mut vault = unlock(vault_path)?;
+    eprintln!(
+        "Read {} entries from {}, archived on {}",
+        contents.entries.len(),
+        file.display(),
+        timestamp::format_date(contents.created)
+    );
+
+    merge_read_entries(&mut vault, contents.entries, file, dry_run, replace)
+}
+
+/// The folder of the entries of a `.env` file: `folder`, or the name of the directory
+/// of the file, i.e. of its project.
+fn env_folder(file: &Path, folder: Option<String>) -> Result<String, Box<dyn Error>> {
+    let folder = match folder {
+        Some(folder) => folder,
+        None if file == Path::new("-") => return Err("give the folder to export with --folder".into()),
+        None => {
+            let file = std::path::absolute(file)?;
+            let directory = file.parent().and_then(Path::file_name);
+            directory
+                .ok_or("give the folder of the variables with --folder")?
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+
+    let folder = folder.trim_matches('/').to_string();
+    if folder.is_empty() {
+        return Err("the folder must not be empty".into());
+    }
+
+    Ok(folder)
+}
+
+/// Imports the variables of a `.env` file into a folder, one entry per variable named
+/// after it, the comments above it as its notes. Entries of the folder named after a
+/// variable are updated, the others are left alone, and the variables are put in the
+/// order of the file so that `export_env` writes them back in it.
+fn import_env(vault_path: &Path, file: &Path, folder: Option<String>, dry_run: bool) -> Result<i32, Box<dyn Error>> {
+    let folder = env_folder(file, folder)?;
+    let env = env_file::parse(&std::fs::read_to_string(file)?)?;
+    eprintln!("Read {} variables from {}", env.variables.len(), file.display());
+    for reason in &env.skipped {
+        eprintln!("skipped {reason}");
+    }
+
+    let mut vault = unlock(vault_path)?;
+    let mut added = Vec::new();
+    let mut updated = Vec::new();
+    let mut unchanged = 0;
+    let mut order = Vec::new();
+
+    for variable in env.variables {
+        let notes = Some(variable.comments.join("\n")).filter(|notes| !notes.is_empty());
+        let existing = vault
+            .get_entries()
+            .iter()
+            .find(|e| e.folder.as_deref() == Some(folder.as_str()) && e.service == variable.key && e.username.is_none());
+
+        match existing {
+            Some(entry) if entry.password == variable.value.as_bytes() && entry.notes == notes => {
+                order.push(entry.id.clone());
+                unchanged += 1;
+            }
+            Some(entry) => {
+                order.push(entry.id.clone());
+                let mut changed = Vec::new();
+                let mut entry = entry.clone();
+                if entry.password != variable.value.as_bytes() {
+                    changed.push("value");
+                    entry.password = variable.value.into_bytes();
+                    entry.derivation = None;
+                }
+                if entry.notes != notes {
+                    changed.push("comments");
+                    entry.notes = notes;
+                }
+
+                if dry_run {
+                    println!("~ {}  {} changed", entry.service, changed.join(", "));
+                }
+                updated.push(entry);
+            }
+            None => {
+                let mut entry = vault_entry::VaultEntry::new(variable.key, None, variable.value.into_bytes());
+                entry.folder = Some(folder.clone());
+                entry.notes = notes;
+                order.push(entry.id.clone());
+
+                if dry_run {
+                    println!("+ {}", entry.service);
+                }
+                added.push(entry);
+            }
+        }
+    }
+
+    let summary = format!("{} added, {} updated, {unchanged} unchanged", added.len(), updated.len());
+    if dry_run {
+        eprintln!("Dry run, importing {} into {folder}: {summary}", file.display());
+        return Ok(0);
+    }
+
+    vault.import_entries(added, &file.display().to_string())?;
+    for entry in updated {
+        vault.update_entry(entry)?;
+    }
+    vault.order_entries(&order)?;
+    eprintln!("Imported {} into {folder}: {summary}", file.display());
+
+    Ok(0)
+}
+
+/// Merges the entries of an age file into the vault. Files encrypted to recipients are
+/// opened with `identity_file`, the others with a passphrase.
+fn import_age(vault_path: &Path, file: &Path, identity_file: Option<&Path>, dry_run: bool) -> Result<i32, Box<dyn Error>> {
+    let bytes = std::fs::read(file)?;
+    let identities = match identity_file {
+        Some(path) => Identities::Keys(age::parse_identities(&std::fs::read_to_string(path)?)?),
+        None if age::is_passphrase_encrypted(&bytes) => Identities::Passphrase(read_password("age passphrase: ")?),
+        None => return Err("the age file is encrypted to recipients, give the identity file with --identity".into()),
+    };
+    let entries = age::read_entries(&bytes, &identities)?;
+
+    let mut vault = unlock(vault_path)?;
+    eprintln!("Read {} entries from {}", entries.len(), file.display());
+
+    merge_read_entries(&mut vault, entries, file, dry_run, false)
+}
+
+/// Merges entries written by another vault, or replaces the entries of the vault with
+/// them, and prints the outcome.
+fn merge_read_entries(
+    vault: &mut Vault,
+    mut entries: Vec<vault_entry::VaultEntry>,
+    file: &Path,
+    dry_run: bool,
+    replace: bool
+) -> Result<i32, Box<dyn Error>> {
+    let adopted = vault.adopt_entries(&mut entries);
+    if adopted > 0 {
+        eprintln!("{adopted} entries have passwords derived from another master password, kept as stored passwords");
+    }
+
+    let source = file.display().to_string();
+    if replace {
+        if dry_run {
+            eprintln!("Dry run: the {} entries of the vault would be replaced", vault.get_entries().len());
+        } else {
+            let replaced = vault.get_entries().len();
+            vault.replace_entries(entries, &source)?;
+            eprintln!("Replaced the {replaced} entries of the vault with those of {source}");
+        }
+        return Ok(0);
+    }
+
+    let outcome = if dry_run {
+        merge::merge(vault.get_entries(), &entries, vault.deletions())
+    } else {
+        vault.merge_entries(&entries, &[], &source)?
+    };
+
+    println!(
+        "{}{source}: {} added, {} updated, {} deleted, {} conflicts",
+        if dry_run { "Dry run, merging " } else { "Merged " },
+        outcome.added,
+        outcome.updated,
+        outcome.deleted,
+        outcome.conflicts.len()
+    );
+    for conflict in &outcome.conflicts {
+        println!("  conflict: {} ({})", conflict.local.service, conflict.changed_fields().join(", "));
+    }
+
+    Ok(0)
+}
+
+/// Prints the security report of the vault, which names entries but never contains
+/// their passwords.
+fn report(vault_path: &Path, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let mut settings = ReportSettings::default();
+    let mut json = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => {
+                json = true;
+            }
+            "--markdown" => {
+                json = false;
+            }
+            "--min-score" => {
+                match args.next().and_then(|v| v.parse().ok()).filter(|score| *score <= 4) {
+                    Some(score) => settings.min_score = score,
+                    None => {
+                        eprintln!("error: --min-score expects a score from 0 to 4\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            "--max-age" => {
+                match args.next().and_then(|v| v.parse().ok()) {
+                    Some(days) => settings.max_age_days = days,
+                    None => {
+                        eprintln!("error: --max-age expects a number of days\n\n{USAGE}");
+                        return Ok(2);
+                    }
+                }
+            }
+            _ => {
+                eprintln!("error: unknown option `{arg}`\n\n{USAGE}");
+                return Ok(2);
+            }
+        }
+    }
+
+    let vault = unlock(vault_path)?;
+    let mut report = SecurityReport::build(vault.get_entries(), settings);
+    report.add_plaintext_exports(vault.plaintext_exports());
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.to_markdown());
+    }
+
+    Ok(if report.findings.is_empty() { 0 } else { 1 })
+}
+
+/// Lists the entries due for rotation, pending rotations first.
+fn rotation(vault_path: &Path, config: Option<&Config>, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let mut policy = config.map(|c| c.rotation.clone()).unwrap_or_default();
+
+    match args {
+        [] => {}
+        [flag, days] if flag == "--days" => {
+            let Ok(days) = days.parse() else {
+                eprintln!("error: --days expects a number\n\n{USAGE}");
+                return Ok(2);
+            };
+            policy.default_days = days;
+            policy.by_tag.clear();
+        }
+        _ => {
+            eprintln!("error: rotation only accepts --days <N>\n\n{USAGE}");
+            return Ok(2);
+        }
+    }
+
+    let vault = unlock(vault_path)?;
+    let entries = vault.get_entries();
+    let due = rotation::due_entries(entries, &policy);
+
+    for d in &due {
+        let entry = &entries[d.index];
+        println!("{}\t{}\t{}", entry.service, entry.username.as_deref().unwrap_or(""), d.describe());
+    }
+
+    Ok(if due.is_empty() { 0 } else { 1 })
+}
+
+/// Lists the entries expired or expiring soon, so that a cron job can warn about them.
+fn expiring(vault_path: &Path, config: Option<&Config>, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let days = match args {
+        [] => config.map_or(expiry::DEFAULT_WARNING_DAYS, |c| c.expiry_warning_days),
+        [flag, days] if flag == "--days" => {
+            let Ok(days) = days.parse() else {
+                eprintln!("error: --days expects a number\n\n{USAGE}");
+                return Ok(2);
+            };
+            days
+        }
+        _ => {
+            eprintln!("error: expiring only accepts --days <N>\n\n{USAGE}");
+            return Ok(2);
+        }
+    };
+
+    let vault = unlock(vault_path)?;
+    let entries = vault.get_entries();
+    let expiring = expiry::expiring_entries(entries, Some(days));
+
+    for e in &expiring {
+        let entry = &entries[e.index];
+        println!(
+            "{}\t{}\t{}\t{}",
+            entry.service,
+            entry.username.as_deref().unwrap_or(""),
+            timestamp::format_date(e.expires),
+            e.describe()
+        );
+    }
+
+    Ok(if expiring.is_empty() { 0 } else { 1 })
+}
+
+/// Starts, confirms or cancels the rotation of a single entry.
+fn rotate(vault_path: &Path, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let (action, query) = match args.first().map(String::as_str) {
+        Some(flag @ ("--confirm" | "--cancel")) => (Some(flag), &args[1..]),
+        _ => (None, args),
+    };
+
+    let input = query.join(" ");
+    let query = match Query::parse(&input) {
+        Ok(query) if !input.trim().is_empty() => query,
+        Ok(_) => {
+            eprintln!("error: rotate expects a query matching the entry\n\n{USAGE}");
+            return Ok(2);
+        }
+        Err(e) => {
+            eprintln!("error: invalid query\n{}", e.annotate(&input));
+            return Ok(2);
+        }
+    };
+
+    let mut vault = unlock(vault_path)?;
+    let results = query.find(vault.get_entries());
+    let [result] = results.as_slice() else {
+        eprintln!("error: {} entries match, rotate needs exactly one", results.len());
+        return Ok(1);
+    };
+    let entry = vault.get_entries()[result.index].clone();
+
+    match action {
+        Some("--confirm") => {
+            vault.confirm_rotation(&entry.id)?;
+            eprintln!("Rotated {}", entry.service);
+        }
+        Some(_) => {
+            vault.cancel_rotation(&entry.id)?;
+            eprintln!("Cancelled the rotation of {}", entry.service);
+        }
+        None => {
+            vault.start_rotation(&entry.id, &PasswordPolicy::default())?;
+            let pending = vault.get_entries()[result.index].pending_rotation.clone().expect("rotation started");
+            println!("{}", String::from_utf8_lossy(&pending.password));
+            eprintln!(
+                "Change the password of {} on the site, then run rotate --confirm with the same query",
+                entry.service
+            );
+        }
+    }
+
+    Ok(0)
+}
+
+/// Prints the entries whose password appears in the Pwned Passwords file, with how
+/// often it was seen. Only SHA-1 hashes of the passwords are looked up.
+fn breach(vault_path: &Path, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let pwned_path = match args {
+        [flag, source, destination] if flag == "--build-index" => {
+            let written = breach::build_index(Path::new(source), Path::new(destination))?;
+            println!("Indexed {written} hashes into {destination}");
+            return Ok(0);
+        }
+        [path] if !path.starts_with("--") => Path::new(path),
+        _ => {
+            eprintln!("error: breach expects the Pwned Passwords file or an index\n\n{USAGE}");
+            return Ok(2);
+        }
+    };
+
+    let mut pwned = PwnedPasswords::open(pwned_path)?;
+    let vault = unlock(vault_path)?;
+    let breached = breach::check_entries(&mut pwned, vault.get_entries())?;
+
+    for found in &breached {
+        println!(
+            "{}\t{}\t{}",
+            found.entry.service,
+            found.entry.username.as_deref().unwrap_or(""),
+            found.occurrences
+        );
+    }
+    eprintln!("{} of {} entries have a breached password", breached.len(), vault.get_entries().len());
+
+    Ok(if breached.is_empty() { 0 } else { 1 })
+}
+
+/// Prints the audit log, oldest event first, and its integrity problems on stderr.
+fn log(vault_path: &Path, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let json = match args {
+        [] => false,
+        [flag] if flag == "--json" => true,
+        _ => {
+            eprintln!("error: log only accepts --json\n\n{USAGE}");
+            return Ok(2);
+        }
+    };
+
+    let vault = unlock(vault_path)?;
+    let report = vault.read_audit_log()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        vault.record(AuditEventKind::Export, None, Some("audit log as JSON".to_string()))?;
+    } else {
+        for logged in &report.events {
+            let event = &logged.event;
+            println!(
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                logged.seq,
+                timestamp::format_date(event.time),
+                event.actor,
+                event.kind,
+                event.entry.as_deref().unwrap_or(""),
+                event.detail.as_deref().unwrap_or("")
+            );
+        }
+    }
+
+    for problem in &report.problems {
+        eprintln!("warning: {problem}");
+    }
+
+    Ok(if report.is_intact() { 0 } else { 1 })
+}
+
+/// What `generate` prints, a password or a passphrase, with its options.
+struct GenerateOptions {
+    password: PasswordPolicy,
+    passphrase: Option<PassphrasePolicy>,
+    /// Built-in list name or path of a word list file.
+    wordlist: String,
+    rules: Option<PasswordRules>,
+    count: usize,
+}
+
+fn parse_generate_options(args: &[String]) -> Result<GenerateOptions, String> {
+    let mut options = GenerateOptions {
+        password: PasswordPolicy::default(),
+        passphrase: None,
+        wordlist: BuiltinList::default().to_string(),
+        rules: None,
+        count: 1,
+    };
+    let mut passphrase = PassphrasePolicy::default();
+    let policy = &mut options.password;
+    let mut args = args.iter();
+
+    let number = |flag: &str, value: Option<&String>| -> Result<usize, String> {
+        value
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| format!("{flag} expects a number"))
+    };
+
+    while let Some(arg) = args.next() {
+        let class = |name: &str| {
+            match name {
+                "lower" => Some(CharClass::Lowercase),
+                "upper" => Some(CharClass::Uppercase),
+                "digits" => Some(CharClass::Digits),
+                "symbols" => Some(CharClass::Symbols),
+                _ => None,
+            }
+        };
+
+        match arg.as_str() {
+            "--length" => {
+                policy.length = number(arg, args.next())?;
+            }
+            "--count" => {
+                options.count = number(arg, args.next())?;
+            }
+            "--symbols" => {
+                policy.symbol_set = args.next().ok_or("--symbols expects a set of characters")?.clone();
+            }
+            "--no-look-alikes" => {
+                policy.exclude_look_alikes = true;
+            }
+            "--passphrase" => {
+                options.passphrase = Some(PassphrasePolicy::default());
+            }
+            "--words" => {
+                passphrase.words = number(arg, args.next())?;
+            }
+            "--separator" => {
+                passphrase.separator = args.next().ok_or("--separator expects a string")?.clone();
+            }
+            "--capitalize" => {
+                passphrase.capitalization = args
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or("--capitalize expects lower, title, upper or random")?;
+            }
+            "--digit" => {
+                passphrase.insert_digit = true;
+            }
+            "--rules" => {
+                let descriptor = args.next().ok_or("--rules expects a passwordrules descriptor")?;
+                options.rules = Some(PasswordRules::parse(descriptor).map_err(|e| format!("--rules: {e}"))?);
+            }
+            "--site" => {
+                let site = args.next().ok_or("--site expects a domain or URL")?;
+                let host = vault_entry::host_of(site).ok_or("--site expects a domain or URL")?;
+                match PasswordRules::for_host(&host) {
+                    Some(rules) => options.rules = Some(rules),
+                    None => eprintln!("no bundled password rules for {host}"),
+                }
+            }
+            "--wordlist" => {
+                options.wordlist = args.next().ok_or("--wordlist expects large, short or a path")?.clone();
+            }
+            _ => {
+                if let Some(class) = arg.strip_prefix("--no-").and_then(class) {
+                    policy.rule_mut(class).enabled = false;
+                } else if let Some(class) = arg.strip_prefix("--min-").and_then(class) {
+                    policy.rule_mut(class).min = number(arg, args.next())?;
+                } else {
+                    return Err(format!("unknown option `{arg}`"));
+                }
+            }
+        }
+    }
+
+    if options.passphrase.is_some() {
+        options.passphrase = Some(passphrase);
+    }
+
+    Ok(options)
+}
+
+fn generate(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = match parse_generate_options(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return Ok(2);
+        }
+    };
+
+    let loaded;
+    let list = match options.wordlist.parse() {
+        Ok(builtin) => WordList::builtin(builtin),
+        Err(_) => {
+            loaded = WordList::load(Path::new(&options.wordlist))?;
+            &loaded
+        }
+    };
+
+    let entropy = match (&options.passphrase, &options.rules) {
+        (Some(passphrase), _) => passphrase.entropy(list),
+        (None, Some(rules)) => rules.restrict(&options.password).entropy(),
+        (None, None) => options.password.entropy(),
+    };
+    let entropy = match entropy {
+        Ok(entropy) => entropy,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return Ok(2);
+        }
+    };
+
+    for _ in 0..options.count {
+        let password = match (&options.passphrase, &options.rules) {
+            (Some(passphrase), _) => passphrase.generate(list)?,
+            (None, Some(rules)) => rules.generate(&options.password)?,
+            (None, None) => options.password.generate()?,
+        };
+
+        if let Some(rules) = &options.rules && let Err(e) = rules.check(&password) {
+            eprintln!("error: the passphrase breaks the site's password rules: {e}");
+            return Ok(2);
+        }
+        println!("{password}");
+    }
+    eprintln!("{entropy:.1} bits of entropy each");
+
+    Ok(0)
+}
+
+fn parse_derive_options(args: &[String]) -> Result<DerivationParams, String> {
+    let mut args = args.iter();
+    let site = args.next().filter(|site| !site.starts_with("--")).ok_or("derive expects a site")?;
+    let mut params = DerivationParams::new(site.clone(), String::new());
+
+    let number = |flag: &str, value: Option<&String>| -> Result<usize, String> {
+        value
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| format!("{flag} expects a number"))
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--login" => {
+                params.login = args.next().ok_or("--login expects a username")?.clone();
+            }
+            "--counter" => {
+                params.counter = number(arg, args.next())?.try_into().map_err(|_| "--counter is too large")?;
+            }
+            "--length" => {
+                params.length = number(arg, args.next())?;
+            }
+            "--no-lower" => {
+                params.lowercase = false;
+            }
+            "--no-upper" => {
+                params.uppercase = false;
+            }
+            "--no-digits" => {
+                params.digits = false;
+            }
+            "--no-symbols" => {
+                params.symbols = false;
+            }
+            _ => {
+                return Err(format!("unknown option `{arg}`"));
+            }
+        }
+    }
+
+    params.validate()?;
+
+    Ok(params)
+}
+
+/// Prints a derived password, and on stderr a fingerprint of the master password to
+/// notice typos.
+fn derive(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let params = match parse_derive_options(args) {
+        Ok(params) => params,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return Ok(2);
+        }
+    };
+
+    let secret = key_derivation::stateless_secret(&read_password("Master password: ")?);
+    eprintln!("master password fingerprint: {}", derived::fingerprint(&secret));
+    println!("{}", params.derive(&secret)?);
+
+    Ok(0)
+}
+
+fn strength() -> Result<i32, Box<dyn Error>> {
+    let password = read_password("Password: ")?;
+    let estimate = strength::estimate(&password, &[]);
+
+    println!("score: {}/4 ({})", estimate.score, estimate.label());
+    println!("guesses: 10^{:.1}", estimate.guesses.log10());
+
+    for (scenario, name) in [
+        (AttackScenario::OnlineThrottled, "online, throttled"),
+        (AttackScenario::OnlineUnthrottled, "online, unthrottled"),
+        (AttackScenario::OfflineSlowHash, "offline, slow hash"),
+        (AttackScenario::OfflineFastHash, "offline, fast hash"),
+    ] {
+        println!("crack time, {name}: {}", estimate.crack_time(scenario));
+    }
+
+    if let Some(warning) = &estimate.warning {
+        println!("warning: {warning}");
+    }
+    for suggestion in &estimate.suggestions {
+        println!("suggestion: {suggestion}");
+    }
+
+    Ok(0)
+}
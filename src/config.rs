@@ -0,0 +1,132 @@
+use std::env;
+use std::fs::{ self, File };
+use std::io::{ self, Read, Write };
+use std::path::{ Path, PathBuf };
+
+use serde::{ Deserialize, Serialize };
+
+use crate::{ expiry, rotation::RotationPolicy };
+
+/// Environment variable overriding the vault location, below the `--vault` flag.
+pub const VAULT_ENV: &str = "PM_VAULT";
+
+pub enum FileType {
+    Config,
+    Vault,
+}
+
+/// Location of a file in the per-user data directory.
+pub fn get_path(file_type: FileType) -> PathBuf {
+    let file_name = match file_type {
+        FileType::Config => "config.json",
+        FileType::Vault => "vault.bin",
+    };
+
+    #[cfg(target_os = "windows")]
+    {
+        PathBuf::from(env::var("LOCALAPPDATA").expect("Could not get LOCALAPPDATA"))
+            .join("PasswordManager")
+            .join(file_name)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        PathBuf::from(env::var("HOME").expect("Could not get HOME"))
+            .join("Library")
+            .join("Application Support")
+            .join("PasswordManager")
+            .join(file_name)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        PathBuf::from(env::var("HOME").expect("Could not get HOME"))
+            .join(".local")
+            .join("share")
+            .join("PasswordManager")
+            .join(file_name)
+    }
+}
+
+pub fn ensure_parents_exist(path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    Ok(())
+}
+
+/// Strength score required for new master passwords, see `strength::estimate`.
+pub const DEFAULT_MIN_MASTER_SCORE: u8 = 3;
+
+fn default_min_master_score() -> u8 {
+    DEFAULT_MIN_MASTER_SCORE
+}
+
+fn default_expiry_warning_days() -> u64 {
+    expiry::DEFAULT_WARNING_DAYS
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Config {
+    pub vault_path: PathBuf,
+    /// From 0 to 4, new master passwords scoring lower are refused.
+    #[serde(default = "default_min_master_score")]
+    pub min_master_score: u8,
+    /// When passwords are due for rotation.
+    #[serde(default)]
+    pub rotation: RotationPolicy,
+    /// Entries expiring within this many days are shown at unlock.
+    #[serde(default = "default_expiry_warning_days")]
+    pub expiry_warning_days: u64,
+    /// Command decrypting a file of a pass store for `import pass`, e.g.
+    /// `gpg --quiet --decrypt {}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pass_decrypt_command: Option<String>,
+}
+
+impl Config {
+    /// Reads the config file, `None` when it does not exist yet.
+    pub fn load() -> io::Result<Option<Self>> {
+        let config_path = get_path(FileType::Config);
+
+        if !config_path.exists() {
+            return Ok(None);
+        }
+
+        let mut file = File::open(config_path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let config = serde_json
+            ::from_str(&contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(Some(config))
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let config_path = get_path(FileType::Config);
+        ensure_parents_exist(&config_path)?;
+
+        let contents = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        File::create(config_path)?.write_all(contents.as_bytes())
+    }
+}
+
+/// Picks the vault to use: the `--vault` flag, then `PM_VAULT`, then the config file and
+/// finally the default location in the data directory.
+pub fn resolve_vault_path(flag: Option<PathBuf>, config: Option<&Config>) -> PathBuf {
+    if let Some(path) = flag {
+        return path;
+    }
+
+    if let Some(path) = env::var_os(VAULT_ENV).filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+
+    match config {
+        Some(config) => config.vault_path.clone(),
+        None => get_path(FileType::Vault),
+    }
+}
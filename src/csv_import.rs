@@ -0,0 +1,458 @@
+use std::io::{ self, Read };
+
+use strum::{ Display, EnumIter, EnumString, IntoEnumIterator };
+
+use crate::{ import::ImportBatch, timestamp, vault_entry::{ self, VaultEntry } };
+
+const FIELDS: &str = "service, username, password, url, notes, folder, tags, created, modified";
+
+/// Password managers and browsers whose CSV exports are recognised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumIter, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum CsvPreset {
+    Chrome,
+    Firefox,
+    Bitwarden,
+    LastPass,
+    KeePassXC,
+}
+
+impl CsvPreset {
+    /// Columns that tell the exports apart, the most specific presets first in the
+    /// order of `detect`.
+    fn signature(self) -> &'static [&'static str] {
+        match self {
+            CsvPreset::Chrome => &["name", "url", "username", "password"],
+            CsvPreset::Firefox => &["url", "username", "password", "httpRealm"],
+            CsvPreset::Bitwarden => &["type", "name", "login_uri", "login_username", "login_password"],
+            CsvPreset::LastPass => &["url", "username", "password", "extra", "name", "grouping"],
+            CsvPreset::KeePassXC => &["Group", "Title", "Username", "Password"],
+        }
+    }
+
+    pub fn mapping(self) -> ColumnMapping {
+        let column = |name: &str| Some(name.to_string());
+
+        match self {
+            CsvPreset::Chrome => ColumnMapping {
+                service: column("name"),
+                username: column("username"),
+                password: column("password"),
+                url: column("url"),
+                notes: column("note"),
+                ..ColumnMapping::default()
+            },
+            CsvPreset::Firefox => ColumnMapping {
+                username: column("username"),
+                password: column("password"),
+                url: column("url"),
+                created: column("timeCreated"),
+                modified: column("timePasswordChanged"),
+                ..ColumnMapping::default()
+            },
+            CsvPreset::Bitwarden => ColumnMapping {
+                service: column("name"),
+                username: column("login_username"),
+                password: column("login_password"),
+                url: column("login_uri"),
+                notes: column("notes"),
+                folder: column("folder"),
+                ..ColumnMapping::default()
+            },
+            CsvPreset::LastPass => ColumnMapping {
+                service: column("name"),
+                username: column("username"),
+                password: column("password"),
+                url: column("url"),
+                notes: column("extra"),
+                folder: column("grouping"),
+                ..ColumnMapping::default()
+            },
+            CsvPreset::KeePassXC => ColumnMapping {
+                service: column("Title"),
+                username: column("Username"),
+                password: column("Password"),
+                url: column("URL"),
+                notes: column("Notes"),
+                folder: column("Group"),
+                created: column("Created"),
+                modified: column("Last Modified"),
+                ..ColumnMapping::default()
+            },
+        }
+    }
+
+    /// The export `headers` come from, if recognised.
+    pub fn detect(headers: &[String]) -> Option<Self> {
+        let order = [
+            CsvPreset::Bitwarden,
+            CsvPreset::KeePassXC,
+            CsvPreset::LastPass,
+            CsvPreset::Firefox,
+            CsvPreset::Chrome,
+        ];
+
+        order.into_iter().find(|preset| {
+            preset
+                .signature()
+                .iter()
+                .all(|column| headers.iter().any(|h| h.trim().eq_ignore_ascii_case(column)))
+        })
+    }
+
+    /// Names of the presets, for messages.
+    pub fn names() -> String {
+        CsvPreset::iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// Columns holding the fields of an entry, by header name. Unmapped fields stay empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnMapping {
+    pub service: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub url: Option<String>,
+    pub notes: Option<String>,
+    pub folder: Option<String>,
+    /// Tags separated by commas or semicolons.
+    pub tags: Option<String>,
+    /// Dates in RFC 3339 or seconds or milliseconds since the Unix epoch.
+    pub created: Option<String>,
+    pub modified: Option<String>,
+}
+
+impl ColumnMapping {
+    /// Parses `field=Column` pairs separated by commas, e.g.
+    /// `service=Title,username=Login,password=Secret,url=Address`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut mapping = ColumnMapping::default();
+
+        for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let Some((field, column)) = pair.split_once('=') else {
+                return Err(format!("`{pair}` is not field=column"));
+            };
+
+            let slot = match field.trim().to_lowercase().as_str() {
+                "service" | "name" | "title" => &mut mapping.service,
+                "username" | "user" | "login" => &mut mapping.username,
+                "password" => &mut mapping.password,
+                "url" => &mut mapping.url,
+                "notes" | "note" => &mut mapping.notes,
+                "folder" | "group" => &mut mapping.folder,
+                "tags" | "tag" => &mut mapping.tags,
+                "created" => &mut mapping.created,
+                "modified" => &mut mapping.modified,
+                field => {
+                    return Err(format!("unknown field `{field}`, expected one of {FIELDS}"));
+                }
+            };
+            *slot = Some(column.trim().to_string());
+        }
+
+        if mapping.password.is_none() {
+            return Err("the password column must be mapped".to_string());
+        }
+        if mapping.service.is_none() && mapping.url.is_none() {
+            return Err("the service or url column must be mapped".to_string());
+        }
+
+        Ok(mapping)
+    }
+
+    fn columns(&self) -> [&Option<String>; 9] {
+        [
+            &self.service,
+            &self.username,
+            &self.password,
+            &self.url,
+            &self.notes,
+            &self.folder,
+            &self.tags,
+            &self.created,
+            &self.modified,
+        ]
+    }
+}
+
+/// How the columns of a CSV file are read.
+#[derive(Debug, Clone)]
+pub enum CsvFormat {
+    /// Recognised from the header row.
+    Detect,
+    Preset(CsvPreset),
+    Custom(Box<ColumnMapping>),
+}
+
+/// Column positions of a mapping in one file.
+struct Columns {
+    positions: [Option<usize>; 9],
+    type_column: Option<usize>,
+}
+
+/// Reads the entries of a CSV export with a header row.
+pub fn import(reader: impl Read, format: CsvFormat) -> io::Result<ImportBatch> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+
+    let (preset, mapping, description) = match format {
+        CsvFormat::Preset(preset) => (Some(preset), preset.mapping(), format!("{preset} CSV")),
+        CsvFormat::Custom(mapping) => (None, *mapping, "CSV with mapped columns".to_string()),
+        CsvFormat::Detect => {
+            let preset = CsvPreset::detect(&headers).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "could not recognise the columns {}, pick a preset ({}) or map the columns",
+                        headers.join(", "),
+                        CsvPreset::names()
+                    )
+                )
+            })?;
+            (Some(preset), preset.mapping(), format!("{preset} CSV"))
+        }
+    };
+
+    let position = |column: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(column.trim()));
+
+    // Presets tolerate missing optional columns, exports differ between versions.
+    let mut positions = [None; 9];
+    for (slot, column) in positions.iter_mut().zip(mapping.columns()) {
+        let Some(column) = column else {
+            continue;
+        };
+
+        *slot = position(column);
+        if slot.is_none() && preset.is_none() {
+            return Err(
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("no column `{column}` in the CSV file, its columns are {}", headers.join(", "))
+                )
+            );
+        }
+    }
+
+    let columns = Columns {
+        positions,
+        type_column: (preset == Some(CsvPreset::Bitwarden)).then(|| position("type")).flatten(),
+    };
+
+    let mut batch = ImportBatch { format: description, ..ImportBatch::default() };
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line());
+
+        match read_entry(&record, &columns, preset) {
+            Ok(Some(entry)) => batch.entries.push(entry),
+            Ok(None) => {}
+            Err(reason) => batch.skipped.push(format!("line {line}: {reason}")),
+        }
+    }
+
+    Ok(batch)
+}
+
+/// The entry of one row, `None` for an empty row.
+fn read_entry(
+    record: &csv::StringRecord,
+    columns: &Columns,
+    preset: Option<CsvPreset>
+) -> Result<Option<VaultEntry>, String> {
+    // Passwords and notes are kept byte for byte, spaces around them may be meant.
+    let [service, username, password, url, notes, folder, tags, created, modified] = columns.positions.map(
+        |position| position.and_then(|p| record.get(p)).filter(|value| !value.trim().is_empty())
+    );
+    let [service, username, url, folder, tags, created, modified] = [
+        service,
+        username,
+        url,
+        folder,
+        tags,
+        created,
+        modified,
+    ].map(|value| value.map(str::trim));
+
+    if [service, username, password, url, notes].iter().all(Option::is_none) {
+        return Ok(None);
+    }
+
+    if let Some(kind) = columns.type_column.and_then(|p| record.get(p)) && kind != "login" {
+        return Err(format!("{kind} item, only logins are imported"));
+    }
+    if preset == Some(CsvPreset::LastPass) && url == Some("http://sn") {
+        return Err("secure note, only logins are imported".to_string());
+    }
+
+    let service = service
+        .map(str::to_string)
+        .or_else(|| url.and_then(vault_entry::host_of).map(|host| host.trim_start_matches("www.").to_string()))
+        .ok_or("no name nor URL")?;
+
+    let mut entry = VaultEntry::new(
+        service,
+        username.map(str::to_string),
+        password.unwrap_or_default().as_bytes().to_vec()
+    );
+    entry.url = url.map(str::to_string);
+    entry.notes = notes.map(str::to_string);
+    entry.folder = folder
+        .map(|folder| {
+            let folder = folder.replace('\\', "/");
+            // KeePassXC paths start with the root group of the database.
+            match preset {
+                Some(CsvPreset::KeePassXC) => folder.split_once('/').map_or(String::new(), |(_, rest)| rest.to_string()),
+                _ => folder,
+            }
+        })
+        .map(|folder| folder.trim_matches('/').to_string())
+        .filter(|folder| !folder.is_empty());
+    entry.tags = tags
+        .map(|tags| {
+            tags.split([',', ';'])
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let created = created.and_then(parse_time);
+    let modified = modified.and_then(parse_time);
+    if let Some(time) = created.or(modified) {
+        entry.created = time;
+    }
+    if let Some(time) = modified.or(created) {
+        entry.modified = time;
+    }
+
+    Ok(Some(entry))
+}
+
+/// RFC 3339, or seconds or milliseconds since the Unix epoch as Firefox writes them.
+fn parse_time(value: &str) -> Option<u64> {
+    match value.parse::<u64>() {
+        Ok(ms) if ms > 100_000_000_000 => Some(ms / 1000),
+        Ok(seconds) => Some(seconds),
+        Err(_) => timestamp::parse_datetime(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import_str(csv: &str, format: CsvFormat) -> ImportBatch {
+        import(csv.as_bytes(), format).unwrap()
+    }
+
+    #[test]
+    fn detects_presets_from_headers() {
+        let headers = |line: &str| line.split(',').map(str::to_string).collect::<Vec<_>>();
+
+        assert_eq!(CsvPreset::detect(&headers("name,url,username,password,note")), Some(CsvPreset::Chrome));
+        assert_eq!(
+            CsvPreset::detect(&headers("url,username,password,httpRealm,formActionOrigin,guid")),
+            Some(CsvPreset::Firefox)
+        );
+        assert_eq!(
+            CsvPreset::detect(&headers("folder,favorite,type,name,notes,fields,login_uri,login_username,login_password")),
+            Some(CsvPreset::Bitwarden)
+        );
+        assert_eq!(CsvPreset::detect(&headers("url,username,password,totp,extra,name,grouping,fav")), Some(CsvPreset::LastPass));
+        assert_eq!(
+            CsvPreset::detect(&headers("Group,Title,Username,Password,URL,Notes")),
+            Some(CsvPreset::KeePassXC)
+        );
+        assert_eq!(CsvPreset::detect(&headers("site,login,secret")), None);
+    }
+
+    #[test]
+    fn imports_a_firefox_export() {
+        let batch = import_str(
+            "url,username,password,httpRealm,formActionOrigin,guid,timeCreated,timeLastUsed,timePasswordChanged\n\
+             https://www.example.com,alice,s3cret,,https://www.example.com,{x},1700000000000,1700000000000,1710000000000\n",
+            CsvFormat::Detect
+        );
+
+        assert_eq!(batch.format, "firefox CSV");
+        let [entry] = batch.entries.as_slice() else { panic!("{:?}", batch.entries) };
+        assert_eq!(entry.service, "example.com");
+        assert_eq!(entry.username.as_deref(), Some("alice"));
+        assert_eq!(entry.password, b"s3cret");
+        assert_eq!(entry.created, 1_700_000_000);
+        assert_eq!(entry.modified, 1_710_000_000);
+    }
+
+    #[test]
+    fn keeps_spaces_around_passwords() {
+        let batch = import_str(
+            "name,url,username,password,note\n  Example  , https://example.com , alice ,\" pw \",\n",
+            CsvFormat::Preset(CsvPreset::Chrome)
+        );
+
+        let [entry] = batch.entries.as_slice() else { panic!("{:?}", batch.entries) };
+        assert_eq!(entry.service, "Example");
+        assert_eq!(entry.username.as_deref(), Some("alice"));
+        assert_eq!(entry.url.as_deref(), Some("https://example.com"));
+        assert_eq!(entry.password, b" pw ");
+    }
+
+    #[test]
+    fn imports_keepassxc_folders_and_dates() {
+        let batch = import_str(
+            "\"Group\",\"Title\",\"Username\",\"Password\",\"URL\",\"Notes\",\"Created\",\"Last Modified\"\n\
+             \"Root/Work/Mail\",\"Mail\",\"bob\",\"pw, with comma\",\"\",\"line 1\nline 2\",\"2024-01-31T10:20:30Z\",\"2024-02-01T12:00:00+02:00\"\n\
+             \"Root\",\"Top\",\"\",\"pw\",\"\",\"\",\"\",\"\"\n",
+            CsvFormat::Detect
+        );
+
+        let [mail, top] = batch.entries.as_slice() else { panic!("{:?}", batch.entries) };
+        assert_eq!(mail.folder.as_deref(), Some("Work/Mail"));
+        assert_eq!(mail.password, b"pw, with comma");
+        assert_eq!(mail.notes.as_deref(), Some("line 1\nline 2"));
+        assert_eq!(mail.created, timestamp::parse_datetime("2024-01-31T10:20:30Z").unwrap());
+        assert_eq!(mail.modified, timestamp::parse_datetime("2024-02-01T10:00:00Z").unwrap());
+        assert_eq!(top.folder, None);
+    }
+
+    #[test]
+    fn skips_what_is_not_a_login() {
+        let batch = import_str(
+            "folder,favorite,type,name,notes,fields,login_uri,login_username,login_password\n\
+             ,,note,A note,text,,,,\n\
+             Bank,,login,Bank,,,https://bank.example,carol,pw\n\
+             ,,,,,,,,\n\
+             ,,login,,,,,dave,pw\n",
+            CsvFormat::Preset(CsvPreset::Bitwarden)
+        );
+
+        let [bank] = batch.entries.as_slice() else { panic!("{:?}", batch.entries) };
+        assert_eq!(bank.folder.as_deref(), Some("Bank"));
+        assert_eq!(batch.skipped, ["line 2: note item, only logins are imported", "line 5: no name nor URL"]);
+    }
+
+    #[test]
+    fn maps_custom_columns() {
+        let mapping = ColumnMapping::parse("title=Site, login=User, password=Secret, tags=Labels").unwrap();
+        let batch = import_str(
+            "Site,User,Secret,Labels\nForum,erin,hunter2,\"social; old\"\n",
+            CsvFormat::Custom(Box::new(mapping))
+        );
+
+        let [entry] = batch.entries.as_slice() else { panic!("{:?}", batch.entries) };
+        assert_eq!(entry.service, "Forum");
+        assert_eq!(entry.tags, ["social", "old"]);
+
+        let mapping = ColumnMapping::parse("service=Site,password=Missing").unwrap();
+        assert!(import("Site,Secret\n".as_bytes(), CsvFormat::Custom(Box::new(mapping))).is_err());
+    }
+
+    #[test]
+    fn rejects_incomplete_mappings() {
+        assert!(ColumnMapping::parse("service=Site").is_err());
+        assert!(ColumnMapping::parse("username=User,password=Secret").is_err());
+        assert!(ColumnMapping::parse("service=Site,password=Secret,colour=Red").is_err());
+        assert!(ColumnMapping::parse("service,password=Secret").is_err());
+    }
+}
@@ -0,0 +1,918 @@
+use std::collections::HashMap;
+use std::io;
+
+use base64::{ Engine, engine::general_purpose::URL_SAFE_NO_PAD };
+use serde::{ Deserialize, Serialize };
+use serde_json::{ Map, Value };
+
+use crate::{
+    import::{ self, ImportBatch },
+    timestamp,
+    vault_entry::{ self, CustomField, EntryKind, VaultEntry },
+};
+
+/// Version of the documents written. Those of another major version are refused.
+const VERSION_MAJOR: u32 = 1;
+const VERSION_MINOR: u32 = 0;
+
+const STRING: &str = "string";
+const CONCEALED: &str = "concealed-string";
+const DATE: &str = "date";
+const YEAR_MONTH: &str = "year-month";
+const COUNTRY: &str = "country-code";
+const SUBDIVISION: &str = "subdivision-code";
+
+/// Custom fields holding what one side has no field for, named as in the Bitwarden
+/// import: the URLs of an item after the first, and a TOTP that is not an `otpauth://`
+/// URI.
+const URL_FIELD: &str = "URL";
+const TOTP_FIELD: &str = "TOTP";
+/// Tag of the items marked as favorite.
+const FAVORITE_TAG: &str = "favorite";
+
+/// A credential value kept in a custom field: its key in the credential, the name of
+/// the custom field, and its CXF field type, concealed ones being protected.
+type Detail = (&'static str, &'static str, &'static str);
+
+/// Card details, named as in the Bitwarden import. The expiry date is split into the
+/// month and the year.
+const CREDIT_CARD: [Detail; 6] = [
+    ("fullName", "Cardholder name", STRING),
+    ("cardType", "Brand", STRING),
+    ("number", "Number", CONCEALED),
+    ("verificationNumber", "Security code", CONCEALED),
+    ("pin", "PIN", CONCEALED),
+    ("validFrom", "Valid from", YEAR_MONTH),
+];
+
+const PERSON_NAME: [Detail; 9] = [
+    ("title", "Title", STRING),
+    ("given", "First name", STRING),
+    ("givenInformal", "Nickname", STRING),
+    ("given2", "Middle name", STRING),
+    ("surnamePrefix", "Surname prefix", STRING),
+    ("surname", "Last name", STRING),
+    ("surname2", "Second last name", STRING),
+    ("credentials", "Credentials", STRING),
+    ("generation", "Generation", STRING),
+];
+
+const ADDRESS: [Detail; 6] = [
+    ("streetAddress", "Address 1", STRING),
+    ("postalCode", "Postal code", STRING),
+    ("city", "City", STRING),
+    ("territory", "State", SUBDIVISION),
+    ("country", "Country", COUNTRY),
+    ("tel", "Phone", STRING),
+];
+
+const PASSPORT: [Detail; 12] = [
+    ("passportNumber", "Passport number", CONCEALED),
+    ("passportType", "Passport type", STRING),
+    ("issuingCountry", "Passport issuing country", COUNTRY),
+    ("issuingAuthority", "Passport issuing authority", STRING),
+    ("issueDate", "Passport issue date", DATE),
+    ("expiryDate", "Passport expiry date", DATE),
+    ("fullName", "Passport full name", STRING),
+    ("nationalIdentificationNumber", "National identification number", CONCEALED),
+    ("nationality", "Nationality", STRING),
+    ("birthDate", "Birth date", DATE),
+    ("birthPlace", "Birth place", STRING),
+    ("sex", "Sex", STRING),
+];
+
+const DRIVERS_LICENSE: [Detail; 9] = [
+    ("licenseNumber", "License number", CONCEALED),
+    ("licenseClass", "License class", STRING),
+    ("territory", "License state", SUBDIVISION),
+    ("country", "License country", COUNTRY),
+    ("issuingAuthority", "License issuing authority", STRING),
+    ("issueDate", "License issue date", DATE),
+    ("expiryDate", "License expiry date", DATE),
+    ("fullName", "License full name", STRING),
+    ("birthDate", "Birth date", DATE),
+];
+
+const IDENTITY_DOCUMENT: [Detail; 11] = [
+    ("documentNumber", "ID number", CONCEALED),
+    ("issuingCountry", "ID issuing country", COUNTRY),
+    ("issuingAuthority", "ID issuing authority", STRING),
+    ("issueDate", "ID issue date", DATE),
+    ("expiryDate", "ID expiry date", DATE),
+    ("fullName", "ID full name", STRING),
+    ("identificationNumber", "National identification number", CONCEALED),
+    ("nationality", "Nationality", STRING),
+    ("birthDate", "Birth date", DATE),
+    ("birthPlace", "Birth place", STRING),
+    ("sex", "Sex", STRING),
+];
+
+/// Credentials of an identity entry, in the order they are written.
+const IDENTITY_CREDENTIALS: [(&str, &[Detail]); 5] = [
+    ("person-name", &PERSON_NAME),
+    ("address", &ADDRESS),
+    ("passport", &PASSPORT),
+    ("drivers-license", &DRIVERS_LICENSE),
+    ("identity-document", &IDENTITY_DOCUMENT),
+];
+
+/// Credentials without an entry kind of their own, kept in custom fields of any entry.
+/// The username, URL, expiry date and passphrase they may have go to the entry.
+const API_KEY: [Detail; 3] = [
+    ("key", "API key", CONCEALED),
+    ("keyType", "API key type", STRING),
+    ("validFrom", "API key valid from", DATE),
+];
+
+const WIFI: [Detail; 3] = [
+    ("ssid", "SSID", STRING),
+    ("networkSecurityType", "Wi-Fi security", STRING),
+    ("hidden", "Hidden network", STRING),
+];
+
+const SSH_KEY: [Detail; 6] = [
+    ("keyType", "SSH key type", STRING),
+    ("privateKey", "SSH private key", CONCEALED),
+    ("keyComment", "SSH key comment", STRING),
+    ("creationDate", "SSH key creation date", DATE),
+    ("expiryDate", "SSH key expiry date", DATE),
+    ("keyGenerationSource", "SSH key source", STRING),
+];
+
+/// A CXF document, the header of an export and the accounts in it.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Header {
+    version: Version,
+    exporter_rp_id: String,
+    exporter_display_name: String,
+    timestamp: u64,
+    accounts: Vec<Account>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Version {
+    major: u32,
+    minor: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Account {
+    id: String,
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    email: String,
+    #[serde(default)]
+    collections: Vec<Collection>,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Collection {
+    id: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    items: Vec<LinkedItem>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sub_collections: Vec<Collection>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct LinkedItem {
+    item: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    creation_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified_at: Option<u64>,
+    #[serde(default)]
+    title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    favorite: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<Scope>,
+    #[serde(default)]
+    credentials: Vec<Credential>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Scope {
+    #[serde(default)]
+    urls: Vec<String>,
+    #[serde(default)]
+    android_apps: Vec<Value>,
+}
+
+/// A credential of an item, its fields kept as JSON as they depend on its type.
+#[derive(Debug, Deserialize, Serialize)]
+struct Credential {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(flatten)]
+    fields: Map<String, Value>,
+}
+
+impl Credential {
+    fn new(kind: &str) -> Self {
+        Credential { kind: kind.to_string(), fields: Map::new() }
+    }
+
+    /// The non-blank value of a field, an editable field or a plain string or number, as
+    /// is: spaces around a password or key are part of it.
+    fn secret(&self, key: &str) -> Option<String> {
+        let value = self.fields.get(key)?;
+        let value = value.get("value").unwrap_or(value);
+
+        let text = match value {
+            Value::String(text) => text.clone(),
+            Value::Number(number) => number.to_string(),
+            Value::Bool(flag) => flag.to_string(),
+            _ => return None,
+        };
+        (!text.trim().is_empty()).then_some(text)
+    }
+
+    /// The non-blank value of a field, trimmed.
+    fn text(&self, key: &str) -> Option<String> {
+        self.secret(key).map(|text| text.trim().to_string())
+    }
+
+    fn set(&mut self, key: &str, field_type: &str, value: &str) {
+        let field = serde_json::json!({ "fieldType": field_type, "value": value });
+        self.fields.insert(key.to_string(), field);
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Reads the items of every account of a CXF JSON document. Credentials the vault has no
+/// place for, e.g. passkeys, are listed in `left_out`, and items holding nothing else in
+/// `skipped`.
+pub fn import(json: &str) -> io::Result<ImportBatch> {
+    let header: Header = serde_json::from_str(json).map_err(|e| invalid_data(format!("invalid CXF document: {e}")))?;
+    if header.version.major != VERSION_MAJOR {
+        return Err(
+            invalid_data(format!("unsupported CXF version {}.{}", header.version.major, header.version.minor))
+        );
+    }
+
+    let mut batch = ImportBatch { format: "CXF JSON".to_string(), ..ImportBatch::default() };
+    for account in &header.accounts {
+        let mut folders = HashMap::new();
+        collect_folders(&account.collections, "", &mut folders);
+
+        for item in &account.items {
+            let title = if item.title.trim().is_empty() { "untitled item" } else { item.title.trim() };
+            let mut left_out = Vec::new();
+
+            match read_item(item, folders.get(item.id.as_str()), &mut left_out) {
+                Ok(entry) => {
+                    if !left_out.is_empty() {
+                        batch.left_out.push(format!("{}: {}", import::label(&entry), left_out.join(", ")));
+                    }
+                    batch.entries.push(entry);
+                }
+                Err(reason) => batch.skipped.push(format!("{title}: {reason}")),
+            }
+        }
+    }
+
+    Ok(batch)
+}
+
+/// Maps the ids of the items in `collections` to the slash separated path of the
+/// deepest collection they are in.
+fn collect_folders<'a>(collections: &'a [Collection], parent: &str, folders: &mut HashMap<&'a str, String>) {
+    for collection in collections {
+        let title = collection.title.trim().trim_matches('/');
+        let path = match (parent.is_empty(), title.is_empty()) {
+            (_, true) => parent.to_string(),
+            (true, false) => title.to_string(),
+            (false, false) => format!("{parent}/{title}"),
+        };
+
+        if !path.is_empty() {
+            for linked in &collection.items {
+                folders.insert(linked.item.as_str(), path.clone());
+            }
+        }
+        collect_folders(&collection.sub_collections, &path, folders);
+    }
+}
+
+/// Adds a custom field, unless another credential of the item already gave it.
+fn push_field(fields: &mut Vec<CustomField>, name: &str, value: String, protected: bool) {
+    if !fields.iter().any(|field| field.name == name && field.value == value) {
+        fields.push(CustomField { name: name.to_string(), value, protected });
+    }
+}
+
+fn details_to_fields(credential: &Credential, details: &[Detail], fields: &mut Vec<CustomField>) {
+    for (key, name, field_type) in details {
+        let concealed = *field_type == CONCEALED;
+        let value = if concealed { credential.secret(key) } else { credential.text(key) };
+        if let Some(value) = value {
+            push_field(fields, name, value, concealed);
+        }
+    }
+}
+
+fn read_item(item: &Item, folder: Option<&String>, left_out: &mut Vec<String>) -> Result<VaultEntry, String> {
+    let mut username = None;
+    let mut password = None;
+    let mut totp = None;
+    let mut notes: Vec<String> = Vec::new();
+    let mut expires = None;
+    let mut urls: Vec<String> = item.scope
+        .iter()
+        .flat_map(|scope| &scope.urls)
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+    let mut custom_fields = Vec::new();
+    let mut kinds = Vec::new();
+
+    for credential in &item.credentials {
+        match credential.kind.as_str() {
+            "basic-auth" => {
+                let (user, secret) = (credential.text("username"), credential.secret("password"));
+                if username.is_none() && password.is_none() {
+                    (username, password) = (user, secret);
+                } else {
+                    // A second login of the item.
+                    if let Some(user) = user {
+                        push_field(&mut custom_fields, "Username", user, false);
+                    }
+                    if let Some(secret) = secret {
+                        push_field(&mut custom_fields, "Password", secret, true);
+                    }
+                }
+                kinds.push(EntryKind::Login);
+            }
+            "totp" => {
+                match totp_uri(credential, &item.title) {
+                    Some(uri) if totp.is_none() => totp = Some(uri),
+                    Some(uri) => push_field(&mut custom_fields, TOTP_FIELD, uri, true),
+                    None => left_out.push("TOTP without a secret".to_string()),
+                }
+            }
+            "note" => notes.extend(credential.text("content")),
+            "credit-card" => {
+                details_to_fields(credential, &CREDIT_CARD, &mut custom_fields);
+                if let Some((year, month)) = credential.text("expiryDate").as_deref().and_then(|d| d.split_once('-')) {
+                    push_field(&mut custom_fields, "Expiration month", month.trim_start_matches('0').to_string(), false);
+                    push_field(&mut custom_fields, "Expiration year", year.to_string(), false);
+                }
+                kinds.push(EntryKind::Card);
+            }
+            kind if IDENTITY_CREDENTIALS.iter().any(|(name, _)| *name == kind) => {
+                for (_, details) in IDENTITY_CREDENTIALS.iter().filter(|(name, _)| *name == kind) {
+                    details_to_fields(credential, details, &mut custom_fields);
+                }
+                kinds.push(EntryKind::Identity);
+            }
+            "api-key" => {
+                details_to_fields(credential, &API_KEY, &mut custom_fields);
+                if let Some(user) = credential.text("username") {
+                    match &username {
+                        None => username = Some(user),
+                        Some(_) => push_field(&mut custom_fields, "API username", user, false),
+                    }
+                }
+                urls.extend(credential.text("url"));
+                match credential.text("expiryDate") {
+                    Some(date) if expires.is_none() && timestamp::parse_date(&date).is_some() => {
+                        expires = timestamp::parse_date(&date);
+                    }
+                    Some(date) => push_field(&mut custom_fields, "API key expiry date", date, false),
+                    None => {}
+                }
+            }
+            "wifi" => {
+                details_to_fields(credential, &WIFI, &mut custom_fields);
+                match credential.secret("passphrase") {
+                    Some(passphrase) if password.is_none() => password = Some(passphrase),
+                    Some(passphrase) => push_field(&mut custom_fields, "Wi-Fi passphrase", passphrase, true),
+                    None => {}
+                }
+            }
+            "ssh-key" => details_to_fields(credential, &SSH_KEY, &mut custom_fields),
+            "generated-password" => {
+                if let Some(generated) = credential.secret("password") {
+                    push_field(&mut custom_fields, "Generated password", generated, true);
+                }
+            }
+            "custom-fields" => {
+                let fields = credential.fields.get("fields").and_then(Value::as_array).into_iter().flatten();
+                let set_label = credential.text("label");
+
+                for field in fields {
+                    let Some(value) = field.get("value").and_then(Value::as_str).filter(|v| !v.is_empty()) else {
+                        continue;
+                    };
+                    let name = field
+                        .get("label")
+                        .and_then(Value::as_str)
+                        .filter(|label| !label.trim().is_empty())
+                        .map(str::to_string)
+                        .or_else(|| set_label.clone())
+                        .unwrap_or_else(|| "Field".to_string());
+                    let protected = field.get("fieldType").and_then(Value::as_str) == Some(CONCEALED);
+
+                    push_field(&mut custom_fields, &name, value.to_string(), protected);
+                }
+            }
+            "passkey" => {
+                match credential.text("rpId") {
+                    Some(rp_id) => left_out.push(format!("passkey for {rp_id}")),
+                    None => left_out.push("passkey".to_string()),
+                }
+            }
+            "file" => {
+                match credential.text("name") {
+                    Some(name) => left_out.push(format!("file {name}")),
+                    None => left_out.push("file".to_string()),
+                }
+            }
+            kind => left_out.push(format!("{kind} credential")),
+        }
+    }
+
+    let android_apps = item.scope.as_ref().map_or(0, |scope| scope.android_apps.len());
+    match android_apps {
+        0 => {}
+        1 => left_out.push("1 Android app".to_string()),
+        count => left_out.push(format!("{count} Android apps")),
+    }
+
+    if username.is_none() && password.is_none() && totp.is_none() && notes.is_empty() && custom_fields.is_empty() {
+        return Err(match left_out.as_slice() {
+            [] => "no credentials".to_string(),
+            missing => format!("only {}, not supported", missing.join(", ")),
+        });
+    }
+
+    let mut urls = urls.into_iter();
+    let url = urls.next();
+    let service = Some(item.title.trim().to_string())
+        .filter(|title| !title.is_empty())
+        .or_else(|| url.as_deref().and_then(vault_entry::host_of))
+        .ok_or("no title nor URL")?;
+
+    // A login, card or identity is what it holds besides notes and custom fields; an
+    // item with several of them is kept as a login.
+    let kind = match kinds.first() {
+        Some(kind) if kinds.iter().all(|k| k == kind) => *kind,
+        Some(_) => EntryKind::Login,
+        None if password.is_none() && username.is_none() && totp.is_none() && url.is_none() => EntryKind::SecureNote,
+        None => EntryKind::Login,
+    };
+
+    let mut entry = VaultEntry::new(service, username, password.unwrap_or_default().into_bytes());
+    entry.kind = kind;
+    entry.url = url;
+    entry.totp = totp;
+    entry.expires = expires;
+    entry.notes = Some(notes.join("\n\n")).filter(|notes| !notes.is_empty());
+    entry.folder = folder.cloned();
+
+    entry.tags = item.tags
+        .iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    if item.favorite == Some(true) && !entry.tags.iter().any(|t| t.eq_ignore_ascii_case(FAVORITE_TAG)) {
+        entry.tags.push(FAVORITE_TAG.to_string());
+    }
+
+    custom_fields.extend(urls.map(|url| CustomField { name: URL_FIELD.to_string(), value: url, protected: false }));
+    entry.custom_fields = custom_fields;
+
+    let created = item.creation_at.filter(|time| *time > 0);
+    let modified = item.modified_at.filter(|time| *time > 0);
+    if let Some(time) = created.or(modified) {
+        entry.created = time;
+    }
+    if let Some(time) = modified.or(created) {
+        entry.modified = time;
+    }
+
+    Ok(entry)
+}
+
+/// `otpauth://` URI of a TOTP credential, its parameters only given when not the
+/// defaults.
+fn totp_uri(credential: &Credential, title: &str) -> Option<String> {
+    let secret: String = credential
+        .text("secret")?
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .collect::<String>()
+        .to_uppercase();
+    let issuer = credential.text("issuer").unwrap_or_else(|| title.trim().to_string());
+    let username = credential.text("username").unwrap_or_default();
+
+    let mut uri = vault_entry::totp_uri(&secret, &issuer, &username);
+    if let Some(period) = credential.text("period").filter(|p| p != "30") {
+        uri.push_str(&format!("&period={period}"));
+    }
+    if let Some(digits) = credential.text("digits").filter(|d| d != "6") {
+        uri.push_str(&format!("&digits={digits}"));
+    }
+    if let Some(algorithm) = credential.text("algorithm").filter(|a| !a.eq_ignore_ascii_case("sha1")) {
+        uri.push_str(&format!("&algorithm={}", algorithm.to_uppercase()));
+    }
+
+    Some(uri)
+}
+
+/// The TOTP credential of an `otpauth://totp/` URI, `None` for any other secret.
+fn totp_credential(uri: &str) -> Option<Credential> {
+    let rest = uri.strip_prefix("otpauth://totp/")?;
+    let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let label = percent_decode(label);
+    let (label_issuer, username) = match label.split_once(':') {
+        Some((issuer, username)) => (Some(issuer.trim().to_string()), username.trim().to_string()),
+        None => (None, label.trim().to_string()),
+    };
+
+    let parameters: HashMap<&str, String> = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key, percent_decode(value)))
+        .collect();
+    let secret = parameters.get("secret").filter(|secret| !secret.is_empty())?;
+
+    let mut credential = Credential::new("totp");
+    credential.fields.insert("secret".to_string(), Value::from(secret.to_uppercase()));
+    for (key, default) in [("period", 30), ("digits", 6)] {
+        let value = parameters.get(key).and_then(|v| v.parse::<u64>().ok()).unwrap_or(default);
+        credential.fields.insert(key.to_string(), Value::from(value));
+    }
+    let algorithm = parameters.get("algorithm").map_or("sha1".to_string(), |a| a.to_lowercase());
+    credential.fields.insert("algorithm".to_string(), Value::from(algorithm));
+    if !username.is_empty() {
+        credential.fields.insert("username".to_string(), Value::from(username));
+    }
+    if let Some(issuer) = parameters.get("issuer").cloned().or(label_issuer).filter(|i| !i.is_empty()) {
+        credential.fields.insert("issuer".to_string(), Value::from(issuer));
+    }
+
+    Some(credential)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A CXF id, the bytes of an entry id in unpadded base64url.
+fn id(entry_id: &str) -> String {
+    let bytes = hex::decode(entry_id)
+        .ok()
+        .filter(|bytes| !bytes.is_empty() && bytes.len() <= 64)
+        .unwrap_or_else(|| hex::decode(vault_entry::generate_id()).expect("generated ids are hexadecimal"));
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Moves the custom fields of the details into a credential of type `kind`, `None` when
+/// there are none.
+fn fields_to_credential(kind: &str, details: &[Detail], fields: &mut Vec<&CustomField>) -> Option<Credential> {
+    let mut credential = Credential::new(kind);
+    for (key, name, field_type) in details {
+        if let Some(index) = fields.iter().position(|field| field.name == *name) {
+            credential.set(key, field_type, &fields.remove(index).value);
+        }
+    }
+
+    (!credential.fields.is_empty()).then_some(credential)
+}
+
+fn write_item(entry: &VaultEntry) -> Item {
+    let mut fields: Vec<&CustomField> = entry.custom_fields.iter().collect();
+    let mut credentials = Vec::new();
+
+    let password = String::from_utf8_lossy(&entry.password);
+    if entry.username.is_some() || !password.is_empty() {
+        let mut login = Credential::new("basic-auth");
+        if let Some(username) = &entry.username {
+            login.set("username", STRING, username);
+        }
+        if !password.is_empty() {
+            login.set("password", CONCEALED, &password);
+        }
+        credentials.push(login);
+    }
+
+    let mut totp = entry.totp.as_deref().and_then(totp_credential);
+    if totp.is_none()
+        && let Some(index) = fields.iter().position(|f| f.name == TOTP_FIELD)
+        && let Some(credential) = totp_credential(&fields[index].value)
+    {
+        fields.remove(index);
+        totp = Some(credential);
+    }
+    if let Some(uri) = entry.totp.as_deref().filter(|_| totp.is_none()) {
+        // Kept for an importer that understands other URIs, e.g. `steam://`.
+        let mut custom = Credential::new("custom-fields");
+        custom.fields.insert(
+            "fields".to_string(),
+            serde_json::json!([{ "fieldType": CONCEALED, "value": uri, "label": TOTP_FIELD }])
+        );
+        credentials.push(custom);
+    }
+    credentials.extend(totp);
+
+    match entry.kind {
+        EntryKind::Card => {
+            let mut card = fields_to_credential("credit-card", &CREDIT_CARD, &mut fields)
+                .unwrap_or_else(|| Credential::new("credit-card"));
+            let month = fields.iter().position(|f| f.name == "Expiration month");
+            let year = fields.iter().position(|f| f.name == "Expiration year");
+            if let (Some(month), Some(year)) = (month, year)
+                && let (Ok(month_number), Ok(year_number)) = (fields[month].value.parse::<u32>(), fields[year].value.parse::<u32>())
+            {
+                card.set("expiryDate", YEAR_MONTH, &format!("{year_number:04}-{month_number:02}"));
+                fields.retain(|f| f.name != "Expiration month" && f.name != "Expiration year");
+            }
+            credentials.push(card);
+        }
+        EntryKind::Identity => {
+            for (kind, details) in IDENTITY_CREDENTIALS {
+                credentials.extend(fields_to_credential(kind, details, &mut fields));
+            }
+        }
+        EntryKind::Login | EntryKind::SecureNote => {}
+    }
+
+    if let Some(notes) = &entry.notes {
+        let mut note = Credential::new("note");
+        note.set("content", STRING, notes);
+        credentials.push(note);
+    }
+
+    let (extra_urls, fields): (Vec<&CustomField>, Vec<&CustomField>) = fields.into_iter().partition(|f| f.name == URL_FIELD);
+    if !fields.is_empty() {
+        let fields: Vec<Value> = fields
+            .iter()
+            .map(|field| {
+                let field_type = if field.protected { CONCEALED } else { STRING };
+                serde_json::json!({ "fieldType": field_type, "value": field.value, "label": field.name })
+            })
+            .collect();
+
+        let mut custom = Credential::new("custom-fields");
+        custom.fields.insert("fields".to_string(), Value::from(fields));
+        credentials.push(custom);
+    }
+
+    let urls: Vec<String> = entry.url.iter().chain(extra_urls.iter().map(|f| &f.value)).cloned().collect();
+
+    Item {
+        id: id(&entry.id),
+        creation_at: Some(entry.created).filter(|time| *time > 0),
+        modified_at: Some(entry.modified).filter(|time| *time > 0),
+        title: entry.service.clone(),
+        favorite: entry.tags.iter().any(|t| t.eq_ignore_ascii_case(FAVORITE_TAG)).then_some(true),
+        scope: (!urls.is_empty()).then(|| Scope { urls, android_apps: Vec::new() }),
+        credentials,
+        tags: entry.tags.iter().filter(|t| !t.eq_ignore_ascii_case(FAVORITE_TAG)).cloned().collect(),
+    }
+}
+
+/// Writes `entries` as a CXF JSON document of a single account, their folders as
+/// collections. See `left_out` for what is lost.
+pub fn export(entries: &[VaultEntry]) -> io::Result<String> {
+    let mut collections: Vec<Collection> = Vec::new();
+    let mut items = Vec::new();
+
+    for entry in entries {
+        let item = write_item(entry);
+
+        if let Some(folder) = &entry.folder {
+            let index = match collections.iter().position(|collection| &collection.title == folder) {
+                Some(index) => index,
+                None => {
+                    collections.push(Collection {
+                        id: id(&vault_entry::generate_id()),
+                        title: folder.clone(),
+                        items: Vec::new(),
+                        sub_collections: Vec::new(),
+                    });
+                    collections.len() - 1
+                }
+            };
+            collections[index].items.push(LinkedItem { item: item.id.clone() });
+        }
+
+        items.push(item);
+    }
+
+    let header = Header {
+        version: Version { major: VERSION_MAJOR, minor: VERSION_MINOR },
+        exporter_rp_id: env!("CARGO_PKG_NAME").to_string(),
+        exporter_display_name: env!("CARGO_PKG_NAME").to_string(),
+        timestamp: timestamp::now(),
+        accounts: vec![Account {
+            id: id(&vault_entry::generate_id()),
+            username: String::new(),
+            email: String::new(),
+            collections,
+            items,
+        }],
+    };
+
+    serde_json::to_string_pretty(&header).map_err(io::Error::other)
+}
+
+/// What the export leaves out of each entry, CXF having no place for it, e.g.
+/// `GitHub (alice): 3 previous passwords, expiry date`.
+pub fn left_out(entries: &[VaultEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let mut missing = Vec::new();
+            match entry.attachments.len() {
+                0 => {}
+                1 => missing.push("1 attachment".to_string()),
+                count => missing.push(format!("{count} attachments")),
+            }
+            match entry.password_history.len() {
+                0 => {}
+                1 => missing.push("1 previous password".to_string()),
+                count => missing.push(format!("{count} previous passwords")),
+            }
+            if entry.expires.is_some() {
+                missing.push("expiry date".to_string());
+            }
+            if entry.pending_rotation.is_some() {
+                missing.push("pending rotation".to_string());
+            }
+            if entry.password_rules.is_some() {
+                missing.push("password rules".to_string());
+            }
+
+            (!missing.is_empty()).then(|| format!("{}: {}", import::label(entry), missing.join(", ")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_entry::PasswordVersion;
+
+    fn entries() -> Vec<VaultEntry> {
+        let mut login = VaultEntry::new("GitHub".to_string(), Some("alice".to_string()), b" spaced pw ".to_vec());
+        login.url = Some("https://github.com/login".to_string());
+        login.notes = Some("work account".to_string());
+        login.folder = Some("Work".to_string());
+        login.tags = vec!["code".to_string(), FAVORITE_TAG.to_string()];
+        login.totp = Some("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP".to_string());
+        login.custom_fields = vec![
+            CustomField { name: "Recovery".to_string(), value: " abcd ".to_string(), protected: true },
+            CustomField { name: URL_FIELD.to_string(), value: "https://gist.github.com".to_string(), protected: false },
+        ];
+        login.created = 1_500_000_000;
+        login.modified = 1_700_000_000;
+
+        let mut card = VaultEntry::new("Visa".to_string(), None, Vec::new());
+        card.kind = EntryKind::Card;
+        card.custom_fields = vec![
+            CustomField { name: "Cardholder name".to_string(), value: "Alice Doe".to_string(), protected: false },
+            CustomField { name: "Number".to_string(), value: "4111111111111111".to_string(), protected: true },
+            CustomField { name: "Expiration month".to_string(), value: "7".to_string(), protected: false },
+            CustomField { name: "Expiration year".to_string(), value: "2030".to_string(), protected: false },
+        ];
+
+        let mut identity = VaultEntry::new("Passport".to_string(), None, Vec::new());
+        identity.kind = EntryKind::Identity;
+        identity.custom_fields = vec![
+            CustomField { name: "First name".to_string(), value: "Alice".to_string(), protected: false },
+            CustomField { name: "Passport number".to_string(), value: "X1234567".to_string(), protected: true },
+        ];
+
+        vec![login, card, identity]
+    }
+
+    /// A CXF document of one account with `items`, given as JSON.
+    fn document(items: &str) -> String {
+        format!(
+            r#"{{"version": {{"major": 1, "minor": 0}}, "exporterRpId": "example.com",
+                "exporterDisplayName": "Example", "timestamp": 1700000000,
+                "accounts": [{{"id": "AA", "items": {items}}}]}}"#
+        )
+    }
+
+    #[test]
+    fn round_trips_entries() {
+        let entries = entries();
+        let batch = import(&export(&entries).unwrap()).unwrap();
+
+        assert!(batch.skipped.is_empty() && batch.left_out.is_empty(), "{:?} {:?}", batch.skipped, batch.left_out);
+        assert_eq!(batch.entries.len(), entries.len());
+        for (read, written) in batch.entries.iter().zip(&entries) {
+            assert_eq!(read.service, written.service);
+            assert_eq!(read.kind, written.kind);
+            assert_eq!(read.username, written.username);
+            assert_eq!(read.password, written.password);
+            assert_eq!(read.url, written.url);
+            assert_eq!(read.notes, written.notes);
+            assert_eq!(read.folder, written.folder);
+            assert_eq!(read.tags, written.tags);
+            assert_eq!(read.totp, written.totp);
+            assert_eq!(read.created, written.created);
+            assert_eq!(read.modified, written.modified);
+
+            let mut fields = read.custom_fields.clone();
+            let mut expected = written.custom_fields.clone();
+            fields.sort_by(|a, b| a.name.cmp(&b.name));
+            expected.sort_by(|a, b| a.name.cmp(&b.name));
+            assert_eq!(fields, expected, "{}", read.service);
+        }
+    }
+
+    #[test]
+    fn keeps_spaces_around_secrets() {
+        let batch = import(&document(
+            r#"[{"id": "AQ", "title": " Home ", "credentials": [
+                {"type": "wifi", "ssid": {"fieldType": "string", "value": " home "},
+                 "passphrase": {"fieldType": "concealed-string", "value": " wifi pass "}},
+                {"type": "api-key", "key": {"fieldType": "concealed-string", "value": "key "}},
+                {"type": "generated-password", "password": " generated"}
+            ]}]"#
+        )).unwrap();
+
+        let [entry] = batch.entries.as_slice() else { panic!("{:?}", batch) };
+        assert_eq!(entry.service, "Home");
+        assert_eq!(entry.password, b" wifi pass ");
+        let field = |name: &str| entry.custom_fields.iter().find(|f| f.name == name).map(|f| f.value.as_str());
+        assert_eq!(field("SSID"), Some("home"));
+        assert_eq!(field("API key"), Some("key "));
+        assert_eq!(field("Generated password"), Some(" generated"));
+    }
+
+    #[test]
+    fn reports_unsupported_credentials() {
+        let batch = import(&document(
+            r#"[
+                {"id": "AQ", "title": "Shop", "credentials": [
+                    {"type": "basic-auth", "username": {"fieldType": "string", "value": "bob"}},
+                    {"type": "passkey", "rpId": "shop.example", "credentialId": "AA", "userName": "bob"},
+                    {"type": "file", "name": "receipt.pdf", "decryptedContent": "AA"}
+                ]},
+                {"id": "Ag", "title": "Bank", "credentials": [{"type": "passkey", "rpId": "bank.example"}]}
+            ]"#
+        )).unwrap();
+
+        assert_eq!(batch.entries.len(), 1);
+        assert_eq!(batch.left_out, ["Shop (bob): passkey for shop.example, file receipt.pdf"]);
+        assert_eq!(batch.skipped, ["Bank: only passkey for bank.example, not supported"]);
+    }
+
+    #[test]
+    fn lists_what_the_export_leaves_out() {
+        let mut entry = VaultEntry::new("Mail".to_string(), Some("carol".to_string()), b"pw".to_vec());
+        entry.password_history = vec![PasswordVersion { password: b"old".to_vec(), derivation: None, time: 1 }];
+        entry.expires = Some(0);
+
+        assert_eq!(left_out(&[entry]), ["Mail (carol): 1 previous password, expiry date"]);
+        assert!(left_out(&entries()).is_empty());
+    }
+}
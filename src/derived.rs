@@ -0,0 +1,136 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::key_derivation;
+
+pub const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+pub const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+pub const DIGITS: &str = "0123456789";
+pub const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+pub const MIN_LENGTH: usize = 5;
+/// 256 bits of entropy are enough to draw this many characters without running dry.
+pub const MAX_LENGTH: usize = 35;
+
+/// What a derived password is computed from besides the master password, stored in the
+/// entry in place of the password itself.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DerivationParams {
+    pub site: String,
+    pub login: String,
+    /// Bumped to rotate the password.
+    pub counter: u32,
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+}
+
+impl DerivationParams {
+    pub fn new(site: String, login: String) -> Self {
+        Self {
+            site,
+            login,
+            counter: 1,
+            length: 16,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+        }
+    }
+
+    /// Character sets of the enabled classes, in a fixed order.
+    fn sets(&self) -> Vec<&'static str> {
+        [(self.lowercase, LOWERCASE), (self.uppercase, UPPERCASE), (self.digits, DIGITS), (self.symbols, SYMBOLS)]
+            .into_iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, set)| set)
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.site.trim().is_empty() {
+            return Err("a derived password needs a site".to_string());
+        }
+        if !(MIN_LENGTH..=MAX_LENGTH).contains(&self.length) {
+            return Err(format!("derived passwords are {MIN_LENGTH} to {MAX_LENGTH} characters long"));
+        }
+        if self.sets().is_empty() {
+            return Err("at least one character class must be enabled".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Short description of the profile, e.g. `counter 2, 16 characters, lower upper digits`.
+    pub fn summary(&self) -> String {
+        let classes: Vec<&str> = [
+            (self.lowercase, "lower"),
+            (self.uppercase, "upper"),
+            (self.digits, "digits"),
+            (self.symbols, "symbols"),
+        ]
+            .into_iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, name)| name)
+            .collect();
+
+        format!("counter {}, {} characters, {}", self.counter, self.length, classes.join(" "))
+    }
+
+    /// The password for these parameters under `secret`, see
+    /// `key_derivation::stateless_secret`.
+    pub fn derive(&self, secret: &[u8; 32]) -> Result<String, String> {
+        self.validate()?;
+
+        let site = self.site.trim().to_lowercase();
+        let mut entropy = Entropy(
+            key_derivation::site_entropy(secret, &site, self.login.trim(), self.counter).to_vec()
+        );
+        let sets = self.sets();
+        let all: Vec<char> = sets.concat().chars().collect();
+
+        // Draws from every class, then one character of each class inserted at drawn
+        // positions so that each class appears.
+        let mut password: Vec<char> = (0..self.length - sets.len()).map(|_| entropy.pick(&all)).collect();
+        let required: Vec<char> = sets
+            .iter()
+            .map(|set| entropy.pick(&set.chars().collect::<Vec<_>>()))
+            .collect();
+
+        for c in required {
+            let position = entropy.take(password.len() as u32 + 1) as usize;
+            password.insert(position, c);
+        }
+
+        Ok(password.into_iter().collect())
+    }
+}
+
+/// A few hex digits identifying the master password a secret came from, so that a typo
+/// shows before a wrong password is derived.
+pub fn fingerprint(secret: &[u8; 32]) -> String {
+    hex::encode(&key_derivation::subkey(secret, b"fingerprint")[..3])
+}
+
+/// Big endian integer consumed by successive divisions.
+struct Entropy(Vec<u8>);
+
+impl Entropy {
+    /// Divides the entropy by `n` in place and returns the remainder.
+    fn take(&mut self, n: u32) -> u32 {
+        let mut remainder: u64 = 0;
+        for byte in self.0.iter_mut() {
+            let value = (remainder << 8) | (*byte as u64);
+            *byte = (value / n as u64) as u8;
+            remainder = value % n as u64;
+        }
+
+        remainder as u32
+    }
+
+    fn pick(&mut self, chars: &[char]) -> char {
+        chars[self.take(chars.len() as u32) as usize]
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+use std::io;
+
+/// A variable of a `.env` file and the comments above it.
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub key: String,
+    pub value: String,
+    /// Comment lines above the variable, without the `#`, then its inline comment. Blank
+    /// lines between comments are kept as empty ones.
+    pub comments: Vec<String>,
+}
+
+/// The variables of a `.env` file in their order, and what could not be read.
+#[derive(Debug, Default)]
+pub struct EnvFile {
+    pub variables: Vec<Variable>,
+    /// Why lines were not read, e.g. `line 4: not a KEY=VALUE assignment`.
+    pub skipped: Vec<String>,
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Whether `key` is a shell variable name: letters, digits and underscores, not starting
+/// with a digit.
+pub fn is_valid_key(key: &str) -> bool {
+    key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Reads `KEY=VALUE` lines, optionally preceded by `export`. Values may be single
+/// quoted, taken literally, or double quoted with backslash escapes, both spanning
+/// several lines. A `#` after whitespace starts an inline comment in unquoted values. A
+/// variable set twice keeps its first place and its last value.
+pub fn parse(text: &str) -> io::Result<EnvFile> {
+    let lines: Vec<&str> = text.strip_prefix('\u{feff}').unwrap_or(text).lines().collect();
+    let mut file = EnvFile::default();
+    let mut comments: Vec<String> = Vec::new();
+    let mut index = 0;
+
+    while index < lines.len() {
+        let number = index + 1;
+        let line = lines[index].trim_start();
+        index += 1;
+
+        if line.trim().is_empty() {
+            if !comments.is_empty() {
+                comments.push(String::new());
+            }
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            comments.push(comment.strip_prefix(' ').unwrap_or(comment).trim_end().to_string());
+            continue;
+        }
+
+        let assignment = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let Some((key, rest)) = assignment.split_once('=') else {
+            file.skipped.push(format!("line {number}: not a KEY=VALUE assignment"));
+            continue;
+        };
+        let key = key.trim();
+        if !is_valid_key(key) {
+            file.skipped.push(format!("line {number}: `{key}` is not a valid variable name"));
+            continue;
+        }
+
+        let rest = rest.trim_start();
+        let (value, inline_comment) = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let mut quoted = rest[1..].to_string();
+                let end = loop {
+                    if let Some(end) = closing_quote(&quoted, quote) {
+                        break end;
+                    }
+                    let Some(next) = lines.get(index) else {
+                        return Err(invalid_data(format!("line {number}: the quoted value of {key} is not closed")));
+                    };
+                    quoted.push('\n');
+                    quoted.push_str(next);
+                    index += 1;
+                };
+
+                let value = if quote == '"' { unescape(&quoted[..end]) } else { quoted[..end].to_string() };
+                let after = quoted[end + 1..].trim();
+                (value, after.strip_prefix('#').map(str::to_string))
+            }
+            _ => {
+                let comment_start = rest
+                    .char_indices()
+                    .find(|&(i, c)| c == '#' && rest[..i].ends_with([' ', '\t']))
+                    .map(|(i, _)| i);
+                match comment_start {
+                    Some(i) => (rest[..i].trim_end().to_string(), Some(rest[i + 1..].to_string())),
+                    None => (rest.trim_end().to_string(), None),
+                }
+            }
+        };
+
+        while comments.last().is_some_and(String::is_empty) {
+            comments.pop();
+        }
+        if let Some(comment) = inline_comment {
+            comments.push(comment.strip_prefix(' ').unwrap_or(&comment).trim_end().to_string());
+        }
+        let comments = std::mem::take(&mut comments);
+
+        match file.variables.iter_mut().find(|variable| variable.key == key) {
+            Some(variable) => {
+                file.skipped.push(format!("line {number}: {key} is set again, its last value is kept"));
+                variable.value = value;
+                variable.comments.extend(comments);
+            }
+            None => file.variables.push(Variable { key: key.to_string(), value, comments }),
+        }
+    }
+
+    if comments.iter().any(|comment| !comment.is_empty()) {
+        file.skipped.push("comments after the last variable".to_string());
+    }
+
+    Ok(file)
+}
+
+/// Byte offset of the quote closing a value, backslashes escaping it in double quotes.
+fn closing_quote(quoted: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in quoted.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quote == '"' => escaped = true,
+            _ if c == quote => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some(c @ ('"' | '\\' | '$' | '`')) => unescaped.push(c),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// Characters a value cannot have unquoted, by how warnings name them.
+const NEEDS_QUOTING: [(&str, &[char]); 7] = [
+    ("spaces", &[' ', '\t']),
+    ("line breaks", &['\n', '\r']),
+    ("#", &['#']),
+    ("quotes", &['"', '\'']),
+    ("$", &['$']),
+    ("backslashes", &['\\']),
+    ("backticks", &['`']),
+];
+
+/// What in `value` needs quoting, empty when it can be written as is.
+fn needs_quoting(value: &str) -> Vec<&'static str> {
+    NEEDS_QUOTING
+        .iter()
+        .filter(|(_, chars)| value.contains(*chars))
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Writes the variables in order, each below its comments, and returns the file with
+/// warnings about the values that had to be quoted or keys set more than once. Values
+/// are single quoted when that is enough, as nothing is expanded in single quotes, and
+/// double quoted with backslash escapes otherwise.
+pub fn write(variables: &[Variable]) -> (String, Vec<String>) {
+    let mut contents = String::new();
+    let mut warnings = Vec::new();
+    let mut keys = HashSet::new();
+
+    for (index, variable) in variables.iter().enumerate() {
+        if !variable.comments.is_empty() {
+            if index > 0 {
+                contents.push('\n');
+            }
+            for comment in &variable.comments {
+                if comment.is_empty() {
+                    contents.push_str("#\n");
+                } else {
+                    contents.push_str(&format!("# {comment}\n"));
+                }
+            }
+        }
+        if !keys.insert(variable.key.as_str()) {
+            warnings.push(format!("{} is set more than once, the last value wins", variable.key));
+        }
+
+        let reasons = needs_quoting(&variable.value);
+        let value = if reasons.is_empty() {
+            variable.value.clone()
+        } else if variable.value.contains(['\'', '\n', '\r']) {
+            warnings.push(format!("{}: the value has {}, written in double quotes", variable.key, reasons.join(", ")));
+            let mut escaped = String::new();
+            for c in variable.value.chars() {
+                match c {
+                    '\n' => escaped.push_str("\\n"),
+                    '\r' => escaped.push_str("\\r"),
+                    '"' | '\\' | '$' | '`' => {
+                        escaped.push('\\');
+                        escaped.push(c);
+                    }
+                    c => escaped.push(c),
+                }
+            }
+            format!("\"{escaped}\"")
+        } else {
+            warnings.push(format!("{}: the value has {}, written in single quotes", variable.key, reasons.join(", ")));
+            format!("'{}'", variable.value)
+        };
+
+        contents.push_str(&format!("{}={value}\n", variable.key));
+    }
+
+    (contents, warnings)
+}
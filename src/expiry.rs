@@ -0,0 +1,71 @@
+use crate::{ timestamp, vault_entry::VaultEntry };
+
+/// Days before its expiry date from which an entry is reported as expiring.
+pub const DEFAULT_WARNING_DAYS: u64 = 14;
+
+/// An entry with an expiry date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiringEntry {
+    pub index: usize,
+    pub expires: u64,
+    /// Whole days until the expiry date, negative once it passed.
+    pub days_left: i64,
+}
+
+impl ExpiringEntry {
+    pub fn is_expired(&self) -> bool {
+        self.days_left < 0
+    }
+
+    pub fn describe(&self) -> String {
+        describe(self.days_left)
+    }
+}
+
+/// `expires in 3 days`, `expired yesterday`...
+pub fn describe(days_left: i64) -> String {
+    match days_left {
+        ..-1 => format!("expired {} days ago", -days_left),
+        -1 => "expired yesterday".to_string(),
+        0 => "expires today".to_string(),
+        1 => "expires tomorrow".to_string(),
+        days => format!("expires in {days} days"),
+    }
+}
+
+/// Whole days from today to the expiry date of `entry`, negative once it passed.
+pub fn days_left(entry: &VaultEntry) -> Option<i64> {
+    let today = timestamp::now() / timestamp::DAY;
+    entry.expires.map(|expires| (expires / timestamp::DAY) as i64 - today as i64)
+}
+
+/// Entries expiring within `within_days`, expired ones included, or every entry with an
+/// expiry date when `None`. Soonest first.
+pub fn expiring_entries(entries: &[VaultEntry], within_days: Option<u64>) -> Vec<ExpiringEntry> {
+    let mut expiring: Vec<ExpiringEntry> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let days_left = days_left(entry)?;
+            let expires = entry.expires?;
+
+            within_days
+                .is_none_or(|within| days_left <= within as i64)
+                .then_some(ExpiringEntry { index, expires, days_left })
+        })
+        .collect();
+
+    expiring.sort_by_key(|e| e.expires);
+    expiring
+}
+
+/// Counts of expired and soon expiring entries, e.g. `2 expired, 1 expiring soon`.
+pub fn counts(expiring: &[ExpiringEntry]) -> String {
+    let expired = expiring.iter().filter(|e| e.is_expired()).count();
+
+    match (expired, expiring.len() - expired) {
+        (0, soon) => format!("{soon} expiring soon"),
+        (expired, 0) => format!("{expired} expired"),
+        (expired, soon) => format!("{expired} expired, {soon} expiring soon"),
+    }
+}
@@ -0,0 +1,211 @@
+use rand::{ Rng, rngs::OsRng };
+use serde::{ Deserialize, Serialize };
+use strum::{ Display, EnumIter, IntoEnumIterator };
+
+pub const DEFAULT_SYMBOLS: &str = "!@#$%^&*()-_=+[]{};:,.<>/?~";
+
+/// Characters easily mistaken for one another when read or typed.
+pub const LOOK_ALIKES: &str = "0Oo1lI|";
+
+pub const MAX_LENGTH: usize = 256;
+
+/// Below this share of valid draws, a policy is rejected rather than sampled for ages.
+const MIN_ACCEPT_PROBABILITY: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumIter)]
+pub enum CharClass {
+    #[strum(to_string = "lowercase")]
+    Lowercase,
+    #[strum(to_string = "uppercase")]
+    Uppercase,
+    #[strum(to_string = "digits")]
+    Digits,
+    #[strum(to_string = "symbols")]
+    Symbols,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClassRule {
+    pub enabled: bool,
+    /// Minimum number of characters of the class in each password.
+    pub min: usize,
+}
+
+impl Default for ClassRule {
+    fn default() -> Self {
+        Self { enabled: true, min: 1 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PasswordPolicy {
+    pub length: usize,
+    pub lowercase: ClassRule,
+    pub uppercase: ClassRule,
+    pub digits: ClassRule,
+    pub symbols: ClassRule,
+    /// Leaves out the characters of `LOOK_ALIKES`.
+    pub exclude_look_alikes: bool,
+    pub symbol_set: String,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            length: 20,
+            lowercase: ClassRule::default(),
+            uppercase: ClassRule::default(),
+            digits: ClassRule::default(),
+            symbols: ClassRule::default(),
+            exclude_look_alikes: false,
+            symbol_set: DEFAULT_SYMBOLS.to_string(),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn rule(&self, class: CharClass) -> &ClassRule {
+        match class {
+            CharClass::Lowercase => &self.lowercase,
+            CharClass::Uppercase => &self.uppercase,
+            CharClass::Digits => &self.digits,
+            CharClass::Symbols => &self.symbols,
+        }
+    }
+
+    pub fn rule_mut(&mut self, class: CharClass) -> &mut ClassRule {
+        match class {
+            CharClass::Lowercase => &mut self.lowercase,
+            CharClass::Uppercase => &mut self.uppercase,
+            CharClass::Digits => &mut self.digits,
+            CharClass::Symbols => &mut self.symbols,
+        }
+    }
+
+    /// Characters of `class` allowed by this policy, without duplicates.
+    pub fn chars(&self, class: CharClass) -> Vec<char> {
+        let mut chars: Vec<char> = match class {
+            CharClass::Lowercase => ('a'..='z').collect(),
+            CharClass::Uppercase => ('A'..='Z').collect(),
+            CharClass::Digits => ('0'..='9').collect(),
+            CharClass::Symbols => {
+                self.symbol_set.chars().filter(|c| !c.is_alphanumeric() && !c.is_whitespace()).collect()
+            }
+        };
+
+        chars.sort_unstable();
+        chars.dedup();
+        if self.exclude_look_alikes {
+            chars.retain(|c| !LOOK_ALIKES.contains(*c));
+        }
+
+        chars
+    }
+
+    /// Enabled classes with their characters.
+    fn classes(&self) -> Vec<(CharClass, Vec<char>)> {
+        CharClass::iter()
+            .filter(|class| self.rule(*class).enabled)
+            .map(|class| (class, self.chars(class)))
+            .collect()
+    }
+
+    /// Probability that a string drawn uniformly from the whole alphabet meets the
+    /// minimum counts.
+    fn accept_probability(&self, classes: &[(CharClass, Vec<char>)]) -> f64 {
+        let alphabet = classes.iter().map(|(_, chars)| chars.len()).sum::<usize>() as f64;
+
+        // p[n]: probability that n positions, filled from the classes seen so far, meet
+        // their minimums.
+        let mut p = vec![0.0; self.length + 1];
+        p[0] = 1.0;
+
+        for (class, chars) in classes {
+            let share = (chars.len() as f64) / alphabet;
+            let min = self.rule(*class).min;
+            let mut next = vec![0.0; self.length + 1];
+
+            for (n, slot) in next.iter_mut().enumerate() {
+                let mut binomial = 1.0;
+                for k in 0..=n {
+                    if k > 0 {
+                        binomial *= ((n - k + 1) as f64) / (k as f64);
+                    }
+                    if k >= min {
+                        *slot += binomial * share.powi(k as i32) * p[n - k];
+                    }
+                }
+            }
+
+            p = next;
+        }
+
+        p[self.length]
+    }
+
+    /// Checks the policy can produce passwords, with a message to show otherwise.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.length == 0 || self.length > MAX_LENGTH {
+            return Err(format!("the length must be between 1 and {MAX_LENGTH}"));
+        }
+
+        let classes = self.classes();
+        if classes.is_empty() {
+            return Err("at least one character class must be enabled".to_string());
+        }
+
+        if let Some((class, _)) = classes.iter().find(|(_, chars)| chars.is_empty()) {
+            return Err(format!("no {class} are left to choose from"));
+        }
+
+        let required: usize = classes.iter().map(|(class, _)| self.rule(*class).min).sum();
+        if required > self.length {
+            return Err(format!("the minimum counts add up to {required}, more than the length"));
+        }
+
+        if self.accept_probability(&classes) < MIN_ACCEPT_PROBABILITY {
+            return Err("the minimum counts are too strict for this length".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Entropy in bits of the passwords this policy generates, which are uniformly
+    /// distributed over every string meeting the policy.
+    pub fn entropy(&self) -> Result<f64, String> {
+        self.validate()?;
+
+        let classes = self.classes();
+        let alphabet = classes.iter().map(|(_, chars)| chars.len()).sum::<usize>() as f64;
+
+        Ok((self.length as f64) * alphabet.log2() + self.accept_probability(&classes).log2())
+    }
+
+    /// Draws a password from the OS random number generator.
+    ///
+    /// Draws are repeated until one meets the minimum counts, so that every valid password
+    /// is equally likely and `entropy` holds.
+    pub fn generate(&self) -> Result<String, String> {
+        self.validate()?;
+
+        let classes = self.classes();
+        let alphabet: Vec<char> = classes
+            .iter()
+            .flat_map(|(_, chars)| chars.iter().copied())
+            .collect();
+
+        loop {
+            let password: Vec<char> = (0..self.length)
+                .map(|_| alphabet[OsRng.gen_range(0..alphabet.len())])
+                .collect();
+
+            let meets_minimums = classes.iter().all(|(class, chars)| {
+                password.iter().filter(|c| chars.contains(c)).count() >= self.rule(*class).min
+            });
+
+            if meets_minimums {
+                return Ok(password.into_iter().collect());
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use crate::vault_entry::VaultEntry;
+
+/// Entries read from an export, and what had to be left out of it.
+#[derive(Debug, Default)]
+pub struct ImportBatch {
+    /// What the export was read as, e.g. `bitwarden CSV`.
+    pub format: String,
+    pub entries: Vec<VaultEntry>,
+    /// Why records of the export were not imported, e.g. `line 12: secure note`.
+    pub skipped: Vec<String>,
+    /// What imported records held that the vault has no place for, e.g.
+    /// `GitHub (alice): passkey for github.com`.
+    pub left_out: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplicate {
+    /// Same account and password as an entry already in the vault or earlier in the
+    /// export, never imported.
+    Identical,
+    /// Same account with another password, only imported on request.
+    SameAccount,
+}
+
+/// An imported entry and the entry it duplicates, if any.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub entry: VaultEntry,
+    /// The kind of duplicate and the label of the entry it duplicates.
+    pub duplicate: Option<(Duplicate, String)>,
+}
+
+impl Candidate {
+    pub fn imported(&self, add_duplicates: bool) -> bool {
+        match self.duplicate {
+            None => true,
+            Some((Duplicate::SameAccount, _)) => add_duplicates,
+            Some((Duplicate::Identical, _)) => false,
+        }
+    }
+
+    /// One line preview of the entry, its password left out, e.g.
+    /// `+ GitHub (alice)  url: https://github.com  folder: work  password: 20 characters`.
+    pub fn preview(&self, add_duplicates: bool) -> String {
+        let entry = &self.entry;
+        let marker = match self.duplicate {
+            None => '+',
+            Some((Duplicate::Identical, _)) => '=',
+            Some((Duplicate::SameAccount, _)) => '~',
+        };
+
+        let mut fields = vec![format!("{marker} {}", label(entry))];
+        if !entry.kind.is_login() {
+            fields.push(entry.kind.to_string());
+        }
+        if let Some(url) = &entry.url {
+            fields.push(format!("url: {url}"));
+        }
+        if let Some(folder) = &entry.folder {
+            fields.push(format!("folder: {folder}"));
+        }
+        if !entry.tags.is_empty() {
+            fields.push(format!("tags: {}", entry.tags.join(", ")));
+        }
+        match String::from_utf8_lossy(&entry.password).chars().count() {
+            0 if entry.kind.is_login() => fields.push("no password".to_string()),
+            0 => {}
+            length => fields.push(format!("password: {length} characters")),
+        }
+        if let Some(notes) = &entry.notes {
+            fields.push(format!("notes: {} lines", notes.lines().count()));
+        }
+        if entry.totp.is_some() {
+            fields.push("totp".to_string());
+        }
+        if !entry.custom_fields.is_empty() {
+            fields.push(format!("custom fields: {}", entry.custom_fields.len()));
+        }
+        if !entry.attachments.is_empty() {
+            fields.push(format!("attachments: {}", entry.attachments.len()));
+        }
+        if !entry.password_history.is_empty() {
+            fields.push(format!("previous passwords: {}", entry.password_history.len()));
+        }
+
+        match &self.duplicate {
+            Some((Duplicate::Identical, other)) => fields.push(format!("identical to {other}, skipped")),
+            Some((Duplicate::SameAccount, other)) => {
+                let action = if self.imported(add_duplicates) { "imported anyway" } else { "skipped" };
+                fields.push(format!("same account as {other} with another password, {action}"));
+            }
+            None => {}
+        }
+
+        fields.join("  ")
+    }
+}
+
+/// `Service (username)`, or the service alone.
+pub fn label(entry: &VaultEntry) -> String {
+    match &entry.username {
+        Some(username) => format!("{} ({username})", entry.service),
+        None => entry.service.clone(),
+    }
+}
+
+/// A password the vault or the export already has for an account.
+struct KnownPassword {
+    password: Vec<u8>,
+    label: String,
+}
+
+fn remember(accounts: &mut HashMap<(String, String), Vec<KnownPassword>>, entry: &VaultEntry) {
+    accounts
+        .entry(entry.account_key())
+        .or_default()
+        .push(KnownPassword { password: entry.password.clone(), label: label(entry) });
+}
+
+/// Compares each imported entry with the vault, then with the entries before it in the
+/// export, by account (see `VaultEntry::account_key`) and password.
+pub fn find_duplicates(entries: Vec<VaultEntry>, existing: &[VaultEntry]) -> Vec<Candidate> {
+    let mut accounts: HashMap<(String, String), Vec<KnownPassword>> = HashMap::new();
+    for entry in existing {
+        remember(&mut accounts, entry);
+    }
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let known = accounts.get(&entry.account_key()).map_or(&[][..], Vec::as_slice);
+
+            let duplicate = match known.iter().find(|k| k.password == entry.password) {
+                Some(k) => Some((Duplicate::Identical, k.label.clone())),
+                None => known.first().map(|k| (Duplicate::SameAccount, k.label.clone())),
+            };
+
+            remember(&mut accounts, &entry);
+            Candidate { entry, duplicate }
+        })
+        .collect()
+}
@@ -4,6 +4,11 @@ use hmac::{ Hmac, Mac };
 use sha2::Sha256;
 use subtle::ConstantTimeEq;
 
+/// Argon2id cost of the vault key.
+pub const MEMORY_KIB: u32 = 65536;
+pub const ITERATIONS: u32 = 2;
+pub const PARALLELISM: u32 = 1;
+
 pub fn derive_key(master_password: &str) -> ([u8; 32], [u8; 22]) {
     let salt = SaltString::generate(&mut OsRng);
     derive_key_with_salt(master_password, salt.as_str().as_bytes().try_into().expect("error"))
@@ -15,7 +20,7 @@ fn derive_key_with_salt(master_password: &str, salt: &[u8; 22]) -> ([u8; 32], [u
     let argon2 = Argon2::new(
         Algorithm::Argon2id,
         Version::V0x13,
-        Params::new(65536, 2, 1, Some(32)).unwrap()
+        Params::new(MEMORY_KIB, ITERATIONS, PARALLELISM, Some(32)).unwrap()
     );
     argon2
         .hash_password_into(master_password.as_bytes(), salt, &mut key)
@@ -33,9 +38,40 @@ pub fn create_verifier(derive_key: &[u8; 32]) -> [u8; 32] {
     mac.finalize().into_bytes().try_into().expect("error")
 }
 
-pub fn verify_password(master_password: &str, salt: &[u8; 22], verifier: &[u8; 32]) -> bool {
+/// Key for a single purpose derived from the vault key, so the vault key itself is never
+/// used directly for encryption.
+pub fn subkey(key: &[u8; 32], purpose: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("Invalid key");
+    mac.update(purpose);
+
+    mac.finalize().into_bytes().into()
+}
+
+/// Derives the vault key from `master_password`, `None` if it does not match `verifier`.
+pub fn unlock(master_password: &str, salt: &[u8; 22], verifier: &[u8; 32]) -> Option<[u8; 32]> {
     let (derive_key, _) = derive_key_with_salt(master_password, salt);
     let computed_verifier = create_verifier(&derive_key);
 
-    return verifier.ct_eq(&computed_verifier).unwrap_u8() == 1;
+    if verifier.ct_eq(&computed_verifier).unwrap_u8() == 1 { Some(derive_key) } else { None }
+}
+
+/// Fixed salt of `stateless_secret`, which must come out the same on every machine.
+const STATELESS_SALT: &[u8; 22] = b"pmgr-stateless-v1-salt";
+
+/// Secret behind derived passwords, computed from the master password alone so that any
+/// machine knowing it can regenerate them without the vault.
+pub fn stateless_secret(master_password: &str) -> [u8; 32] {
+    derive_key_with_salt(master_password, STATELESS_SALT).0
+}
+
+/// Entropy of one derived password, see `derived::DerivationParams`.
+pub fn site_entropy(secret: &[u8; 32], site: &str, login: &str, counter: u32) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("Invalid key");
+    mac.update(site.as_bytes());
+    mac.update(&[0]);
+    mac.update(login.as_bytes());
+    mac.update(&[0]);
+    mac.update(format!("{counter:x}").as_bytes());
+
+    mac.finalize().into_bytes().into()
 }
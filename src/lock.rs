@@ -0,0 +1,36 @@
+use std::fs::{ File, OpenOptions, TryLockError };
+use std::io;
+use std::path::{ Path, PathBuf };
+
+use crate::config::ensure_parents_exist;
+
+/// Exclusive advisory lock on a vault, held until dropped.
+///
+/// The lock is taken on a `<vault>.lock` file next to the vault rather than on the vault
+/// itself, so saving can replace the vault file without giving the lock up.
+#[derive(Debug)]
+pub struct VaultLock {
+    _file: File,
+}
+
+pub fn lock_path(vault_path: &Path) -> PathBuf {
+    let mut file_name = vault_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".lock");
+    vault_path.with_file_name(file_name)
+}
+
+impl VaultLock {
+    /// Takes the lock for `vault_path`, `None` when another process already holds it.
+    pub fn try_acquire(vault_path: &Path) -> io::Result<Option<Self>> {
+        let path = lock_path(vault_path);
+        ensure_parents_exist(&path)?;
+
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(path)?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+}
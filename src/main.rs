@@ -1,14 +1,61 @@
+mod age;
+mod archive;
+mod audit_log;
+mod breach;
+mod cli;
+mod config;
+mod csv_import;
+mod cxf;
+mod env_file;
+mod derived;
+mod expiry;
+mod generator;
+mod import;
+mod bitwarden;
+mod kdbx;
 mod key_derivation;
+mod lock;
+mod merge;
+mod pass_import;
+mod plaintext_export;
+mod paper;
+mod passphrase;
+mod password_rules;
+mod query;
+mod rotation;
+mod search;
+mod security_report;
+mod strength;
+mod timestamp;
 mod vault;
 mod vault_entry;
 mod vault_header;
 mod app;
 mod ui;
 
-use crate::app::App;
+use crate::{ app::App, config::Config };
 
 fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
-    ratatui::run(|terminal| App::new().expect("Error when launch App").run(terminal))?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (vault_flag, args) = match cli::parse_global_options(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{}", cli::usage());
+            std::process::exit(2);
+        }
+    };
+
+    let config = Config::load()?;
+    let vault_path = config::resolve_vault_path(vault_flag, config.as_ref());
+
+    if !args.is_empty() {
+        std::process::exit(cli::run(&vault_path, config.as_ref(), &args));
+    }
+
+    ratatui::run(|terminal| {
+        App::new(vault_path, config).expect("Error when launch App").run(terminal)
+    })?;
     Ok(())
 }
@@ -0,0 +1,163 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::vault_entry::VaultEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+/// An entry changed in both vaults since they diverged.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Conflict {
+    pub local: VaultEntry,
+    pub remote: VaultEntry,
+}
+
+impl Conflict {
+    pub fn id(&self) -> &str {
+        &self.local.id
+    }
+
+    /// The side applied until the conflict is resolved: the most recently modified one,
+    /// the local one on ties.
+    pub fn default_side(&self) -> Side {
+        if self.remote.modified > self.local.modified { Side::Remote } else { Side::Local }
+    }
+
+    /// Entry to keep when resolving in favour of `side`. The other version is recorded
+    /// as a revision so the next merge does not report it again.
+    pub fn resolve(&self, side: Side) -> VaultEntry {
+        match side {
+            Side::Local => combine(&self.local, &self.remote),
+            Side::Remote => combine(&self.remote, &self.local),
+        }
+    }
+
+    /// Names of the fields that differ between both versions.
+    pub fn changed_fields(&self) -> Vec<String> {
+        let local = content(&self.local);
+        let remote = content(&self.remote);
+
+        let mut fields: Vec<String> = local
+            .iter()
+            .chain(remote.iter())
+            .filter(|(key, _)| local.get(*key) != remote.get(*key))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        fields.sort();
+        fields.dedup();
+        fields
+    }
+}
+
+fn content(entry: &VaultEntry) -> serde_json::Map<String, serde_json::Value> {
+    let mut value = serde_json::to_value(entry).expect("entries always serialize");
+
+    let map = value.as_object_mut().expect("entries serialize to objects");
+    for bookkeeping in ["id", "created", "modified", "revisions"] {
+        map.remove(bookkeeping);
+    }
+
+    std::mem::take(map)
+}
+
+/// An entry deleted from a vault, kept so merges do not bring it back.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Deletion {
+    pub id: String,
+    /// Revisions of the entry when it was deleted, its last one included.
+    pub revisions: Vec<String>,
+    pub time: u64,
+}
+
+impl Deletion {
+    pub fn of(entry: &VaultEntry, time: u64) -> Deletion {
+        let mut revisions = entry.revisions.clone();
+        revisions.push(entry.revision());
+
+        Deletion { id: entry.id.clone(), revisions, time }
+    }
+
+    /// Whether `entry` is the deleted one or an older version of it. Versions changed
+    /// after the deletion are not covered and come back.
+    pub fn covers(&self, entry: &VaultEntry) -> bool {
+        self.id == entry.id && self.revisions.contains(&entry.revision())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MergeOutcome {
+    pub entries: Vec<VaultEntry>,
+    pub conflicts: Vec<Conflict>,
+    /// Entries only present in the remote vault.
+    pub added: usize,
+    /// Entries for which the remote version replaced the local one.
+    pub updated: usize,
+    /// Local entries dropped because the remote vault deleted them.
+    pub deleted: usize,
+}
+
+/// `winner` with the revisions of `loser`, so later merges see both as its ancestors.
+fn combine(winner: &VaultEntry, loser: &VaultEntry) -> VaultEntry {
+    let mut entry = winner.clone();
+
+    for revision in &loser.revisions {
+        entry.record_revision(revision.clone());
+    }
+    entry.record_revision(loser.revision());
+    entry.created = match (winner.created, loser.created) {
+        (0, created) | (created, 0) => created,
+        (a, b) => a.min(b),
+    };
+
+    entry
+}
+
+/// Merges the entries of two copies of a vault, matched by id.
+///
+/// Entries only present on one side are kept. When an entry differs, the version that
+/// already has the other one in its revisions wins. Otherwise both sides changed it: the
+/// most recently modified version is applied and a [`Conflict`] is reported. Entries
+/// covered by one of `deletions`, from either side, are dropped first.
+pub fn merge(local: &[VaultEntry], remote: &[VaultEntry], deletions: &[Deletion]) -> MergeOutcome {
+    let mut outcome = MergeOutcome::default();
+
+    let deleted = |entry: &&VaultEntry| deletions.iter().any(|deletion| deletion.covers(entry));
+    let (dropped, local): (Vec<&VaultEntry>, Vec<&VaultEntry>) = local.iter().partition(deleted);
+    let remote: Vec<&VaultEntry> = remote.iter().filter(|entry| !deleted(entry)).collect();
+    outcome.deleted = dropped.len();
+
+    for &local_entry in &local {
+        let Some(&remote_entry) = remote.iter().find(|e| e.id == local_entry.id) else {
+            outcome.entries.push(local_entry.clone());
+            continue;
+        };
+
+        if local_entry.revision() == remote_entry.revision() {
+            let mut entry = combine(local_entry, remote_entry);
+            entry.modified = local_entry.modified.max(remote_entry.modified);
+            outcome.entries.push(entry);
+        } else if local_entry.descends_from(remote_entry) {
+            outcome.entries.push(combine(local_entry, remote_entry));
+        } else if remote_entry.descends_from(local_entry) {
+            outcome.entries.push(combine(remote_entry, local_entry));
+            outcome.updated += 1;
+        } else {
+            let conflict = Conflict { local: local_entry.clone(), remote: remote_entry.clone() };
+            outcome.entries.push(conflict.resolve(conflict.default_side()));
+            outcome.conflicts.push(conflict);
+        }
+    }
+
+    for &remote_entry in &remote {
+        if !local.iter().any(|e| e.id == remote_entry.id) {
+            outcome.entries.push(remote_entry.clone());
+            outcome.added += 1;
+        }
+    }
+
+    outcome
+}
@@ -0,0 +1,506 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io;
+
+use aes_gcm::{ Aes256Gcm, KeyInit, Nonce, aead::{ Aead, Payload } };
+use hkdf::Hkdf;
+use qrcode::{ EcLevel, QrCode, render::unicode::Dense1x2 };
+use rand::{ RngCore, rngs::OsRng };
+use sha2::{ Digest, Sha256 };
+
+use crate::{ key_derivation, timestamp, vault_header::VaultHeader };
+
+/// Starts the line of every chunk, followed by its label and its checksum.
+const MAGIC: &str = "PMGR1";
+/// Label of the chunk holding the master password encrypted with the recovery secret.
+const RECOVERY_LABEL: &str = "R";
+const RECOVERY_INFO: &[u8] = b"pmgr-paper-recovery";
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Vault bytes per chunk, small enough for a QR code about 70 characters wide.
+const CHUNK_BYTES: usize = 200;
+const GROUP_SIZE: usize = 5;
+const GROUPS_PER_LINE: usize = 8;
+/// Characters of the checksum of a chunk, and of a line of it.
+const CHUNK_CHECKSUM: usize = 8;
+const LINE_CHECKSUM: usize = 2;
+const SECRET_BYTES: usize = 16;
+
+/// The content of a paper backup once scanned or typed back in.
+pub struct Reassembled {
+    pub vault: Vec<u8>,
+    recovery_block: Option<Vec<u8>>,
+}
+
+impl Reassembled {
+    /// SHA-256 of the vault file, also printed on the backup to compare with.
+    pub fn fingerprint(&self) -> String {
+        grouped_hex(&Sha256::digest(&self.vault)).split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// The master password kept in the recovery block, decrypted with the recovery
+    /// secret printed apart from the rest of the backup.
+    pub fn recover_master_password(&self, recovery_secret: &str) -> io::Result<String> {
+        let block = self.recovery_block.as_ref().ok_or_else(|| invalid_data("the recovery block is missing"))?;
+        let secret = parse_secret(recovery_secret)?;
+        if block.len() < 12 {
+            return Err(invalid_data("the recovery block is truncated"));
+        }
+
+        let (nonce, ciphertext) = block.split_at(12);
+        let aad = Sha256::digest(&self.vault);
+        let password = Aes256Gcm::new(&recovery_key(&secret).into())
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| invalid_data("wrong recovery secret, or the recovery block belongs to another vault"))?;
+
+        String::from_utf8(password).map_err(|_| invalid_data("the recovery block is malformed"))
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            text.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        text.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+
+    text
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in text.chars() {
+        let value = ALPHABET.iter().position(|a| *a as char == c)? as u32;
+
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(bytes)
+}
+
+/// The first `length` base32 characters of the SHA-256 of `text`.
+fn checksum(text: &str, length: usize) -> String {
+    let mut checksum = base32_encode(&Sha256::digest(text.as_bytes()));
+    checksum.truncate(length);
+    checksum
+}
+
+/// Base32 as typed back in, in canonical form: lowercase is accepted, and 0, 1 and 8,
+/// which are not in the alphabet, are read as the O, I and B they were mistaken for.
+fn normalize(text: &str) -> Option<String> {
+    text.chars()
+        .map(|c| match c.to_ascii_uppercase() {
+            '0' => 'O',
+            '1' => 'I',
+            '8' => 'B',
+            c => c,
+        })
+        .map(|c| ALPHABET.contains(&(c as u8)).then_some(c))
+        .collect()
+}
+
+fn recovery_key(secret: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, secret)
+        .expand(RECOVERY_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    key
+}
+
+/// The recovery secret as printed: base32 then a checksum, in dash separated groups.
+fn format_secret(secret: &[u8]) -> String {
+    let encoded = base32_encode(secret);
+    let text = format!("{encoded}{}", checksum(&encoded, LINE_CHECKSUM));
+
+    text.as_bytes()
+        .chunks(GROUP_SIZE)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn parse_secret(text: &str) -> io::Result<Vec<u8>> {
+    let invalid = || invalid_data("the recovery secret is mistyped");
+    let compact: String = text.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+    let compact = normalize(&compact).ok_or_else(invalid)?;
+
+    let split = compact.len().checked_sub(LINE_CHECKSUM).ok_or_else(invalid)?;
+    let (encoded, typed_checksum) = compact.split_at(split);
+    if checksum(encoded, LINE_CHECKSUM) != typed_checksum {
+        return Err(invalid());
+    }
+
+    base32_decode(encoded).filter(|secret| secret.len() == SECRET_BYTES).ok_or_else(invalid)
+}
+
+/// Hex in groups of 8 digits, 4 groups per line, each line indented like the values of
+/// the document.
+fn grouped_hex(bytes: &[u8]) -> String {
+    let hex = hex::encode(bytes);
+    let groups: Vec<&str> = hex.as_bytes().chunks(8).map(|g| std::str::from_utf8(g).unwrap_or("")).collect();
+
+    groups.chunks(4).map(|line| line.join(" ")).collect::<Vec<_>>().join("\n               ")
+}
+
+fn qr_code(content: &str) -> io::Result<String> {
+    let code = QrCode::with_error_correction_level(content.as_bytes(), EcLevel::M)
+        .map_err(|e| io::Error::other(format!("cannot encode a QR code: {e}")))?;
+
+    Ok(code.render::<Dense1x2>().quiet_zone(true).build())
+}
+
+/// Prints one chunk as a QR code holding the whole chunk on one line, then as text to
+/// type in, every line ending with its own checksum so that a typo is found at once.
+fn write_chunk(document: &mut String, title: &str, label: &str, data: &[u8]) -> io::Result<()> {
+    let encoded = base32_encode(data);
+    let chunk_checksum = checksum(&format!("{label} {encoded}"), CHUNK_CHECKSUM);
+
+    let _ = writeln!(document, "{title}");
+    let _ = writeln!(document);
+    let _ = writeln!(document, "{}", qr_code(&format!("{MAGIC} {label} {chunk_checksum} {encoded}"))?);
+    let _ = writeln!(document);
+    let _ = writeln!(document, "{MAGIC} {label} {chunk_checksum}");
+
+    for (number, line) in encoded.as_bytes().chunks(GROUP_SIZE * GROUPS_PER_LINE).enumerate() {
+        let line = String::from_utf8_lossy(line);
+        let groups: Vec<&str> = line.as_bytes().chunks(GROUP_SIZE).map(|g| std::str::from_utf8(g).unwrap_or("")).collect();
+        let line_checksum = checksum(&format!("{label} {} {line}", number + 1), LINE_CHECKSUM);
+        let _ = writeln!(document, "{}  {line_checksum}", groups.join(" "));
+    }
+    let _ = writeln!(document);
+
+    Ok(())
+}
+
+/// Renders the vault file `vault` as a plain text document to print: how to restore it,
+/// the parameters to decrypt it by other means, a recovery secret to keep apart, and
+/// the file as QR codes and base32 text. The recovery secret opens a copy of
+/// `master_password` printed with the rest.
+pub fn render(vault: &[u8], master_password: &str, vault_name: &str, entries: usize) -> io::Result<String> {
+    let header = VaultHeader::read(vault)?;
+
+    let mut secret = [0u8; SECRET_BYTES];
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut secret);
+    OsRng.fill_bytes(&mut nonce);
+
+    let fingerprint = Sha256::digest(vault);
+    let encrypted = Aes256Gcm::new(&recovery_key(&secret).into())
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: master_password.as_bytes(), aad: &fingerprint })
+        .map_err(|_| io::Error::other("encryption failed"))?;
+    let recovery_block = [nonce.as_slice(), &encrypted].concat();
+
+    let chunks: Vec<&[u8]> = vault.chunks(CHUNK_BYTES).collect();
+
+    let mut document = String::new();
+    let _ = writeln!(document, "EMERGENCY KIT OF A PASSWORD MANAGER VAULT");
+    let _ = writeln!(document);
+    let _ = writeln!(document, "Vault          {vault_name}, {entries} entries");
+    let _ = writeln!(document, "Printed        {}", timestamp::format_date(timestamp::now()));
+    let _ = writeln!(document, "SHA-256        {}", grouped_hex(&fingerprint));
+    let _ = writeln!(document, "Chunks         {}", chunks.len());
+    let _ = writeln!(document);
+    let _ = writeln!(document, "This document holds the encrypted vault. Keep it as safe as the vault:");
+    let _ = writeln!(document, "with the master password, or with the recovery secret, it opens every");
+    let _ = writeln!(document, "entry.");
+    let _ = writeln!(document);
+    let _ = writeln!(document, "To restore, scan every QR code into a text file, one per line, or type");
+    let _ = writeln!(document, "in the lines under them, each chunk starting with its PMGR1 line. Then");
+    let _ = writeln!(document, "run, and compare the SHA-256 it prints with the one above:");
+    let _ = writeln!(document);
+    let _ = writeln!(document, "    password-manager --vault <NEW VAULT> restore <TEXT FILE>");
+    let _ = writeln!(document);
+    let _ = writeln!(document, "Add --recovery-secret if the master password is lost.");
+    let _ = writeln!(document);
+    let _ = writeln!(document, "VAULT PARAMETERS");
+    let _ = writeln!(document);
+    let _ = writeln!(document, "Format         PMGR version {}: magic, version, salt, verifier and nonce", header.version());
+    let _ = writeln!(document, "               in 72 bytes, then the data");
+    let _ = writeln!(
+        document,
+        "Key            Argon2id v19, {} KiB, {} iterations, {} lane, 32 bytes",
+        key_derivation::MEMORY_KIB,
+        key_derivation::ITERATIONS,
+        key_derivation::PARALLELISM
+    );
+    let _ = writeln!(document, "Salt           {} (ASCII)", String::from_utf8_lossy(&header.salt));
+    let _ = writeln!(document, "Verifier       HMAC-SHA256(\"magic-pwd\", key), to check the key:");
+    let _ = writeln!(document, "               {}", grouped_hex(&header.verifier));
+    let _ = writeln!(document, "Encryption     AES-256-GCM, key HMAC-SHA256(key, \"vault\"),");
+    let _ = writeln!(document, "               nonce {}", hex::encode(header.none));
+    let _ = writeln!(document, "Data           JSON, once decrypted");
+    let _ = writeln!(document);
+    let _ = writeln!(document, "MASTER PASSWORD RECOVERY BLOCK");
+    let _ = writeln!(document);
+    write_chunk(&mut document, "Opens with the recovery secret only.", RECOVERY_LABEL, &recovery_block)?;
+
+    let _ = writeln!(document, "VAULT");
+    let _ = writeln!(document);
+    for (index, chunk) in chunks.iter().enumerate() {
+        let label = format!("{}/{}", index + 1, chunks.len());
+        write_chunk(&mut document, &format!("Chunk {label}"), &label, chunk)?;
+    }
+
+    let _ = writeln!(document, "- - - - - - - - - - - - - - cut here - - - - - - - - - - - - - - -");
+    let _ = writeln!(document);
+    let _ = writeln!(document, "RECOVERY SECRET of {vault_name}, printed {}", timestamp::format_date(timestamp::now()));
+    let _ = writeln!(document);
+    let _ = writeln!(document, "    {}", format_secret(&secret));
+    let _ = writeln!(document);
+    let _ = writeln!(document, "Keep it apart from the rest: it reveals the master password to whoever");
+    let _ = writeln!(document, "also holds the recovery block.");
+
+    Ok(document)
+}
+
+/// A chunk as read back, keyed by its label.
+struct ReadChunk {
+    checksum: String,
+    encoded: String,
+}
+
+/// Reads a chunk header line, `PMGR1 <label> <checksum>`, followed on the same line by
+/// the data when it was scanned from a QR code.
+fn header_line(line: &str) -> Option<(String, ReadChunk)> {
+    let mut words = line.split_whitespace();
+    if !words.next()?.eq_ignore_ascii_case(MAGIC) {
+        return None;
+    }
+    let label = words.next()?.to_ascii_uppercase();
+    let checksum = normalize(words.next()?)?;
+    let encoded = words.collect();
+
+    Some((label, ReadChunk { checksum, encoded }))
+}
+
+/// Reassembles the vault file from the chunks of a paper backup, scanned or typed in.
+/// Lines outside the chunks are ignored, so the whole document can be given as is.
+pub fn reassemble(text: &str) -> io::Result<Reassembled> {
+    let mut chunks: BTreeMap<String, ReadChunk> = BTreeMap::new();
+    let mut current: Option<(String, ReadChunk, usize)> = None;
+
+    let mut finish = |chunk: Option<(String, ReadChunk, usize)>| -> io::Result<()> {
+        let Some((label, chunk, _)) = chunk else {
+            return Ok(());
+        };
+
+        let encoded = normalize(&chunk.encoded).ok_or_else(|| invalid_data(format!("chunk {label} is not base32")))?;
+        if checksum(&format!("{label} {encoded}"), CHUNK_CHECKSUM) != chunk.checksum {
+            return Err(invalid_data(format!("chunk {label} does not match its checksum, check it was read in full")));
+        }
+
+        if let Some(previous) = chunks.get(&label) && previous.encoded != encoded {
+            return Err(invalid_data(format!("chunk {label} was given twice, with different contents")));
+        }
+        chunks.insert(label, ReadChunk { checksum: chunk.checksum, encoded });
+        Ok(())
+    };
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if let Some((label, chunk)) = header_line(line) {
+            finish(current.take())?;
+            if chunk.encoded.is_empty() {
+                current = Some((label, chunk, 0));
+            } else {
+                finish(Some((label, chunk, 0)))?;
+            }
+            continue;
+        }
+
+        let Some((label, chunk, lines)) = current.as_mut() else {
+            continue;
+        };
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        let Some(line_checksum) = words.pop().filter(|_| !words.is_empty()) else {
+            finish(current.take())?;
+            continue;
+        };
+
+        *lines += 1;
+        let data = words.concat();
+        let normalized = normalize(&data)
+            .ok_or_else(|| invalid_data(format!("chunk {label}, line {lines}: not base32, check for typos")))?;
+        if normalize(line_checksum).is_none_or(|typed| checksum(&format!("{label} {lines} {normalized}"), LINE_CHECKSUM) != typed) {
+            return Err(invalid_data(format!("chunk {label}, line {lines}: does not match its checksum, check for typos")));
+        }
+        chunk.encoded.push_str(&normalized);
+    }
+    finish(current.take())?;
+
+    let recovery_block = chunks
+        .remove(RECOVERY_LABEL)
+        .map(|chunk| base32_decode(&chunk.encoded).expect("chunks are checked to be base32"));
+
+    let mut total = None;
+    let mut parts = BTreeMap::new();
+    for (label, chunk) in &chunks {
+        let parsed = label
+            .split_once('/')
+            .and_then(|(index, count)| Some((index.parse::<usize>().ok()?, count.parse::<usize>().ok()?)))
+            .filter(|(index, count)| (1..=*count).contains(index));
+        let Some((index, count)) = parsed else {
+            return Err(invalid_data(format!("unknown chunk {label}")));
+        };
+        if total.is_some_and(|total| total != count) {
+            return Err(invalid_data("the chunks come from different backups"));
+        }
+        total = Some(count);
+        parts.insert(index, base32_decode(&chunk.encoded).expect("chunks are checked to be base32"));
+    }
+
+    let Some(total) = total else {
+        return Err(invalid_data("no chunk of the vault was found"));
+    };
+    let missing: Vec<String> = (1..=total).filter(|index| !parts.contains_key(index)).map(|i| i.to_string()).collect();
+    if !missing.is_empty() {
+        return Err(invalid_data(format!("missing chunks {} of {total}", missing.join(", "))));
+    }
+
+    let vault: Vec<u8> = parts.into_values().flatten().collect();
+    VaultHeader::read(vault.as_slice())?;
+
+    Ok(Reassembled { vault, recovery_block })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER_PASSWORD: &str = "correct horse battery staple";
+
+    /// A vault file of four chunks, its data depending on `seed`, and its emergency kit.
+    fn kit(seed: u8) -> (Vec<u8>, String) {
+        let mut vault = Vec::new();
+        VaultHeader::new(*b"0123456789abcdefghijkl", [7; 32], [9; 12]).write(&mut vault).unwrap();
+        vault.extend((0..700).map(|i| (i * 7 % 256) as u8 ^ seed));
+
+        let document = render(&vault, MASTER_PASSWORD, "test.bin", 3).unwrap();
+        (vault, document)
+    }
+
+    /// The typed lines of chunk `label`, from its header line to the blank line after it.
+    fn typed_chunk<'a>(document: &'a str, label: &str) -> Vec<&'a str> {
+        let header = format!("{MAGIC} {label} ");
+        document
+            .lines()
+            .skip_while(|line| !line.starts_with(&header))
+            .take_while(|line| !line.is_empty())
+            .collect()
+    }
+
+    /// Chunk `label` on one line, as scanned from its QR code.
+    fn scanned_chunk(document: &str, label: &str) -> String {
+        let lines = typed_chunk(document, label);
+        let data: String = lines[1..]
+            .iter()
+            .flat_map(|line| {
+                let mut groups: Vec<&str> = line.split_whitespace().collect();
+                groups.pop();
+                groups
+            })
+            .collect();
+
+        format!("{} {data}", lines[0])
+    }
+
+    fn recovery_secret(document: &str) -> &str {
+        document
+            .lines()
+            .skip_while(|line| !line.starts_with("RECOVERY SECRET"))
+            .nth(2)
+            .unwrap()
+            .trim()
+    }
+
+    #[test]
+    fn reassembles_the_whole_document() {
+        let (vault, document) = kit(0);
+        assert!(document.contains("Chunks         4"));
+
+        let reassembled = reassemble(&document).unwrap();
+        assert_eq!(reassembled.vault, vault);
+        assert!(document.contains(&reassembled.fingerprint()[..35]));
+        assert_eq!(reassembled.recover_master_password(recovery_secret(&document)).unwrap(), MASTER_PASSWORD);
+    }
+
+    #[test]
+    fn reassembles_scanned_chunks_in_any_order() {
+        let (vault, document) = kit(0);
+        let scanned: Vec<String> = ["3/4", "1/4", "4/4", "2/4", "1/4"].iter().map(|label| scanned_chunk(&document, label)).collect();
+
+        let reassembled = reassemble(&scanned.join("\n")).unwrap();
+        assert_eq!(reassembled.vault, vault);
+        assert!(reassembled.recover_master_password(recovery_secret(&document)).is_err());
+    }
+
+    #[test]
+    fn reads_through_common_typos() {
+        let (vault, document) = kit(0);
+        let typed = typed_chunk(&document, "1/4").join("\n").to_lowercase().replace('o', "0").replace('i', "1");
+        let rest: Vec<String> = ["2/4", "3/4", "4/4"].iter().map(|label| scanned_chunk(&document, label)).collect();
+
+        assert_eq!(reassemble(&format!("{typed}\n{}", rest.join("\n"))).unwrap().vault, vault);
+    }
+
+    #[test]
+    fn reports_missing_chunks() {
+        let (_, document) = kit(0);
+        let scanned: Vec<String> = ["1/4", "4/4"].iter().map(|label| scanned_chunk(&document, label)).collect();
+
+        let error = reassemble(&scanned.join("\n")).err().unwrap();
+        assert_eq!(error.to_string(), "missing chunks 2, 3 of 4");
+        assert!(reassemble("nothing to see").is_err());
+    }
+
+    #[test]
+    fn reports_corrupted_chunks() {
+        let (_, document) = kit(0);
+
+        // A typed line is checked on its own, and names the line.
+        let line = typed_chunk(&document, "2/4")[2];
+        let swapped = if line.starts_with('A') { line.replacen('A', "B", 1) } else { format!("A{}", &line[1..]) };
+        let error = reassemble(&document.replacen(line, &swapped, 1)).err().unwrap();
+        assert_eq!(error.to_string(), "chunk 2/4, line 2: does not match its checksum, check for typos");
+
+        // A scanned chunk only has the checksum of the whole chunk.
+        let scanned = scanned_chunk(&document, "3/4");
+        let cut = &scanned[..scanned.len() - 5];
+        let error = reassemble(cut).err().unwrap();
+        assert_eq!(error.to_string(), "chunk 3/4 does not match its checksum, check it was read in full");
+    }
+
+    #[test]
+    fn refuses_chunks_of_different_backups() {
+        let (_, first) = kit(0);
+        let (_, second) = kit(1);
+
+        let mixed = format!("{}\n{}", scanned_chunk(&first, "1/4"), scanned_chunk(&second, "1/4"));
+        let error = reassemble(&mixed).err().unwrap();
+        assert_eq!(error.to_string(), "chunk 1/4 was given twice, with different contents");
+    }
+}
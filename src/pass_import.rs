@@ -0,0 +1,207 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{ Path, PathBuf };
+use std::process::{ Command, Stdio };
+use std::time::UNIX_EPOCH;
+
+use crate::{ import::ImportBatch, vault_entry::{ CustomField, VaultEntry } };
+
+/// Keys of the `key: value` lines holding the username, in order of preference. An
+/// email is only the username when none of the others is there.
+const USERNAME_KEYS: [&str; 4] = ["login", "username", "user", "email"];
+const URL_KEYS: [&str; 4] = ["url", "website", "site", "link"];
+const TAGS_KEY: &str = "tags";
+/// Custom fields whose key contains one of these are protected like the password.
+const SECRET_KEYS: [&str; 7] = ["pin", "secret", "answer", "recovery", "token", "key", "code"];
+
+/// Reads a pass store, one entry per file under `root` except hidden ones such as
+/// `.git` and `.gpg-id`. Files ending in `.gpg` are decrypted with `decrypt_command`,
+/// the others are read as already decrypted.
+pub fn import(root: &Path, decrypt_command: Option<&str>) -> io::Result<ImportBatch> {
+    if !root.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not a directory", root.display())));
+    }
+
+    let mut files = Vec::new();
+    collect_files(root, &mut files)?;
+    files.sort();
+
+    let mut batch = ImportBatch { format: "pass store".to_string(), ..ImportBatch::default() };
+    for file in files {
+        let relative = file.strip_prefix(root).expect("the files are under the root");
+
+        let entry = read_text(&file, decrypt_command)?.and_then(|text| {
+            let mut entry = read_entry(relative, &text)?;
+            if let Some(modified) = modified_time(&file) {
+                entry.created = modified;
+                entry.modified = modified;
+            }
+            Ok(entry)
+        });
+
+        match entry {
+            Ok(entry) => batch.entries.push(entry),
+            Err(reason) => batch.skipped.push(format!("{}: {reason}", relative.display())),
+        }
+    }
+
+    Ok(batch)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else {
+            files.push(entry.path());
+        }
+    }
+
+    Ok(())
+}
+
+fn modified_time(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// The decrypted content of a file, or why it cannot be imported. Fails when the
+/// decrypt command cannot be run at all, which would fail for every file.
+fn read_text(path: &Path, decrypt_command: Option<&str>) -> io::Result<Result<String, String>> {
+    let bytes = if path.extension().is_some_and(|extension| extension == "gpg") {
+        let Some(command) = decrypt_command else {
+            return Ok(Err("encrypted, decrypt the store first or give a decrypt command".to_string()));
+        };
+
+        match decrypt(command, path)? {
+            Ok(bytes) => bytes,
+            Err(reason) => return Ok(Err(reason)),
+        }
+    } else {
+        match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) => return Ok(Err(e.to_string())),
+        }
+    };
+
+    Ok(String::from_utf8(bytes).map_err(|_| "not a text file".to_string()))
+}
+
+/// Runs `command` with the path of the file in place of `{}`, or after its arguments,
+/// and returns what it printed. The command is split on whitespace, not run by a shell.
+fn decrypt(command: &str, path: &Path) -> io::Result<Result<Vec<u8>, String>> {
+    let mut words = command.split_whitespace();
+    let Some(program) = words.next() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the decrypt command is empty"));
+    };
+
+    let mut args: Vec<OsString> = words
+        .map(|word| if word == "{}" { path.as_os_str().to_owned() } else { OsString::from(word) })
+        .collect();
+    if !command.split_whitespace().any(|word| word == "{}") {
+        args.push(path.as_os_str().to_owned());
+    }
+
+    let output = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("could not run the decrypt command `{program}`: {e}")))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = match stderr.trim() {
+            "" => output.status.to_string(),
+            stderr => stderr.to_string(),
+        };
+        return Ok(Err(format!("{program} failed: {reason}")));
+    }
+
+    Ok(Ok(output.stdout))
+}
+
+/// The key and the value of a `key: value` line, `None` for other lines, e.g. a URL.
+fn key_value(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    if key.trim().is_empty() || key.starts_with(char::is_whitespace) || key.len() > 32 || value.starts_with("//") {
+        return None;
+    }
+
+    Some((key.trim(), value.trim()))
+}
+
+/// Builds the entry of the file at `relative` in the store. The first line is the
+/// password, `key: value` lines give the username, the URL, the tags and custom
+/// fields, an `otpauth://` line the TOTP secret and the other lines the notes.
+fn read_entry(relative: &Path, text: &str) -> Result<VaultEntry, String> {
+    let mut folders: Vec<String> = relative.iter().map(|c| c.to_string_lossy().into_owned()).collect();
+    let file_name = folders.pop().unwrap_or_default();
+    let name = file_name
+        .strip_suffix(".gpg")
+        .or_else(|| file_name.strip_suffix(".txt"))
+        .unwrap_or(&file_name)
+        .to_string();
+
+    let mut lines = text.lines();
+    let password = lines.next().filter(|line| !line.is_empty()).ok_or("no password on the first line")?;
+
+    let mut pairs: Vec<(&str, &str)> = Vec::new();
+    let mut totp = None;
+    let mut notes = Vec::new();
+    for line in lines {
+        if line.trim().starts_with("otpauth://") && totp.is_none() {
+            totp = Some(line.trim().to_string());
+        } else if let Some(pair) = key_value(line) {
+            pairs.push(pair);
+        } else {
+            notes.push(line);
+        }
+    }
+
+    let take = |pairs: &mut Vec<(&str, &str)>, keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| pairs.iter().position(|(k, value)| k.eq_ignore_ascii_case(key) && !value.is_empty()))
+            .map(|index| pairs.remove(index).1.to_string())
+    };
+    let mut username = take(&mut pairs, &USERNAME_KEYS);
+    let url = take(&mut pairs, &URL_KEYS);
+
+    // Without a username line, `site.com/alice` is alice's login to site.com.
+    let service = match folders.last() {
+        Some(site) if username.is_none() && site.contains('.') => {
+            username = Some(name);
+            folders.pop().unwrap_or_default()
+        }
+        _ => name,
+    };
+
+    let mut entry = VaultEntry::new(service, username, password.as_bytes().to_vec());
+    entry.url = url;
+    entry.folder = Some(folders.join("/")).filter(|folder| !folder.is_empty());
+    entry.totp = totp;
+
+    for (key, value) in pairs {
+        if key.eq_ignore_ascii_case(TAGS_KEY) {
+            entry.tags.extend(value.split([',', ' ']).filter(|t| !t.is_empty()).map(str::to_string));
+            continue;
+        }
+
+        let lowercase = key.to_lowercase();
+        entry.custom_fields.push(CustomField {
+            name: key.to_string(),
+            value: value.to_string(),
+            protected: SECRET_KEYS.iter().any(|secret| lowercase.contains(secret)),
+        });
+    }
+
+    let notes = notes.join("\n");
+    entry.notes = Some(notes.trim().to_string()).filter(|notes| !notes.is_empty());
+
+    Ok(entry)
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use rand::{ Rng, rngs::OsRng };
+use serde::{ Deserialize, Serialize };
+use strum::{ Display, EnumIter, EnumString };
+
+/// Lists bundled in the binary, in the diceware format of the EFF lists: a dice roll and a
+/// word per line, five dice for the large list and four for the short one.
+///
+/// These files only stand in for the EFF lists, with their sizes so a word is worth the
+/// same entropy. They are to be replaced by `eff_large_wordlist.txt` and
+/// `eff_short_wordlist_1.txt` from https://www.eff.org/dice, published by the Electronic
+/// Frontier Foundation under CC BY 3.0 US, and credited as such.
+const LARGE_LIST: &str = include_str!("wordlists/large.txt");
+const SHORT_LIST: &str = include_str!("wordlists/short.txt");
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumIter, EnumString, Deserialize, Serialize)]
+#[strum(serialize_all = "lowercase")]
+pub enum BuiltinList {
+    /// 7776 words, 12.9 bits each.
+    #[default]
+    Large,
+    /// 1296 shorter words, 10.3 bits each.
+    Short,
+}
+
+#[derive(Debug)]
+pub struct WordList {
+    words: Vec<String>,
+}
+
+impl WordList {
+    pub fn builtin(list: BuiltinList) -> &'static WordList {
+        static LARGE: OnceLock<WordList> = OnceLock::new();
+        static SHORT: OnceLock<WordList> = OnceLock::new();
+
+        match list {
+            BuiltinList::Large => LARGE.get_or_init(|| Self::parse(LARGE_LIST).expect("valid word list")),
+            BuiltinList::Short => SHORT.get_or_init(|| Self::parse(SHORT_LIST).expect("valid word list")),
+        }
+    }
+
+    /// Reads a word list with one word per line, optionally preceded by its dice roll as
+    /// in the EFF lists. Duplicates are dropped so they do not inflate the entropy.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut words: Vec<String> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                match line.split_once(char::is_whitespace) {
+                    Some((roll, word)) if roll.chars().all(|c| ('1'..='6').contains(&c)) => word.trim(),
+                    _ => line,
+                }
+            })
+            .map(str::to_string)
+            .collect();
+
+        words.sort_unstable();
+        words.dedup();
+
+        if words.len() < 2 {
+            return Err("a word list needs at least two different words".to_string());
+        }
+
+        Ok(Self { words })
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumIter, EnumString, Deserialize, Serialize)]
+#[strum(serialize_all = "lowercase")]
+pub enum Capitalization {
+    #[default]
+    Lower,
+    /// First letter of every word.
+    Title,
+    Upper,
+    /// Each word either lowercase or title case, one more bit per word.
+    Random,
+}
+
+pub const MAX_WORDS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PassphrasePolicy {
+    pub words: usize,
+    pub separator: String,
+    pub capitalization: Capitalization,
+    /// Appends a random digit to one random word.
+    pub insert_digit: bool,
+}
+
+impl Default for PassphrasePolicy {
+    fn default() -> Self {
+        Self {
+            words: 6,
+            separator: "-".to_string(),
+            capitalization: Capitalization::Lower,
+            insert_digit: false,
+        }
+    }
+}
+
+fn title_case(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl PassphrasePolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.words == 0 || self.words > MAX_WORDS {
+            return Err(format!("the number of words must be between 1 and {MAX_WORDS}"));
+        }
+
+        Ok(())
+    }
+
+    /// Entropy in bits of the passphrases drawn from `list`. Capitalization and the digit
+    /// add bits only when they are random.
+    pub fn entropy(&self, list: &WordList) -> Result<f64, String> {
+        self.validate()?;
+
+        let mut bits = (self.words as f64) * (list.len() as f64).log2();
+        if self.capitalization == Capitalization::Random {
+            bits += self.words as f64;
+        }
+        if self.insert_digit {
+            bits += ((10 * self.words) as f64).log2();
+        }
+
+        Ok(bits)
+    }
+
+    /// Draws a passphrase from `list` with the OS random number generator.
+    pub fn generate(&self, list: &WordList) -> Result<String, String> {
+        self.validate()?;
+
+        let mut words: Vec<String> = (0..self.words)
+            .map(|_| {
+                let word = &list.words[OsRng.gen_range(0..list.len())];
+
+                match self.capitalization {
+                    Capitalization::Lower => word.to_lowercase(),
+                    Capitalization::Title => title_case(word),
+                    Capitalization::Upper => word.to_uppercase(),
+                    Capitalization::Random if OsRng.gen_bool(0.5) => title_case(word),
+                    Capitalization::Random => word.to_lowercase(),
+                }
+            })
+            .collect();
+
+        if self.insert_digit {
+            let index = OsRng.gen_range(0..words.len());
+            words[index].push(char::from(b'0' + OsRng.gen_range(0..10u8)));
+        }
+
+        Ok(words.join(&self.separator))
+    }
+}
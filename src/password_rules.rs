@@ -0,0 +1,379 @@
+use std::fmt;
+
+use crate::{
+    generator::{ CharClass, PasswordPolicy },
+    vault_entry::VaultEntry,
+};
+
+/// Characters of the `special` class of the `passwordrules` syntax.
+const SPECIAL: &str = "-~!@#$%^&*_+=`|(){}[:;\"'<>,.?] ";
+
+/// Rules of sites known to restrict passwords, after the quirks collected in Apple's
+/// password-manager-resources. Sites change their rules, so an entry's own descriptor
+/// takes precedence.
+const DOMAIN_RULES: [(&str, &str); 18] = [
+    ("americanexpress.com", "minlength: 8; maxlength: 20; max-consecutive: 4; required: lower, upper; required: digit; allowed: [%&_?#=];"),
+    ("apple.com", "minlength: 8; maxlength: 63; required: lower; required: upper; required: digit; allowed: ascii-printable;"),
+    ("bankofamerica.com", "minlength: 8; maxlength: 20; max-consecutive: 3; required: lower; required: upper; required: digit; allowed: [-@#*()+={}/?~;,._];"),
+    ("battle.net", "minlength: 8; maxlength: 16; required: lower, upper; allowed: digit, special;"),
+    ("chase.com", "minlength: 8; maxlength: 32; max-consecutive: 2; required: lower, upper; required: digit; required: [!#$%+/=@~];"),
+    ("citi.com", "minlength: 8; maxlength: 64; max-consecutive: 2; required: digit; required: upper; required: lower; required: [!#$%&*@^];"),
+    ("costco.com", "minlength: 8; maxlength: 20; required: lower, upper; allowed: digit, [-!#$%&'()*+/:;=?@^_`{|}~];"),
+    ("dell.com", "minlength: 8; maxlength: 20; required: lower; required: upper; required: digit; required: [!#$%&*+-.<=>?@^_];"),
+    ("discover.com", "minlength: 6; maxlength: 16; required: lower, upper; required: digit; allowed: [@#$%^&*-];"),
+    ("ea.com", "minlength: 8; maxlength: 64; required: lower; required: upper; required: digit; allowed: special;"),
+    ("ebay.com", "minlength: 6; maxlength: 64; required: lower, upper; required: digit, [!@#$%^&*];"),
+    ("hsbc.com", "minlength: 8; maxlength: 30; required: lower; required: upper; required: digit; allowed: [-!#$%&*+./=?@^_];"),
+    ("paypal.com", "minlength: 8; maxlength: 20; max-consecutive: 3; required: lower, upper; required: digit, [!@#$%^&*()];"),
+    ("target.com", "minlength: 8; maxlength: 20; required: lower, upper; required: digit, [-!#$%&*_+=@^];"),
+    ("usbank.com", "minlength: 8; maxlength: 24; max-consecutive: 3; required: lower; required: upper; required: digit; allowed: [!#$%&*+-.=?@^_];"),
+    ("verizonwireless.com", "minlength: 8; maxlength: 20; required: lower, upper; required: digit; allowed: unicode;"),
+    ("wellsfargo.com", "minlength: 8; maxlength: 32; required: lower; required: digit; allowed: upper, [-!#$%&*+./=?@^_~];"),
+    ("zillow.com", "minlength: 8; maxlength: 32; required: lower; required: upper; required: digit; allowed: [!#$%&*+-.<=>?@^_];"),
+];
+
+/// Generated candidates tried before a site's rules are considered impossible to meet.
+const MAX_ATTEMPTS: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CharSet {
+    Upper,
+    Lower,
+    Digit,
+    Special,
+    AsciiPrintable,
+    Unicode,
+    Custom(String),
+}
+
+impl CharSet {
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            CharSet::Upper => c.is_ascii_uppercase(),
+            CharSet::Lower => c.is_ascii_lowercase(),
+            CharSet::Digit => c.is_ascii_digit(),
+            CharSet::Special => SPECIAL.contains(c),
+            CharSet::AsciiPrintable => (' '..='~').contains(&c),
+            CharSet::Unicode => true,
+            CharSet::Custom(chars) => chars.contains(c),
+        }
+    }
+}
+
+impl fmt::Display for CharSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharSet::Upper => write!(f, "upper"),
+            CharSet::Lower => write!(f, "lower"),
+            CharSet::Digit => write!(f, "digit"),
+            CharSet::Special => write!(f, "special"),
+            CharSet::AsciiPrintable => write!(f, "ascii-printable"),
+            CharSet::Unicode => write!(f, "unicode"),
+            CharSet::Custom(chars) => {
+                // `-` and `]` are only literal at the edges of a custom class.
+                let mut ordered: String = chars.chars().filter(|c| !matches!(c, '-' | ']')).collect();
+                if chars.contains(']') {
+                    ordered.insert(0, ']');
+                }
+                if chars.contains('-') {
+                    ordered.insert(0, '-');
+                }
+                write!(f, "[{ordered}]")
+            }
+        }
+    }
+}
+
+/// Requirements of a site on its passwords, in the `passwordrules` syntax, e.g.
+/// `minlength: 8; maxlength: 20; required: lower; required: digit; allowed: [-_];`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasswordRules {
+    /// Each password needs a character of every group.
+    pub required: Vec<Vec<CharSet>>,
+    pub allowed: Vec<CharSet>,
+    pub max_consecutive: Option<usize>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+}
+
+fn parse_sets(value: &str) -> Result<Vec<CharSet>, String> {
+    let mut sets = Vec::new();
+    let mut rest = value.trim();
+
+    while !rest.is_empty() {
+        if let Some(custom) = rest.strip_prefix('[') {
+            // A `]` right after the opening bracket is part of the class.
+            let close = custom
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| *c == ']')
+                .map(|(i, _)| i)
+                .filter(|_| custom.starts_with(']'))
+                .or_else(|| custom.find(']'))
+                .ok_or_else(|| format!("unclosed character class in `{value}`"))?;
+
+            let chars = &custom[..close];
+            if chars.is_empty() {
+                return Err("empty character class `[]`".to_string());
+            }
+
+            sets.push(CharSet::Custom(chars.to_string()));
+            rest = custom[close + 1..].trim_start();
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let name = rest[..end].trim();
+
+            sets.push(
+                match name.to_lowercase().as_str() {
+                    "upper" => CharSet::Upper,
+                    "lower" => CharSet::Lower,
+                    "digit" => CharSet::Digit,
+                    "special" => CharSet::Special,
+                    "ascii-printable" => CharSet::AsciiPrintable,
+                    "unicode" => CharSet::Unicode,
+                    _ => {
+                        return Err(format!("unknown character class `{name}`"));
+                    }
+                }
+            );
+            rest = rest[end..].trim_start();
+        }
+
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after.trim_start();
+        } else if !rest.is_empty() {
+            return Err(format!("expected `,` between the character classes of `{value}`"));
+        }
+    }
+
+    Ok(sets)
+}
+
+impl PasswordRules {
+    pub fn parse(descriptor: &str) -> Result<Self, String> {
+        let mut rules = PasswordRules::default();
+
+        // Custom classes may contain `;`, so properties are split outside brackets only.
+        let mut properties = Vec::new();
+        let mut start = 0;
+        let mut in_class = false;
+        for (i, c) in descriptor.char_indices() {
+            match c {
+                '[' if !in_class => in_class = true,
+                ']' if in_class && descriptor[..i].ends_with('[') => {}
+                ']' => in_class = false,
+                ';' if !in_class => {
+                    properties.push(&descriptor[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        properties.push(&descriptor[start..]);
+
+        for property in properties.into_iter().map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, value) = property
+                .split_once(':')
+                .ok_or_else(|| format!("expected `name: value` in `{property}`"))?;
+
+            let number = || {
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("`{}` expects a number", name.trim()))
+            };
+
+            match name.trim().to_lowercase().as_str() {
+                "required" => rules.required.push(parse_sets(value)?),
+                "allowed" => rules.allowed.extend(parse_sets(value)?),
+                "max-consecutive" => rules.max_consecutive = Some(number()?),
+                "minlength" => rules.min_length = Some(number()?),
+                "maxlength" => rules.max_length = Some(number()?),
+                other => {
+                    return Err(format!("unknown property `{other}`"));
+                }
+            }
+        }
+
+        if let (Some(min), Some(max)) = (rules.min_length, rules.max_length) && min > max {
+            return Err(format!("minlength {min} is above maxlength {max}"));
+        }
+
+        Ok(rules)
+    }
+
+    /// Rules of the entry: its own descriptor, or else the bundled rules of its site.
+    pub fn for_entry(entry: &VaultEntry) -> Option<Self> {
+        if let Some(descriptor) = &entry.password_rules {
+            return Self::parse(descriptor).ok();
+        }
+
+        Self::for_host(&entry.host()?)
+    }
+
+    /// Bundled rules of a host name or one of its parent domains.
+    pub fn for_host(host: &str) -> Option<Self> {
+        let host = host.to_lowercase();
+
+        DOMAIN_RULES
+            .iter()
+            .find(|(domain, _)| host == *domain || host.ends_with(&format!(".{domain}")))
+            .map(|(_, descriptor)| Self::parse(descriptor).expect("bundled rules are valid"))
+    }
+
+    /// Whether `c` may appear at all. Without any class given, every printable ASCII
+    /// character may.
+    fn allows_char(&self, c: char) -> bool {
+        if self.required.is_empty() && self.allowed.is_empty() {
+            return CharSet::AsciiPrintable.contains(c);
+        }
+
+        self.allowed.iter().chain(self.required.iter().flatten()).any(|set| set.contains(c))
+    }
+
+    /// The first rule `password` breaks, if any.
+    pub fn check(&self, password: &str) -> Result<(), String> {
+        let length = password.chars().count();
+
+        if let Some(min) = self.min_length && length < min {
+            return Err(format!("shorter than {min} characters"));
+        }
+        if let Some(max) = self.max_length && length > max {
+            return Err(format!("longer than {max} characters"));
+        }
+
+        if let Some(c) = password.chars().find(|c| !self.allows_char(*c)) {
+            return Err(format!("`{c}` is not allowed"));
+        }
+
+        for group in &self.required {
+            if !password.chars().any(|c| group.iter().any(|set| set.contains(c))) {
+                let names: Vec<String> = group.iter().map(CharSet::to_string).collect();
+                return Err(format!("needs a character of {}", names.join(" or ")));
+            }
+        }
+
+        if let Some(max) = self.max_consecutive {
+            let chars: Vec<char> = password.chars().collect();
+            if max > 0 && chars.windows(max + 1).any(|run| run.iter().all(|c| *c == run[0])) {
+                return Err(format!("repeats a character more than {max} times in a row"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `policy` narrowed to the characters and lengths these rules allow. What the policy
+    /// cannot express, such as a character required from one of several classes, is
+    /// enforced by `generate`.
+    pub fn restrict(&self, policy: &PasswordPolicy) -> PasswordPolicy {
+        let mut restricted = policy.clone();
+
+        restricted.length = policy.length
+            .max(self.min_length.unwrap_or(0))
+            .min(self.max_length.unwrap_or(usize::MAX));
+
+        let full = PasswordPolicy {
+            symbol_set: SPECIAL.to_string(),
+            ..PasswordPolicy::default()
+        };
+
+        for class in [CharClass::Lowercase, CharClass::Uppercase, CharClass::Digits, CharClass::Symbols] {
+            let chars: Vec<char> = full
+                .chars(class)
+                .into_iter()
+                .filter(|c| self.allows_char(*c))
+                .collect();
+
+            // A group that only this class can satisfy becomes a minimum count.
+            let required_alone = self.required.iter().any(|group| {
+                group.iter().all(|set| {
+                    match set {
+                        CharSet::Upper => class == CharClass::Uppercase,
+                        CharSet::Lower => class == CharClass::Lowercase,
+                        CharSet::Digit => class == CharClass::Digits,
+                        CharSet::Special => class == CharClass::Symbols,
+                        CharSet::Custom(custom) => custom.chars().all(|c| full.chars(class).contains(&c)),
+                        CharSet::AsciiPrintable | CharSet::Unicode => false,
+                    }
+                })
+            });
+
+            let rule = restricted.rule_mut(class);
+            if chars.is_empty() {
+                rule.enabled = false;
+                rule.min = 0;
+                continue;
+            }
+
+            rule.enabled = policy.rule(class).enabled || required_alone;
+            if required_alone {
+                rule.min = rule.min.max(1);
+            }
+
+            // The chosen symbols that are allowed, and those a rule asks for by name.
+            if class == CharClass::Symbols {
+                let required: Vec<char> = self.required
+                    .iter()
+                    .flatten()
+                    .filter_map(|set| if let CharSet::Custom(custom) = set { Some(custom) } else { None })
+                    .flat_map(|custom| custom.chars())
+                    .filter(|c| chars.contains(c))
+                    .collect();
+
+                let mut symbols: String = policy.symbol_set
+                    .chars()
+                    .filter(|c| chars.contains(c))
+                    .chain(required)
+                    .collect();
+                if symbols.is_empty() {
+                    symbols = chars.into_iter().collect();
+                }
+                restricted.symbol_set = symbols;
+            }
+        }
+
+        restricted
+    }
+
+    /// Generates a password with `policy` narrowed to these rules, drawing again until
+    /// one follows every rule.
+    pub fn generate(&self, policy: &PasswordPolicy) -> Result<String, String> {
+        let restricted = self.restrict(policy);
+        restricted.validate()?;
+
+        for _ in 0..MAX_ATTEMPTS {
+            let password = restricted.generate()?;
+            if self.check(&password).is_ok() {
+                return Ok(password);
+            }
+        }
+
+        Err("no password following the site's rules could be generated with these settings".to_string())
+    }
+}
+
+impl fmt::Display for PasswordRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sets = |sets: &[CharSet]| sets.iter().map(CharSet::to_string).collect::<Vec<_>>().join(", ");
+        let mut properties = Vec::new();
+
+        if let Some(min) = self.min_length {
+            properties.push(format!("minlength: {min}"));
+        }
+        if let Some(max) = self.max_length {
+            properties.push(format!("maxlength: {max}"));
+        }
+        if let Some(max) = self.max_consecutive {
+            properties.push(format!("max-consecutive: {max}"));
+        }
+        for group in &self.required {
+            properties.push(format!("required: {}", sets(group)));
+        }
+        if !self.allowed.is_empty() {
+            properties.push(format!("allowed: {}", sets(&self.allowed)));
+        }
+
+        write!(f, "{};", properties.join("; "))
+    }
+}
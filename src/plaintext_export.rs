@@ -0,0 +1,199 @@
+use std::fs::{ self, OpenOptions };
+use std::io::{ self, IsTerminal, Write };
+use std::path::Path;
+
+use serde::{ Deserialize, Serialize };
+
+use crate::{ timestamp, vault_entry::{ CustomField, VaultEntry } };
+
+/// Columns of the CSV export, the fields `import csv --map` knows plus the TOTP.
+const CSV_COLUMNS: [&str; 10] = [
+    "service",
+    "username",
+    "password",
+    "url",
+    "notes",
+    "folder",
+    "tags",
+    "created",
+    "modified",
+    "totp",
+];
+
+/// A plaintext export as remembered by the vault, for the security report.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PlaintextExport {
+    pub time: u64,
+    pub format: String,
+    /// The file written, or `stdout`.
+    pub destination: String,
+    pub entries: usize,
+}
+
+/// An entry as written to a JSON export, its passwords in clear.
+#[derive(Debug, Serialize)]
+struct PlainEntry<'a> {
+    service: &'a str,
+    username: Option<&'a str>,
+    password: String,
+    url: Option<&'a str>,
+    notes: Option<&'a str>,
+    folder: Option<&'a str>,
+    tags: &'a [String],
+    created: String,
+    modified: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    totp: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[CustomField]>::is_empty")]
+    custom_fields: &'a [CustomField],
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    previous_passwords: Vec<String>,
+}
+
+fn datetime(time: u64) -> String {
+    if time == 0 { String::new() } else { timestamp::format_datetime(time) }
+}
+
+/// The entries as CSV, one row each under a header of `CSV_COLUMNS`. Custom fields and
+/// attachments have no column and are left out.
+pub fn to_csv(entries: &[VaultEntry]) -> io::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(CSV_COLUMNS)?;
+
+    for entry in entries {
+        writer.write_record([
+            entry.service.as_str(),
+            entry.username.as_deref().unwrap_or(""),
+            &String::from_utf8_lossy(&entry.password),
+            entry.url.as_deref().unwrap_or(""),
+            entry.notes.as_deref().unwrap_or(""),
+            entry.folder.as_deref().unwrap_or(""),
+            &entry.tags.join(","),
+            &datetime(entry.created),
+            &datetime(entry.modified),
+            entry.totp.as_deref().unwrap_or(""),
+        ])?;
+    }
+
+    writer.into_inner().map_err(|e| io::Error::other(e.to_string()))
+}
+
+/// The entries as a JSON array, custom fields and previous passwords included.
+/// Attachments are left out.
+pub fn to_json(entries: &[VaultEntry]) -> io::Result<Vec<u8>> {
+    let plain: Vec<PlainEntry> = entries
+        .iter()
+        .map(|entry| PlainEntry {
+            service: &entry.service,
+            username: entry.username.as_deref(),
+            password: String::from_utf8_lossy(&entry.password).into_owned(),
+            url: entry.url.as_deref(),
+            notes: entry.notes.as_deref(),
+            folder: entry.folder.as_deref(),
+            tags: &entry.tags,
+            created: datetime(entry.created),
+            modified: datetime(entry.modified),
+            expires: entry.expires.map(datetime),
+            totp: entry.totp.as_deref(),
+            custom_fields: &entry.custom_fields,
+            previous_passwords: entry.password_history
+                .iter()
+                .map(|version| String::from_utf8_lossy(&version.password).into_owned())
+                .collect(),
+        })
+        .collect();
+
+    serde_json::to_vec_pretty(&plain).map_err(io::Error::other)
+}
+
+/// Why `path` is not a safe place for a plaintext export, `None` when it is. `-` stands
+/// for stdout, which must not be a terminal. Files must not already exist, nor be in a
+/// directory other users can read or write.
+pub fn unsafe_destination(path: &Path) -> Option<String> {
+    if path == Path::new("-") {
+        return io::stdout().is_terminal().then(|| "stdout is a terminal".to_string());
+    }
+
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if is_terminal_device(&metadata) {
+            return Some(format!("{} is a terminal", path.display()));
+        }
+        return Some(format!("{} already exists", path.display()));
+    }
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let metadata = fs::metadata(parent).ok()?;
+    if let Some(mode) = other_users_access(&metadata) {
+        return Some(format!("other users can {mode} {}", parent.display()));
+    }
+
+    None
+}
+
+#[cfg(unix)]
+fn is_terminal_device(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    metadata.file_type().is_char_device()
+}
+
+#[cfg(not(unix))]
+fn is_terminal_device(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn other_users_access(metadata: &fs::Metadata) -> Option<&'static str> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode();
+    match (mode & 0o004 != 0, mode & 0o002 != 0) {
+        (_, true) => Some("write to"),
+        (true, false) => Some("read"),
+        (false, false) => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn other_users_access(_metadata: &fs::Metadata) -> Option<&'static str> {
+    None
+}
+
+/// Writes `contents` to `path`, or to stdout for `-`, the file readable by its owner
+/// only. An existing file is replaced rather than written through, so it does not keep
+/// laxer permissions.
+pub fn write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if path == Path::new("-") {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(contents)?;
+        return stdout.flush();
+    }
+
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_file() || metadata.is_symlink() => fs::remove_file(path)?,
+        // A device or a pipe, e.g. /dev/tty, has nothing to replace.
+        Ok(metadata) if !metadata.is_dir() => {
+            let mut device = OpenOptions::new().write(true).open(path)?;
+            return device.write_all(contents);
+        }
+        _ => {}
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
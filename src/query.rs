@@ -0,0 +1,400 @@
+use std::fmt;
+
+use crate::{ search::{ self, SearchResult }, timestamp, vault_entry::VaultEntry };
+
+const FIELDS: &str = "service, username, url, tag, folder, notes, modified, created";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Service,
+    Username,
+    Url,
+    Tag,
+    Folder,
+    Notes,
+    Modified,
+    Created,
+    /// Negated free text, matched against every text field.
+    Any,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "service" | "name" => Some(Field::Service),
+            "username" | "user" | "login" => Some(Field::Username),
+            "url" => Some(Field::Url),
+            "tag" => Some(Field::Tag),
+            "folder" => Some(Field::Folder),
+            "notes" | "note" => Some(Field::Notes),
+            "modified" => Some(Field::Modified),
+            "created" => Some(Field::Created),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn holds(&self, left: u64, right: u64) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+
+    /// `age < x` is `timestamp > now - x`.
+    fn flipped(&self) -> Self {
+        match self {
+            Comparison::Less => Comparison::Greater,
+            Comparison::LessOrEqual => Comparison::GreaterOrEqual,
+            Comparison::Greater => Comparison::Less,
+            Comparison::GreaterOrEqual => Comparison::LessOrEqual,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    /// Lowercased value, may contain `*` and `?` wildcards.
+    Pattern(String),
+    /// Compares the field against an absolute timestamp.
+    Date(Comparison, u64),
+}
+
+#[derive(Debug, Clone)]
+struct Filter {
+    negated: bool,
+    field: Field,
+    predicate: Predicate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    /// Byte offset in the query where the problem starts.
+    pub position: usize,
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self { position, message: message.into() }
+    }
+
+    /// Renders the query with a caret under the offending position.
+    pub fn annotate(&self, query: &str) -> String {
+        let column = query[..self.position.min(query.len())].chars().count();
+        format!("{}\n{}^ {}", query, " ".repeat(column), self.message)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at position {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// A parsed search query.
+///
+/// Terms are separated by whitespace and can be quoted. `field:value` terms filter
+/// entries, a leading `-` negates a term and every other term is fuzzy matched:
+///
+/// ```text
+/// gthb tag:work username:alice url:*.corp.example -folder:archive modified:<90d
+/// ```
+///
+/// Text values match case-insensitively as substrings unless they contain `*` or `?`
+/// wildcards, tags and folders match as a whole. `modified` and `created` take a
+/// comparison with an age (`90d`, `6w`, `3m`, `1y`) or a date (`2024-01-31`).
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    filters: Vec<Filter>,
+    text: String,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        Self::parse_at(input, timestamp::now())
+    }
+
+    fn parse_at(input: &str, now: u64) -> Result<Self, QueryError> {
+        let mut query = Query::default();
+        let mut text: Vec<String> = Vec::new();
+
+        for token in tokenize(input)? {
+            match token.key {
+                None if !token.negated => text.push(token.value),
+                None => {
+                    query.filters.push(Filter {
+                        negated: true,
+                        field: Field::Any,
+                        predicate: Predicate::Pattern(token.value.to_lowercase()),
+                    });
+                }
+                Some(key) => {
+                    let field = Field::from_name(&key).ok_or_else(|| {
+                        QueryError::new(
+                            token.start,
+                            format!("unknown field `{key}`, expected one of {FIELDS}")
+                        )
+                    })?;
+
+                    if token.value.is_empty() {
+                        return Err(QueryError::new(token.value_start, format!("missing value for `{key}`")));
+                    }
+
+                    let predicate = match field {
+                        Field::Modified | Field::Created => {
+                            parse_date_predicate(&token.value, token.value_start, now)?
+                        }
+                        _ => Predicate::Pattern(token.value.to_lowercase()),
+                    };
+
+                    query.filters.push(Filter { negated: token.negated, field, predicate });
+                }
+            }
+        }
+
+        query.text = text.join(" ");
+        Ok(query)
+    }
+
+    /// Whether `entry` passes every field filter. Free text is not considered.
+    pub fn matches(&self, entry: &VaultEntry) -> bool {
+        self.filters.iter().all(|filter| filter.matches(entry) != filter.negated)
+    }
+
+    /// Entries passing the filters, ranked by fuzzy matching of the free text.
+    pub fn find(&self, entries: &[VaultEntry]) -> Vec<SearchResult> {
+        let candidates = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| self.matches(entry))
+            .map(|(index, _)| index);
+
+        search::rank(entries, candidates, &self.text)
+    }
+}
+
+impl Filter {
+    fn matches(&self, entry: &VaultEntry) -> bool {
+        match &self.predicate {
+            Predicate::Date(comparison, bound) => {
+                let value = if self.field == Field::Created { entry.created } else { entry.modified };
+                // Entries imported without timestamps never satisfy a date filter.
+                value != 0 && comparison.holds(value, *bound)
+            }
+            Predicate::Pattern(pattern) => {
+                match self.field {
+                    Field::Service => matches_text(pattern, &entry.service),
+                    Field::Username => entry.username.as_deref().is_some_and(|u| matches_text(pattern, u)),
+                    Field::Url => {
+                        entry.url.as_deref().is_some_and(|u| matches_text(pattern, u)) ||
+                            entry.host().is_some_and(|h| matches_whole(pattern, &h))
+                    }
+                    Field::Notes => entry.notes.as_deref().is_some_and(|n| matches_text(pattern, n)),
+                    Field::Tag => entry.tags.iter().any(|t| matches_whole(pattern, t)),
+                    Field::Folder => entry.folder.as_deref().is_some_and(|f| matches_folder(pattern, f)),
+                    Field::Any => {
+                        matches_text(pattern, &entry.service) ||
+                            entry.username.as_deref().is_some_and(|u| matches_text(pattern, u)) ||
+                            entry.url.as_deref().is_some_and(|u| matches_text(pattern, u)) ||
+                            entry.notes.as_deref().is_some_and(|n| matches_text(pattern, n)) ||
+                            entry.tags.iter().any(|t| matches_text(pattern, t))
+                    }
+                    Field::Modified | Field::Created => false,
+                }
+            }
+        }
+    }
+}
+
+fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+fn matches_text(pattern: &str, value: &str) -> bool {
+    let value = value.to_lowercase();
+
+    if has_wildcards(pattern) {
+        glob(pattern, &value)
+    } else {
+        value.contains(pattern)
+    }
+}
+
+fn matches_whole(pattern: &str, value: &str) -> bool {
+    let value = value.to_lowercase();
+
+    if has_wildcards(pattern) { glob(pattern, &value) } else { value == pattern }
+}
+
+/// A folder filter also selects the folders nested below it.
+fn matches_folder(pattern: &str, folder: &str) -> bool {
+    let folder = folder.trim_matches('/');
+    let pattern = pattern.trim_matches('/');
+
+    matches_whole(pattern, folder) ||
+        folder
+            .match_indices('/')
+            .any(|(i, _)| matches_whole(pattern, &folder[..i]))
+}
+
+/// Matches `value` against a pattern where `*` is any run of chars and `?` one char.
+fn glob(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            v = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn parse_date_predicate(value: &str, start: usize, now: u64) -> Result<Predicate, QueryError> {
+    let (comparison, operand) = if let Some(rest) = value.strip_prefix("<=") {
+        (Comparison::LessOrEqual, rest)
+    } else if let Some(rest) = value.strip_prefix(">=") {
+        (Comparison::GreaterOrEqual, rest)
+    } else if let Some(rest) = value.strip_prefix('<') {
+        (Comparison::Less, rest)
+    } else if let Some(rest) = value.strip_prefix('>') {
+        (Comparison::Greater, rest)
+    } else {
+        return Err(QueryError::new(start, format!("expected `<`, `<=`, `>` or `>=` before `{value}`")));
+    };
+
+    let operand_start = start + (value.len() - operand.len());
+
+    if let Some(date) = timestamp::parse_date(operand) {
+        return Ok(Predicate::Date(comparison, date));
+    }
+
+    if let Some(age) = parse_age(operand) {
+        return Ok(Predicate::Date(comparison.flipped(), now.saturating_sub(age)));
+    }
+
+    let message = if operand.is_empty() {
+        "expected an age like `90d` or a date like `2024-01-31`".to_string()
+    } else {
+        format!("invalid age or date `{operand}`, expected e.g. `90d`, `6w`, `3m`, `1y` or `2024-01-31`")
+    };
+
+    Err(QueryError::new(operand_start, message))
+}
+
+fn parse_age(value: &str) -> Option<u64> {
+    let unit = value.chars().last()?;
+    let amount: u64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+
+    let days = match unit {
+        'd' => 1,
+        'w' => 7,
+        'm' => 30,
+        'y' => 365,
+        _ => {
+            return None;
+        }
+    };
+
+    amount.checked_mul(days * timestamp::DAY)
+}
+
+#[derive(Debug)]
+struct Token {
+    start: usize,
+    negated: bool,
+    key: Option<String>,
+    value: String,
+    value_start: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut token = Token {
+            start,
+            negated: false,
+            key: None,
+            value: String::new(),
+            value_start: start,
+        };
+
+        if c == '-' {
+            chars.next();
+            match chars.peek() {
+                Some(&(_, next)) if !next.is_whitespace() => {
+                    token.negated = true;
+                    token.value_start = start + 1;
+                }
+                // A lone `-` is plain text.
+                _ => {
+                    token.value.push('-');
+                    tokens.push(token);
+                    continue;
+                }
+            }
+        }
+
+        let mut quote_start: Option<usize> = None;
+
+        while let Some(&(i, c)) = chars.peek() {
+            if quote_start.is_none() && c.is_whitespace() {
+                break;
+            }
+            chars.next();
+
+            match c {
+                '"' if quote_start.is_some() => quote_start = None,
+                '"' => quote_start = Some(i),
+                ':' if quote_start.is_none() && token.key.is_none() && !token.value.is_empty() => {
+                    token.key = Some(std::mem::take(&mut token.value));
+                    token.value_start = i + 1;
+                }
+                _ => token.value.push(c),
+            }
+        }
+
+        if let Some(position) = quote_start {
+            return Err(QueryError::new(position, "unterminated quote"));
+        }
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use serde::{ Deserialize, Serialize };
+
+use crate::{ timestamp, vault_entry::VaultEntry };
+
+/// Days after which a password is due for rotation when no tag policy applies.
+pub const DEFAULT_ROTATION_DAYS: u64 = 365;
+
+fn default_rotation_days() -> u64 {
+    DEFAULT_ROTATION_DAYS
+}
+
+/// How old passwords may get before they are due for rotation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RotationPolicy {
+    #[serde(default = "default_rotation_days")]
+    pub default_days: u64,
+    /// Maximum ages for entries with a tag, e.g. `{"critical": 90}`. The shortest one of
+    /// the tags of an entry applies.
+    #[serde(default)]
+    pub by_tag: HashMap<String, u64>,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        Self {
+            default_days: DEFAULT_ROTATION_DAYS,
+            by_tag: HashMap::new(),
+        }
+    }
+}
+
+impl RotationPolicy {
+    pub fn max_age_days(&self, entry: &VaultEntry) -> u64 {
+        entry.tags
+            .iter()
+            .filter_map(|tag| self.by_tag.get(tag))
+            .min()
+            .copied()
+            .unwrap_or(self.default_days)
+    }
+}
+
+/// An entry whose password is too old or whose rotation is waiting for confirmation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DueEntry {
+    pub index: usize,
+    /// Days since the password was set, `None` when unknown.
+    pub age_days: Option<u64>,
+    pub max_age_days: u64,
+    pub pending: bool,
+}
+
+impl DueEntry {
+    pub fn describe(&self) -> String {
+        let age = match self.age_days {
+            Some(days) => format!("{days} days old, rotate every {}", self.max_age_days),
+            None => "age unknown".to_string(),
+        };
+
+        if self.pending { format!("{age}, new password waiting for confirmation") } else { age }
+    }
+}
+
+/// Entries with a pending rotation first, then the overdue ones, oldest first.
+pub fn due_entries(entries: &[VaultEntry], policy: &RotationPolicy) -> Vec<DueEntry> {
+    let now = timestamp::now();
+
+    let mut due: Vec<DueEntry> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let changed = entry.password_changed();
+            let age_days = (changed != 0).then(|| now.saturating_sub(changed) / timestamp::DAY);
+            let max_age_days = policy.max_age_days(entry);
+            let pending = entry.pending_rotation.is_some();
+
+            let overdue = age_days.is_some_and(|days| days > max_age_days);
+            (overdue || pending).then_some(DueEntry { index, age_days, max_age_days, pending })
+        })
+        .collect();
+
+    due.sort_by_key(|d| (!d.pending, std::cmp::Reverse(d.age_days.map(|days| days.saturating_sub(d.max_age_days)))));
+    due
+}
@@ -0,0 +1,240 @@
+use crate::vault_entry::VaultEntry;
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 12;
+const BONUS_WORD_START: i64 = 8;
+const PENALTY_GAP: i64 = 1;
+const PENALTY_LEADING_MAX: i64 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    Service,
+    Username,
+    Url,
+    Tag(usize),
+    Notes,
+}
+
+impl SearchField {
+    /// Matches in the fields users usually type from rank above matches buried in notes.
+    fn bonus(&self) -> i64 {
+        match self {
+            SearchField::Service => 30,
+            SearchField::Username => 20,
+            SearchField::Url => 15,
+            SearchField::Tag(_) => 10,
+            SearchField::Notes => 0,
+        }
+    }
+}
+
+/// Characters of a field that matched the query, as char indices into the field value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMatch {
+    pub field: SearchField,
+    pub positions: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// Index of the entry in `Vault::get_entries`.
+    pub index: usize,
+    pub score: i64,
+    pub matches: Vec<FieldMatch>,
+}
+
+impl SearchResult {
+    pub fn positions(&self, field: SearchField) -> &[usize] {
+        self.matches
+            .iter()
+            .find(|m| m.field == field)
+            .map_or(&[], |m| m.positions.as_slice())
+    }
+}
+
+/// Ranks the entries at the `candidates` indices against `query`, best match first.
+///
+/// The query is split on whitespace and every term has to fuzzy match at least one
+/// field of an entry. An empty query keeps every candidate, in the given order.
+pub fn rank(
+    entries: &[VaultEntry],
+    candidates: impl IntoIterator<Item = usize>,
+    query: &str
+) -> Vec<SearchResult> {
+    let terms: Vec<&str> = query.split_whitespace().collect();
+
+    if terms.is_empty() {
+        return candidates
+            .into_iter()
+            .map(|index| SearchResult { index, score: 0, matches: Vec::new() })
+            .collect();
+    }
+
+    let mut results: Vec<SearchResult> = candidates
+        .into_iter()
+        .filter_map(|index| {
+            let (score, matches) = score_entry(&entries[index], &terms)?;
+            Some(SearchResult { index, score, matches })
+        })
+        .collect();
+
+    results.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| entries[a.index].service.cmp(&entries[b.index].service))
+            .then_with(|| a.index.cmp(&b.index))
+    });
+
+    results
+}
+
+fn fields(entry: &VaultEntry) -> Vec<(SearchField, &str)> {
+    let mut fields = vec![(SearchField::Service, entry.service.as_str())];
+
+    if let Some(username) = &entry.username {
+        fields.push((SearchField::Username, username.as_str()));
+    }
+    if let Some(url) = &entry.url {
+        fields.push((SearchField::Url, url.as_str()));
+    }
+    for (i, tag) in entry.tags.iter().enumerate() {
+        fields.push((SearchField::Tag(i), tag.as_str()));
+    }
+    if let Some(notes) = &entry.notes {
+        fields.push((SearchField::Notes, notes.as_str()));
+    }
+
+    fields
+}
+
+fn score_entry(entry: &VaultEntry, terms: &[&str]) -> Option<(i64, Vec<FieldMatch>)> {
+    let fields = fields(entry);
+    let mut total = 0;
+    let mut matches: Vec<FieldMatch> = Vec::new();
+
+    for term in terms {
+        let mut best: Option<i64> = None;
+
+        for (field, value) in &fields {
+            let Some((score, positions)) = fuzzy_match(term, value) else {
+                continue;
+            };
+
+            let score = score + field.bonus();
+            best = Some(best.map_or(score, |b| b.max(score)));
+
+            match matches.iter_mut().find(|m| m.field == *field) {
+                Some(existing) => {
+                    existing.positions.extend(positions);
+                    existing.positions.sort_unstable();
+                    existing.positions.dedup();
+                }
+                None => matches.push(FieldMatch { field: *field, positions }),
+            }
+        }
+
+        total += best?;
+    }
+
+    Some((total, matches))
+}
+
+/// Case-insensitive subsequence match of `pattern` in `text`.
+///
+/// Returns the score of the best alignment and the char indices of `text` it uses.
+/// Consecutive characters and characters starting a word score higher, gaps between
+/// matched characters cost a little.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<char> = pattern.chars().map(fold_case).collect();
+    let original: Vec<char> = text.chars().collect();
+    let text: Vec<char> = original.iter().copied().map(fold_case).collect();
+
+    if pattern.is_empty() || pattern.len() > text.len() {
+        return None;
+    }
+
+    let n = text.len();
+    let bonus: Vec<i64> = (0..n).map(|j| word_start_bonus(&original, j)).collect();
+
+    // scores[i][j]: best score with pattern[i] matched at text[j].
+    // parents[i][j]: where pattern[i - 1] was matched for that score.
+    let mut scores: Vec<Vec<Option<i64>>> = vec![vec![None; n]; pattern.len()];
+    let mut parents: Vec<Vec<usize>> = vec![vec![0; n]; pattern.len()];
+
+    for j in 0..n {
+        if text[j] == pattern[0] {
+            let leading = (j as i64).min(PENALTY_LEADING_MAX);
+            scores[0][j] = Some(SCORE_MATCH + bonus[j] - leading);
+        }
+    }
+
+    for i in 1..pattern.len() {
+        // Best `scores[i - 1][k] + k` for k < j - 1, so the linear gap penalty can be
+        // applied without rescanning the previous row.
+        let mut best_gapped: Option<(i64, usize)> = None;
+
+        for j in i..n {
+            if j >= 2 && let Some(score) = scores[i - 1][j - 2] {
+                let candidate = score + (j as i64 - 2) * PENALTY_GAP;
+                if best_gapped.is_none_or(|(best, _)| candidate > best) {
+                    best_gapped = Some((candidate, j - 2));
+                }
+            }
+
+            if text[j] != pattern[i] {
+                continue;
+            }
+
+            let consecutive = scores[i - 1][j - 1].map(|s| (s + BONUS_CONSECUTIVE, j - 1));
+            let gapped = best_gapped.map(|(s, k)| (s - (j as i64 - 1) * PENALTY_GAP, k));
+
+            let best = match (consecutive, gapped) {
+                (Some(c), Some(g)) => Some(if g.0 > c.0 { g } else { c }),
+                (c, g) => c.or(g),
+            };
+
+            if let Some((score, parent)) = best {
+                scores[i][j] = Some(score + SCORE_MATCH + bonus[j]);
+                parents[i][j] = parent;
+            }
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (mut j, score) = scores[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by_key(|&(j, s)| (s, std::cmp::Reverse(j)))?;
+
+    let mut positions = vec![0; pattern.len()];
+    for i in (0..pattern.len()).rev() {
+        positions[i] = j;
+        j = parents[i][j];
+    }
+
+    Some((score, positions))
+}
+
+/// Lowercases one char into one char, so indices in the folded text match the original.
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn word_start_bonus(text: &[char], j: usize) -> i64 {
+    if j == 0 {
+        return BONUS_WORD_START;
+    }
+
+    let previous = text[j - 1];
+    let current = text[j];
+
+    if
+        (!previous.is_alphanumeric() && current.is_alphanumeric()) ||
+        (previous.is_lowercase() && current.is_uppercase())
+    {
+        return BONUS_WORD_START;
+    }
+
+    0
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use serde::Serialize;
+use strum::{ Display, EnumIter, IntoEnumIterator };
+
+use crate::{ plaintext_export::PlaintextExport, strength, timestamp, vault_entry::VaultEntry };
+
+/// Services known to offer two-factor authentication, matched against the host of an
+/// entry's URL, or without one its service name. Not exhaustive, it covers widely used
+/// services.
+const TWO_FACTOR_DOMAINS: [&str; 60] = [
+    "google.com",
+    "gmail.com",
+    "youtube.com",
+    "microsoft.com",
+    "live.com",
+    "outlook.com",
+    "office.com",
+    "apple.com",
+    "icloud.com",
+    "amazon.com",
+    "aws.amazon.com",
+    "facebook.com",
+    "instagram.com",
+    "whatsapp.com",
+    "twitter.com",
+    "x.com",
+    "linkedin.com",
+    "github.com",
+    "gitlab.com",
+    "bitbucket.org",
+    "dropbox.com",
+    "box.com",
+    "slack.com",
+    "discord.com",
+    "zoom.us",
+    "reddit.com",
+    "twitch.tv",
+    "steampowered.com",
+    "epicgames.com",
+    "playstation.com",
+    "xbox.com",
+    "nintendo.com",
+    "paypal.com",
+    "stripe.com",
+    "coinbase.com",
+    "binance.com",
+    "kraken.com",
+    "wise.com",
+    "revolut.com",
+    "ebay.com",
+    "shopify.com",
+    "cloudflare.com",
+    "digitalocean.com",
+    "heroku.com",
+    "azure.com",
+    "npmjs.com",
+    "pypi.org",
+    "crates.io",
+    "docker.com",
+    "atlassian.com",
+    "notion.so",
+    "figma.com",
+    "salesforce.com",
+    "proton.me",
+    "protonmail.com",
+    "fastmail.com",
+    "yahoo.com",
+    "namecheap.com",
+    "godaddy.com",
+    "tiktok.com",
+];
+
+/// Tags marking an entry whose account has two-factor authentication turned on.
+const TWO_FACTOR_TAGS: [&str; 4] = ["2fa", "mfa", "totp", "otp"];
+
+/// Default strength score below which a password is reported as weak.
+pub const DEFAULT_MIN_SCORE: u8 = 3;
+/// Default age in days after which an unchanged password is reported.
+pub const DEFAULT_MAX_AGE_DAYS: u64 = 365;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReportSettings {
+    pub min_score: u8,
+    pub max_age_days: u64,
+}
+
+impl Default for ReportSettings {
+    fn default() -> Self {
+        Self {
+            min_score: DEFAULT_MIN_SCORE,
+            max_age_days: DEFAULT_MAX_AGE_DAYS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumIter, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    #[strum(to_string = "Reused password")]
+    Reused,
+    #[strum(to_string = "Weak password")]
+    Weak,
+    #[strum(to_string = "Old password")]
+    Old,
+    #[strum(to_string = "Missing 2FA")]
+    MissingTwoFactor,
+    #[strum(to_string = "Duplicate entry")]
+    Duplicate,
+    #[strum(to_string = "Plaintext export")]
+    PlaintextExport,
+}
+
+/// An entry as named in a report, without its password.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryRef {
+    pub id: String,
+    pub service: String,
+    pub username: Option<String>,
+}
+
+impl EntryRef {
+    fn new(entry: &VaultEntry) -> Self {
+        Self {
+            id: entry.id.clone(),
+            service: entry.service.clone(),
+            username: entry.username.clone(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match &self.username {
+            Some(username) => format!("{} ({username})", self.service),
+            None => self.service.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub kind: FindingKind,
+    /// The entries concerned, several for reused passwords and duplicates, none for
+    /// plaintext exports.
+    pub entries: Vec<EntryRef>,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityReport {
+    pub generated: u64,
+    pub entries: usize,
+    pub min_score: u8,
+    pub max_age_days: u64,
+    pub findings: Vec<Finding>,
+}
+
+/// Whether the entry belongs to a service known to offer two-factor authentication: its
+/// host is one of the domains or a subdomain of one, or it has no URL and its service is
+/// named after one of the domains, e.g. `github.com`.
+fn supports_two_factor(entry: &VaultEntry) -> bool {
+    match entry.host() {
+        Some(host) => TWO_FACTOR_DOMAINS
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{domain}"))),
+        None => TWO_FACTOR_DOMAINS.contains(&entry.service.trim().to_lowercase().as_str()),
+    }
+}
+
+/// Whether the entry holds a TOTP secret or is tagged as using two-factor authentication.
+fn has_two_factor(entry: &VaultEntry) -> bool {
+    entry.totp.is_some() || entry.tags.iter().any(|tag| TWO_FACTOR_TAGS.contains(&tag.to_lowercase().as_str()))
+}
+
+/// Groups of at least two entries sharing a key, in the order of their first entry.
+fn groups<'a, K: std::hash::Hash + Eq>(
+    entries: &'a [VaultEntry],
+    key: impl Fn(&'a VaultEntry) -> Option<K>
+) -> Vec<Vec<&'a VaultEntry>> {
+    let mut indexes: HashMap<K, usize> = HashMap::new();
+    let mut groups: Vec<Vec<&'a VaultEntry>> = Vec::new();
+
+    for entry in entries {
+        let Some(key) = key(entry) else {
+            continue;
+        };
+
+        match indexes.get(&key) {
+            Some(&index) => groups[index].push(entry),
+            None => {
+                indexes.insert(key, groups.len());
+                groups.push(vec![entry]);
+            }
+        }
+    }
+
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+impl SecurityReport {
+    /// Checks every entry. Passwords are compared and estimated in memory only, the
+    /// report never contains them.
+    pub fn build(entries: &[VaultEntry], settings: ReportSettings) -> Self {
+        let now = timestamp::now();
+        let mut findings = Vec::new();
+
+        for group in groups(entries, |e| Some(e.password.as_slice()).filter(|p| !p.is_empty())) {
+            findings.push(Finding {
+                kind: FindingKind::Reused,
+                detail: format!("the same password is used by {} entries", group.len()),
+                entries: group.into_iter().map(EntryRef::new).collect(),
+            });
+        }
+
+        for entry in entries {
+            let password = String::from_utf8_lossy(&entry.password);
+            if password.is_empty() {
+                continue;
+            }
+
+            let inputs = [
+                entry.service.as_str(),
+                entry.username.as_deref().unwrap_or(""),
+                entry.url.as_deref().unwrap_or(""),
+            ];
+            let estimate = strength::estimate(&password, &inputs);
+
+            if estimate.score < settings.min_score {
+                let mut detail = format!("scored {}/4 ({})", estimate.score, estimate.label());
+                if let Some(warning) = &estimate.warning {
+                    detail.push_str(&format!(": {warning}"));
+                }
+
+                findings.push(Finding { kind: FindingKind::Weak, entries: vec![EntryRef::new(entry)], detail });
+            }
+        }
+
+        for entry in entries {
+            let changed = entry.password_changed();
+            if changed == 0 {
+                continue;
+            }
+
+            let days = now.saturating_sub(changed) / timestamp::DAY;
+            if days > settings.max_age_days {
+                findings.push(Finding {
+                    kind: FindingKind::Old,
+                    entries: vec![EntryRef::new(entry)],
+                    detail: format!("not changed for {days} days, since {}", timestamp::format_date(changed)),
+                });
+            }
+        }
+
+        for entry in entries {
+            if supports_two_factor(entry) && !has_two_factor(entry) {
+                findings.push(Finding {
+                    kind: FindingKind::MissingTwoFactor,
+                    entries: vec![EntryRef::new(entry)],
+                    detail: "the service supports two-factor authentication, tag the entry 2fa once it is on".to_string(),
+                });
+            }
+        }
+
+        for group in groups(entries, |e| Some(e.account_key())) {
+            findings.push(Finding {
+                kind: FindingKind::Duplicate,
+                detail: format!("{} entries for the same account", group.len()),
+                entries: group.into_iter().map(EntryRef::new).collect(),
+            });
+        }
+
+        Self {
+            generated: now,
+            entries: entries.len(),
+            min_score: settings.min_score,
+            max_age_days: settings.max_age_days,
+            findings,
+        }
+    }
+
+    /// Adds a finding for every unencrypted export of the vault, as the file may still
+    /// be lying around.
+    pub fn add_plaintext_exports(&mut self, exports: &[PlaintextExport]) {
+        for export in exports {
+            let mut detail = format!(
+                "{} entries written unencrypted as {} to {} on {}",
+                export.entries,
+                export.format,
+                export.destination,
+                timestamp::format_date(export.time)
+            );
+            if export.destination != "stdout" && std::path::Path::new(&export.destination).exists() {
+                detail.push_str(", the file is still there");
+            }
+
+            self.findings.push(Finding { kind: FindingKind::PlaintextExport, entries: Vec::new(), detail });
+        }
+    }
+
+    pub fn count(&self, kind: FindingKind) -> usize {
+        self.findings.iter().filter(|f| f.kind == kind).count()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+
+        let _ = writeln!(markdown, "# Security report");
+        let _ = writeln!(markdown);
+        let _ = writeln!(
+            markdown,
+            "{} entries checked on {}, weak below a score of {}, old after {} days.",
+            self.entries,
+            timestamp::format_date(self.generated),
+            self.min_score,
+            self.max_age_days
+        );
+
+        for kind in FindingKind::iter() {
+            let findings: Vec<&Finding> = self.findings.iter().filter(|f| f.kind == kind).collect();
+
+            let _ = writeln!(markdown);
+            let _ = writeln!(markdown, "## {kind} ({})", findings.len());
+            let _ = writeln!(markdown);
+
+            if findings.is_empty() {
+                let _ = writeln!(markdown, "None.");
+            }
+            for finding in findings {
+                let names: Vec<String> = finding.entries.iter().map(EntryRef::label).collect();
+                if names.is_empty() {
+                    let _ = writeln!(markdown, "- {}", finding.detail);
+                } else {
+                    let _ = writeln!(markdown, "- {}: {}", names.join(", "), finding.detail);
+                }
+            }
+        }
+
+        markdown
+    }
+}
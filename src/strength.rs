@@ -0,0 +1,735 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::timestamp;
+
+/// Most common passwords first. Only the head of such a list for now: it is to be replaced
+/// by a frequency-ranked list of 10k to 100k passwords, e.g. from the SecLists project,
+/// without which rarer leaked passwords are scored as words or brute force.
+const PASSWORDS: &str = include_str!("dictionaries/passwords.txt");
+const ENGLISH: &str = include_str!("dictionaries/english.txt");
+const NAMES: &str = include_str!("dictionaries/names.txt");
+
+/// Only the start of very long passwords is analysed, the rest counts as random.
+const MAX_ANALYSED: usize = 100;
+const MAX_WORD_LENGTH: usize = 16;
+
+/// Guesses per character not covered by any pattern.
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_YEAR_SPACE: f64 = 20.0;
+
+const QWERTY: [&str; 4] = ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./"];
+const QWERTY_SHIFTED: [&str; 4] = ["~!@#$%^&*()_+", "QWERTYUIOP{}|", "ASDFGHJKL:\"", "ZXCVBNM<>?"];
+
+const LEET: [(char, &str); 10] = [
+    ('4', "a"),
+    ('@', "a"),
+    ('8', "b"),
+    ('3', "e"),
+    ('1', "il"),
+    ('!', "i"),
+    ('0', "o"),
+    ('$', "s"),
+    ('5', "s"),
+    ('7', "t"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dictionary {
+    Passwords,
+    English,
+    Names,
+    /// Words of the entry itself, such as its service or username.
+    UserInputs,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Dictionary {
+        dictionary: Dictionary,
+        rank: usize,
+        reversed: bool,
+        leet: bool,
+    },
+    Keyboard {
+        turns: usize,
+    },
+    Repeat,
+    Sequence,
+    Date,
+    Bruteforce,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    /// Char positions, `end` excluded.
+    pub start: usize,
+    pub end: usize,
+    pub pattern: Pattern,
+    pub guesses: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub guesses: f64,
+    /// From 0, too guessable, to 4, very unguessable.
+    pub score: u8,
+    /// Patterns making up the password, in order.
+    pub sequence: Vec<Match>,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+/// How fast an attacker can guess, to turn guesses into a crack time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackScenario {
+    /// Online service limiting attempts, 100 per hour.
+    OnlineThrottled,
+    /// Online service without limits, 10 per second.
+    OnlineUnthrottled,
+    /// Stolen hashes of a slow function such as Argon2, 10 thousand per second.
+    OfflineSlowHash,
+    /// Stolen hashes of a fast function, 10 billion per second.
+    OfflineFastHash,
+}
+
+impl AttackScenario {
+    fn guesses_per_second(self) -> f64 {
+        match self {
+            AttackScenario::OnlineThrottled => 100.0 / 3600.0,
+            AttackScenario::OnlineUnthrottled => 10.0,
+            AttackScenario::OfflineSlowHash => 1e4,
+            AttackScenario::OfflineFastHash => 1e10,
+        }
+    }
+}
+
+pub const SCORE_LABELS: [&str; 5] = ["very weak", "weak", "fair", "strong", "very strong"];
+
+impl Estimate {
+    pub fn crack_seconds(&self, scenario: AttackScenario) -> f64 {
+        self.guesses / scenario.guesses_per_second()
+    }
+
+    /// Crack time as a rough duration, e.g. `3 hours` or `centuries`.
+    pub fn crack_time(&self, scenario: AttackScenario) -> String {
+        format_duration(self.crack_seconds(scenario))
+    }
+
+    pub fn label(&self) -> &'static str {
+        SCORE_LABELS[self.score as usize]
+    }
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = 60.0 * MINUTE;
+    const DAY: f64 = 24.0 * HOUR;
+    const MONTH: f64 = 31.0 * DAY;
+    const YEAR: f64 = 12.0 * MONTH;
+    const CENTURY: f64 = 100.0 * YEAR;
+
+    let (amount, unit) = if seconds < 1.0 {
+        return "less than a second".to_string();
+    } else if seconds < MINUTE {
+        (seconds, "second")
+    } else if seconds < HOUR {
+        (seconds / MINUTE, "minute")
+    } else if seconds < DAY {
+        (seconds / HOUR, "hour")
+    } else if seconds < MONTH {
+        (seconds / DAY, "day")
+    } else if seconds < YEAR {
+        (seconds / MONTH, "month")
+    } else if seconds < CENTURY {
+        (seconds / YEAR, "year")
+    } else {
+        return "centuries".to_string();
+    };
+
+    let amount = amount.round() as u64;
+    if amount == 1 { format!("1 {unit}") } else { format!("{amount} {unit}s") }
+}
+
+/// Ranked dictionaries, word to rank starting at 1.
+fn dictionaries() -> &'static [(Dictionary, HashMap<&'static str, usize>)] {
+    static DICTIONARIES: OnceLock<Vec<(Dictionary, HashMap<&'static str, usize>)>> = OnceLock::new();
+
+    DICTIONARIES.get_or_init(|| {
+        // The common passwords are listed most frequent first. The other lists are not
+        // ranked, every word costs about half the list.
+        let ranked = |text: &'static str| -> HashMap<&'static str, usize> {
+            text.lines().enumerate().map(|(i, w)| (w, i + 1)).collect()
+        };
+        let flat = |text: &'static str| -> HashMap<&'static str, usize> {
+            let rank = text.lines().count() / 2;
+            text.lines().map(|w| (w, rank)).collect()
+        };
+
+        vec![
+            (Dictionary::Passwords, ranked(PASSWORDS)),
+            (Dictionary::Names, flat(NAMES)),
+            (Dictionary::English, flat(ENGLISH))
+        ]
+    })
+}
+
+/// Characters `c` may stand for in l33t speak, empty if none.
+fn unleet(c: char) -> &'static str {
+    LEET.iter().find(|(l, _)| *l == c).map_or("", |(_, letters)| letters)
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+
+    (1..=k).fold(1.0, |acc, i| (acc * ((n - k + i) as f64)) / (i as f64))
+}
+
+/// Extra guesses for the capitalisation of a word that is not all lowercase.
+fn uppercase_variations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+
+    if upper == 0 {
+        return 1.0;
+    }
+
+    // All uppercase, or only the first or last letter: the usual, cheap variations.
+    let first_only = upper == 1 && word.first().is_some_and(|c| c.is_uppercase());
+    let last_only = upper == 1 && word.last().is_some_and(|c| c.is_uppercase());
+    if lower == 0 || first_only || last_only {
+        return 2.0;
+    }
+
+    (1..=upper.min(lower)).map(|i| binomial(upper + lower, i)).sum()
+}
+
+fn dictionary_matches(chars: &[char], user_inputs: &HashMap<String, usize>, matches: &mut Vec<Match>) {
+    let lower: Vec<char> = chars.iter().flat_map(|c| c.to_lowercase()).collect();
+    if lower.len() != chars.len() {
+        return;
+    }
+
+    let lookup = |word: &str| -> Vec<(Dictionary, usize)> {
+        let mut found: Vec<(Dictionary, usize)> = dictionaries()
+            .iter()
+            .filter_map(|(dictionary, words)| words.get(word).map(|rank| (*dictionary, *rank)))
+            .collect();
+
+        if let Some(rank) = user_inputs.get(word) {
+            found.push((Dictionary::UserInputs, *rank));
+        }
+
+        found
+    };
+
+    for start in 0..chars.len() {
+        for end in start + 3..=chars.len().min(start + MAX_WORD_LENGTH) {
+            let original = &chars[start..end];
+            let word: String = lower[start..end].iter().collect();
+            let reversed: String = lower[start..end].iter().rev().collect();
+            let variations = uppercase_variations(original);
+
+            for (dictionary, rank) in lookup(&word) {
+                matches.push(Match {
+                    start,
+                    end,
+                    pattern: Pattern::Dictionary { dictionary, rank, reversed: false, leet: false },
+                    guesses: (rank as f64) * variations,
+                });
+            }
+
+            if reversed != word {
+                for (dictionary, rank) in lookup(&reversed) {
+                    matches.push(Match {
+                        start,
+                        end,
+                        pattern: Pattern::Dictionary { dictionary, rank, reversed: true, leet: false },
+                        guesses: (rank as f64) * variations * 2.0,
+                    });
+                }
+            }
+
+            // A single reading of the l33t characters, the first letter each stands for.
+            let substituted = original.iter().filter(|c| !unleet(**c).is_empty()).count();
+            if substituted > 0 && substituted < original.len() {
+                let unleeted: String = lower[start..end]
+                    .iter()
+                    .map(|c| unleet(*c).chars().next().unwrap_or(*c))
+                    .collect();
+
+                for (dictionary, rank) in lookup(&unleeted) {
+                    let unsubstituted = original.len() - substituted;
+                    let leet_variations: f64 = (1..=substituted.min(unsubstituted).max(1))
+                        .map(|i| binomial(original.len(), i))
+                        .sum();
+
+                    matches.push(Match {
+                        start,
+                        end,
+                        pattern: Pattern::Dictionary { dictionary, rank, reversed: false, leet: true },
+                        guesses: (rank as f64) * variations * leet_variations.max(2.0),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Row and column of `c` on a QWERTY keyboard, and whether it needs shift.
+fn key_position(c: char) -> Option<(usize, usize, bool)> {
+    for (shifted, rows) in [(false, QWERTY), (true, QWERTY_SHIFTED)] {
+        for (row, keys) in rows.iter().enumerate() {
+            if let Some(column) = keys.chars().position(|k| k == c) {
+                return Some((row, column, shifted));
+            }
+        }
+    }
+
+    None
+}
+
+/// Direction from one key to a neighbouring key, `None` if they are not adjacent.
+fn key_direction(from: char, to: char) -> Option<(isize, isize)> {
+    let (row_a, column_a, _) = key_position(from)?;
+    let (row_b, column_b, _) = key_position(to)?;
+    let direction = ((row_b as isize) - (row_a as isize), (column_b as isize) - (column_a as isize));
+
+    // Rows are staggered, the keys above are at the same or the next column.
+    let adjacent = match direction.0 {
+        0 => direction.1.abs() == 1,
+        -1 => direction.1 == 0 || direction.1 == 1,
+        1 => direction.1 == 0 || direction.1 == -1,
+        _ => false,
+    };
+
+    if adjacent { Some(direction) } else { None }
+}
+
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    const KEYS: f64 = 94.0;
+    const AVERAGE_DEGREE: f64 = 4.6;
+
+    let mut start = 0;
+    while start + 1 < chars.len() {
+        let mut end = start + 1;
+        let mut turns = 0;
+        let mut last_direction = None;
+
+        while end < chars.len() {
+            let Some(direction) = key_direction(chars[end - 1], chars[end]) else {
+                break;
+            };
+            if last_direction != Some(direction) {
+                turns += 1;
+                last_direction = Some(direction);
+            }
+            end += 1;
+        }
+
+        let length = end - start;
+        if length >= 3 {
+            let mut guesses = 0.0;
+            for i in 2..=length {
+                for j in 1..=turns.min(i - 1) {
+                    guesses += binomial(i - 1, j - 1) * KEYS * AVERAGE_DEGREE.powi(j as i32);
+                }
+            }
+
+            let shifted = chars[start..end].iter().filter(|c| key_position(**c).is_some_and(|k| k.2)).count();
+            if shifted > 0 {
+                guesses *= uppercase_variations(
+                    &chars[start..end]
+                        .iter()
+                        .map(|c| if key_position(*c).is_some_and(|k| k.2) { 'A' } else { 'a' })
+                        .collect::<Vec<_>>()
+                );
+            }
+
+            matches.push(Match { start, end, pattern: Pattern::Keyboard { turns }, guesses });
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+fn repeat_matches(chars: &[char], matches: &mut Vec<Match>) {
+    for start in 0..chars.len() {
+        for block in 1..=(chars.len() - start) / 2 {
+            let unit = &chars[start..start + block];
+            let mut end = start + block;
+            while end + block <= chars.len() && &chars[end..end + block] == unit {
+                end += block;
+            }
+
+            let count = (end - start) / block;
+            if count >= 2 && end - start >= 3 {
+                let unit_guesses = estimate_chars(unit, &HashMap::new()).guesses;
+                matches.push(Match {
+                    start,
+                    end,
+                    pattern: Pattern::Repeat,
+                    guesses: unit_guesses * (count as f64),
+                });
+            }
+        }
+    }
+}
+
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let delta = (chars[start + 1] as i64) - (chars[start] as i64);
+        let same_kind = |a: char, b: char| {
+            (a.is_ascii_lowercase() && b.is_ascii_lowercase()) ||
+                (a.is_ascii_uppercase() && b.is_ascii_uppercase()) ||
+                (a.is_ascii_digit() && b.is_ascii_digit())
+        };
+
+        let mut end = start + 1;
+        while
+            end < chars.len() &&
+            delta != 0 &&
+            delta.abs() <= 5 &&
+            same_kind(chars[start], chars[end]) &&
+            (chars[end] as i64) - (chars[end - 1] as i64) == delta
+        {
+            end += 1;
+        }
+
+        if end - start >= 3 {
+            let first = chars[start];
+            let base = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let direction = if delta < 0 { 2.0 } else { 1.0 };
+
+            matches.push(Match {
+                start,
+                end,
+                pattern: Pattern::Sequence,
+                guesses: base * ((end - start) as f64) * direction * (delta.abs() as f64),
+            });
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+fn year_space(year: i64) -> f64 {
+    let current_year = timestamp::format_date(timestamp::now())[..4].parse::<i64>().unwrap_or(2025);
+    ((year - current_year).abs() as f64).max(MIN_YEAR_SPACE)
+}
+
+/// Expands a two digit year the way people write them.
+fn full_year(year: i64) -> i64 {
+    match year {
+        0..=50 => 2000 + year,
+        51..=99 => 1900 + year,
+        _ => year,
+    }
+}
+
+fn is_date(day: i64, month: i64, year: i64) -> bool {
+    (1..=31).contains(&day) && (1..=12).contains(&month) && (1900..=2099).contains(&year)
+}
+
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    // Years alone.
+    for start in 0..chars.len().saturating_sub(3) {
+        let text: String = chars[start..start + 4].iter().collect();
+        if let Ok(year) = text.parse::<i64>() && (1900..=2099).contains(&year) {
+            matches.push(Match { start, end: start + 4, pattern: Pattern::Date, guesses: year_space(year) });
+        }
+    }
+
+    // Day, month and year, in any order, with or without one separator.
+    for start in 0..chars.len() {
+        for end in start + 4..=chars.len().min(start + 10) {
+            let token: String = chars[start..end].iter().collect();
+            let separator = token.chars().find(|c| " -/._\\".contains(*c));
+
+            let number = |text: &str| -> Option<i64> {
+                if text.is_empty() || !text.chars().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                text.parse().ok()
+            };
+
+            // (day, month, year) readings of the token, in any order people write dates.
+            let mut readings = Vec::new();
+            match separator {
+                Some(s) => {
+                    let parts: Vec<Option<i64>> = token.split(s).map(number).collect();
+                    if let [Some(a), Some(b), Some(c)] = parts[..] {
+                        readings.extend([(a, b, c), (b, a, c), (c, b, a)]);
+                    }
+                }
+                None if number(&token).is_some() && (6..=8).contains(&token.len()) => {
+                    // The year either last or first, four or two digits.
+                    let year_length = if token.len() == 6 { 2 } else { 4 };
+                    for year_first in [false, true] {
+                        let (year, rest) = if year_first {
+                            token.split_at(year_length)
+                        } else {
+                            let (rest, year) = token.split_at(token.len() - year_length);
+                            (year, rest)
+                        };
+                        if rest.len() % 2 != 0 {
+                            continue;
+                        }
+                        let (a, b) = rest.split_at(rest.len() / 2);
+                        if let (Some(a), Some(b), Some(year)) = (number(a), number(b), number(year)) {
+                            readings.extend([(a, b, year), (b, a, year)]);
+                        }
+                    }
+                }
+                None => {}
+            }
+
+            if let Some((_, _, year)) = readings
+                .iter()
+                .map(|(d, m, y)| (*d, *m, full_year(*y)))
+                .find(|(d, m, y)| is_date(*d, *m, *y))
+            {
+                let mut guesses = year_space(year) * 365.0;
+                if separator.is_some() {
+                    guesses *= 4.0;
+                }
+                matches.push(Match { start, end, pattern: Pattern::Date, guesses });
+            }
+        }
+    }
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).map(|i| i as f64).product()
+}
+
+/// Cheapest way to cover `chars` with the matches, bruteforcing the gaps.
+fn most_guessable(chars: &[char], matches: &[Match]) -> (f64, Vec<Match>) {
+    let n = chars.len();
+    if n == 0 {
+        return (1.0, Vec::new());
+    }
+
+    // best[k][l]: lowest product of guesses covering the first k chars with l matches,
+    // with the match ending at k.
+    let mut best: Vec<HashMap<usize, (f64, Match)>> = vec![HashMap::new(); n + 1];
+    let mut by_end: Vec<Vec<&Match>> = vec![Vec::new(); n + 1];
+    for m in matches {
+        by_end[m.end].push(m);
+    }
+
+    let bruteforce = |start: usize, end: usize| Match {
+        start,
+        end,
+        pattern: Pattern::Bruteforce,
+        guesses: BRUTEFORCE_CARDINALITY.powi((end - start) as i32).max(if end - start == 1 { 11.0 } else { 51.0 }),
+    };
+
+    for end in 1..=n {
+        let mut candidates: Vec<Match> = by_end[end].iter().map(|m| (*m).clone()).collect();
+        for start in 0..end {
+            candidates.push(bruteforce(start, end));
+        }
+
+        for candidate in candidates {
+            let previous: Vec<(usize, f64)> = if candidate.start == 0 {
+                vec![(0, 1.0)]
+            } else {
+                best[candidate.start]
+                    .iter()
+                    .filter(|(_, (_, m))| {
+                        // Two bruteforce runs in a row are one longer run.
+                        !(m.pattern == Pattern::Bruteforce && candidate.pattern == Pattern::Bruteforce)
+                    })
+                    .map(|(l, (product, _))| (*l, *product))
+                    .collect()
+            };
+
+            for (l, product) in previous {
+                let product = product * candidate.guesses;
+                let current = best[end].get(&(l + 1));
+                let total = |p: f64| factorial(l + 1) * p;
+                if current.is_none_or(|(p, _)| total(product) < total(*p)) {
+                    best[end].insert(l + 1, (product, candidate.clone()));
+                }
+            }
+        }
+    }
+
+    let (length, (product, _)) = best[n]
+        .iter()
+        .min_by(|a, b| {
+            let total_a = factorial(*a.0) * a.1.0;
+            let total_b = factorial(*b.0) * b.1.0;
+            total_a.total_cmp(&total_b)
+        })
+        .expect("every position can be bruteforced");
+    let guesses = factorial(*length) * product;
+
+    // Walk back through the matches that led to the best total.
+    let mut sequence = Vec::new();
+    let (mut k, mut l) = (n, *length);
+    while k > 0 {
+        let (_, m) = &best[k][&l];
+        sequence.push(m.clone());
+        k = m.start;
+        l -= 1;
+    }
+    sequence.reverse();
+
+    (guesses, sequence)
+}
+
+fn estimate_chars(chars: &[char], user_inputs: &HashMap<String, usize>) -> Estimate {
+    let (analysed, rest) = chars.split_at(chars.len().min(MAX_ANALYSED));
+
+    let mut matches = Vec::new();
+    dictionary_matches(analysed, user_inputs, &mut matches);
+    keyboard_matches(analysed, &mut matches);
+    sequence_matches(analysed, &mut matches);
+    date_matches(analysed, &mut matches);
+    if analysed.len() <= 32 {
+        repeat_matches(analysed, &mut matches);
+    }
+
+    let (mut guesses, sequence) = most_guessable(analysed, &matches);
+    guesses *= BRUTEFORCE_CARDINALITY.powi(rest.len() as i32);
+
+    let score = match guesses {
+        g if g < 1e3 + 5.0 => 0,
+        g if g < 1e6 + 5.0 => 1,
+        g if g < 1e8 + 5.0 => 2,
+        g if g < 1e10 + 5.0 => 3,
+        _ => 4,
+    };
+
+    let (warning, suggestions) = feedback(score, &sequence, chars);
+
+    Estimate { guesses, score, sequence, warning, suggestions }
+}
+
+fn feedback(score: u8, sequence: &[Match], chars: &[char]) -> (Option<String>, Vec<String>) {
+    if chars.is_empty() {
+        return (
+            Some("The password is empty".to_string()),
+            vec!["Use a few words, avoid common phrases".to_string()],
+        );
+    }
+
+    if score >= 3 {
+        return (None, Vec::new());
+    }
+
+    let mut suggestions = vec!["Add another word or two. Uncommon words are better.".to_string()];
+
+    // The longest pattern explains the most of the weakness.
+    let Some(worst) = sequence
+        .iter()
+        .filter(|m| m.pattern != Pattern::Bruteforce)
+        .max_by_key(|m| m.end - m.start)
+    else {
+        return (None, suggestions);
+    };
+
+    let whole = worst.end - worst.start == chars.len();
+    let warning = match &worst.pattern {
+        Pattern::Dictionary { dictionary, rank, reversed, leet } => {
+            let word = &chars[worst.start..worst.end];
+            if word.first().is_some_and(|c| c.is_uppercase()) {
+                suggestions.push("Capitalization doesn't help very much".to_string());
+            } else if word.iter().all(|c| !c.is_lowercase()) && word.iter().any(|c| c.is_uppercase()) {
+                suggestions.push("All-uppercase is almost as easy to guess as all-lowercase".to_string());
+            }
+            if *reversed {
+                suggestions.push("Reversed words aren't much harder to guess".to_string());
+            }
+            if *leet {
+                suggestions.push("Predictable substitutions like '@' instead of 'a' don't help very much".to_string());
+            }
+
+            match dictionary {
+                Dictionary::Passwords if whole && *rank <= 10 => "This is a top-10 common password",
+                Dictionary::Passwords if whole && *rank <= 100 => "This is a top-100 common password",
+                Dictionary::Passwords => "This is similar to a commonly used password",
+                Dictionary::English if whole => "A word by itself is easy to guess",
+                Dictionary::Names if whole => "Names and surnames by themselves are easy to guess",
+                Dictionary::Names => "Common names and surnames are easy to guess",
+                Dictionary::UserInputs => "Avoid the service or user name in the password",
+                Dictionary::English => "Common words are easy to guess",
+            }
+        }
+        Pattern::Keyboard { turns } => {
+            suggestions.push("Use a longer keyboard pattern with more turns".to_string());
+            if *turns == 1 { "Straight rows of keys are easy to guess" } else { "Short keyboard patterns are easy to guess" }
+        }
+        Pattern::Repeat => {
+            suggestions.push("Avoid repeated words and characters".to_string());
+            if worst.end - worst.start <= 3 || chars[worst.start..worst.end].iter().all(|c| *c == chars[worst.start]) {
+                "Repeats like \"aaa\" are easy to guess"
+            } else {
+                "Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\""
+            }
+        }
+        Pattern::Sequence => {
+            suggestions.push("Avoid sequences".to_string());
+            "Sequences like abc or 6543 are easy to guess"
+        }
+        Pattern::Date => {
+            suggestions.push("Avoid dates and years that are associated with you".to_string());
+            "Dates are often easy to guess"
+        }
+        Pattern::Bruteforce => unreachable!(),
+    };
+
+    (Some(warning.to_string()), suggestions)
+}
+
+/// Estimates how many guesses `password` takes to crack, in the spirit of zxcvbn: the
+/// password is split into the patterns an attacker would try (dictionary words, keyboard
+/// walks, repeats, sequences, dates) and the cheapest combination gives the guesses.
+///
+/// `user_inputs` are words an attacker
+/// would try first for this password, such as the service and username of an entry.
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Estimate {
+    let user_inputs: HashMap<String, usize> = user_inputs
+        .iter()
+        .flat_map(|input| input.split(|c: char| !c.is_alphanumeric()))
+        .map(str::to_lowercase)
+        .filter(|word| word.chars().count() >= 3)
+        .enumerate()
+        .map(|(i, word)| (word, i + 1))
+        .collect();
+
+    let chars: Vec<char> = password.chars().collect();
+    estimate_chars(&chars, &user_inputs)
+}
+
+/// Checks a new master password scores at least `min_score`, with the reason otherwise.
+pub fn check_master_password(password: &str, min_score: u8) -> Result<Estimate, String> {
+    let estimate = estimate(password, &[]);
+
+    if estimate.score < min_score {
+        let mut message = format!(
+            "the master password is {}, at least {} is required",
+            estimate.label(),
+            SCORE_LABELS[(min_score as usize).min(4)]
+        );
+        if let Some(warning) = &estimate.warning {
+            message.push_str(&format!(": {warning}"));
+        }
+        return Err(message);
+    }
+
+    Ok(estimate)
+}
@@ -0,0 +1,187 @@
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+pub const DAY: u64 = 86_400;
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Parses a `YYYY-MM-DD` date as midnight UTC.
+pub fn parse_date(value: &str) -> Option<u64> {
+    let mut parts = value.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    u64::try_from(days).ok().map(|d| d * DAY)
+}
+
+/// Parses an RFC 3339 date and time such as `2024-01-31T10:20:30.123Z` or
+/// `2024-01-31 10:20:30+02:00`. Offsets may also be written `+02` or `+0200`. Fractions
+/// of seconds are dropped, a missing offset is UTC.
+pub fn parse_datetime(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if value.len() < 10 || !value.is_char_boundary(10) {
+        return None;
+    }
+
+    let (date, rest) = value.split_at(10);
+    let date = parse_date(date)?;
+    let Some(time) = rest.strip_prefix(['T', 't', ' ']) else {
+        return rest.is_empty().then_some(date);
+    };
+
+    let offset_start = time.find(['Z', 'z', '+', '-']).unwrap_or(time.len());
+    let (time, offset) = time.split_at(offset_start);
+
+    let mut parts = time.split(':');
+    let hours: u64 = parts.next()?.parse().ok()?;
+    let minutes: u64 = parts.next()?.parse().ok()?;
+    let seconds: u64 = match parts.next() {
+        Some(seconds) => seconds.split('.').next()?.parse().ok()?,
+        None => 0,
+    };
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    let local = date + hours * 3600 + minutes * 60 + seconds;
+    let offset = match offset {
+        "" | "Z" | "z" => 0,
+        offset => {
+            let (sign, offset) = offset.split_at(1);
+            let (hours, minutes) = match offset.split_once(':') {
+                Some((hours, minutes)) if minutes.len() == 2 => (hours, minutes),
+                Some(_) => return None,
+                None if offset.len() == 4 => offset.split_at(2),
+                None => (offset, "00"),
+            };
+            if hours.len() != 2 || !hours.bytes().chain(minutes.bytes()).all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+
+            let (hours, minutes): (i64, i64) = (hours.parse().ok()?, minutes.parse().ok()?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            match sign {
+                "+" => hours * 3600 + minutes * 60,
+                "-" => -(hours * 3600 + minutes * 60),
+                _ => return None,
+            }
+        }
+    };
+
+    u64::try_from(local as i64 - offset).ok()
+}
+
+/// Formats a timestamp as a `YYYY-MM-DD` UTC date.
+pub fn format_date(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / DAY) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Formats a timestamp as an RFC 3339 UTC date and time, e.g. `2024-01-31T10:20:30.000Z`.
+pub fn format_datetime(timestamp: u64) -> String {
+    let seconds = timestamp % DAY;
+    format!(
+        "{}T{:02}:{:02}:{:02}.000Z",
+        format_date(timestamp),
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between days since the epoch and proleptic Gregorian dates, after
+// Howard Hinnant's `days_from_civil` / `civil_from_days`.
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + (day as i64) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = year_of_era + era * 400 + (if month <= 2 { 1 } else { 0 });
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dates_and_times() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2024-02-29"), Some(1_709_164_800));
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_datetime("2024-01-31"), parse_date("2024-01-31"));
+        assert_eq!(parse_datetime("2024-01-31T10:20:30.123Z"), Some(1_706_696_430));
+        assert_eq!(parse_datetime("2024-01-31 10:20"), Some(1_706_696_400));
+        assert_eq!(parse_datetime("2024-01-31T24:00:00Z"), None);
+        assert_eq!(parse_datetime("2024-01-31X10:20:30Z"), None);
+    }
+
+    #[test]
+    fn applies_offsets_in_every_form() {
+        let utc = parse_datetime("2024-01-31T10:20:30Z");
+
+        for offset in ["+02", "+0200", "+02:00"] {
+            assert_eq!(parse_datetime(&format!("2024-01-31T12:20:30{offset}")), utc, "{offset}");
+        }
+        assert_eq!(parse_datetime("2024-01-31T08:50:30-0130"), utc);
+        assert_eq!(parse_datetime("2024-01-31T10:20:30-00:00"), utc);
+    }
+
+    #[test]
+    fn rejects_invalid_offsets() {
+        for offset in ["+24:00", "+23:60", "+2400", "+2", "+020", "+02:0", "+ab", "Zulu", "+02:00:00"] {
+            assert_eq!(parse_datetime(&format!("2024-01-31T12:20:30{offset}")), None, "{offset}");
+        }
+        assert!(parse_datetime("2024-01-31T12:20:30+23:59").is_some());
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let time = 1_706_696_430;
+        assert_eq!(format_date(time), "2024-01-31");
+        assert_eq!(format_datetime(time), "2024-01-31T10:20:30.000Z");
+        assert_eq!(parse_datetime(&format_datetime(time)), Some(time));
+    }
+}
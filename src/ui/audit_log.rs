@@ -0,0 +1,129 @@
+use crossterm::event::{ KeyCode, KeyModifiers };
+use ratatui::{
+    layout::{ Constraint, Layout },
+    style::Style,
+    text::Line,
+    widgets::{ Block, List, ListState, Paragraph, StatefulWidget, Widget },
+};
+
+use crate::{
+    app::{ App, FocusedWidget, Message },
+    audit_log::{ AuditReport, LoggedEvent },
+    timestamp,
+};
+
+#[derive(Debug)]
+pub struct AuditLogWidgetState {
+    /// Loaded when the tab is opened, `None` while no vault is unlocked.
+    pub report: Option<Result<AuditReport, String>>,
+    pub list_state: ListState,
+}
+
+impl AuditLogWidgetState {
+    pub fn new() -> Self {
+        Self {
+            report: None,
+            list_state: ListState::default().with_selected(Some(0)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AuditLogWidget;
+
+impl AuditLogWidget {
+    pub fn handle_key_event(
+        key_event: crossterm::event::KeyEvent,
+        _app_state: &App
+    ) -> Option<Message> {
+        match key_event.code {
+            KeyCode::Up if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Message::SetFocusedWidget(FocusedWidget::Top))
+            }
+            KeyCode::Down => Some(Message::SelectNextAuditEvent),
+            KeyCode::Up => Some(Message::SelectPreviousAuditEvent),
+            KeyCode::Char('r') => Some(Message::ReloadAuditLog),
+            KeyCode::Char('e') => Some(Message::ExportAuditLog),
+            _ => None,
+        }
+    }
+}
+
+fn event_line(logged: &LoggedEvent) -> String {
+    let event = &logged.event;
+    let mut line = format!(
+        "{:>5}  {}  {:<20} {:<13}",
+        logged.seq,
+        timestamp::format_date(event.time),
+        event.actor,
+        event.kind.to_string()
+    );
+
+    if let Some(entry) = &event.entry {
+        line.push_str(&format!(" {entry}"));
+    }
+    if let Some(detail) = &event.detail {
+        line.push_str(&format!(" ({detail})"));
+    }
+
+    line
+}
+
+impl StatefulWidget for AuditLogWidget {
+    type State = App;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State
+    )
+        where Self: Sized
+    {
+        let style = if state.focused_widget == FocusedWidget::Top {
+            Style::new()
+        } else {
+            Style::new().blue()
+        };
+
+        let block = Block::bordered().title("Audit log").border_style(style);
+
+        let report = match &state.audit_log_state.report {
+            None => {
+                Paragraph::new("Unlock a vault to see its audit log").block(block).render(area, buf);
+                return;
+            }
+            Some(Err(e)) => {
+                Paragraph::new(Line::styled(e.clone(), Style::new().red())).block(block).render(area, buf);
+                return;
+            }
+            Some(Ok(report)) => report,
+        };
+
+        let [problems_area, list_area] = Layout::vertical([
+            Constraint::Length(if report.is_intact() { 0 } else { (report.problems.len() as u16).min(5) + 2 }),
+            Constraint::Fill(1),
+        ]).areas(area);
+
+        if !report.is_intact() {
+            let lines: Vec<Line> = report.problems
+                .iter()
+                .map(|p| Line::styled(p.clone(), Style::new().red()))
+                .collect();
+
+            Paragraph::new(lines)
+                .block(Block::bordered().title("Integrity problems").border_style(Style::new().red()))
+                .render(problems_area, buf);
+        }
+
+        // Most recent first.
+        let list = List::new(report.events.iter().rev().map(event_line))
+            .block(
+                block
+                    .title_bottom(format!("{} events • r reload • e export", report.events.len()))
+            )
+            .highlight_style(Style::new().reversed());
+
+        StatefulWidget::render(list, list_area, buf, &mut state.audit_log_state.list_state);
+    }
+}
@@ -1,15 +1,33 @@
 use std::path::PathBuf;
 
 use crossterm::event::{ KeyCode, KeyModifiers };
-use ratatui::{ style::Style, widgets::{ Block, List, ListState, StatefulWidget, Tabs, Widget } };
+use ratatui::{
+    layout::{ Constraint, Layout },
+    style::Style,
+    text::{ Line, Span },
+    widgets::{ Block, List, ListItem, ListState, Paragraph, StatefulWidget, Widget, Wrap },
+};
 
-use crate::{ app::{ App, AppState, FocusedWidget, Message }, ui::file_selector::{ FileSelector } };
+use crate::{
+    app::{ App, AppState, FocusedWidget, Message },
+    expiry,
+    search::{ SearchField, SearchResult },
+    strength::{ self, SCORE_LABELS },
+    ui::{ file_selector::{ FileSelector }, strength_meter::strength_lines },
+    vault_entry::VaultEntry,
+};
 
 #[derive(Debug)]
 pub struct CenterLeftWidgetState {
-    list_state: ListState,
+    pub list_state: ListState,
     pub current_dir: PathBuf,
     pub selected_file: Option<PathBuf>,
+    pub search_query: String,
+    pub password_input: String,
+    /// Passphrase suggested for a new vault, shown in clear until the vault is created.
+    pub suggested_passphrase: Option<String>,
+    /// Only lists entries with an expiry date, soonest first.
+    pub expiring_only: bool,
 }
 
 impl CenterLeftWidgetState {
@@ -18,6 +36,10 @@ impl CenterLeftWidgetState {
             list_state: ListState::default().with_selected(Some(0)),
             current_dir: PathBuf::from("/"),
             selected_file: None,
+            search_query: String::new(),
+            password_input: String::new(),
+            suggested_passphrase: None,
+            expiring_only: false,
         }
     }
 }
@@ -43,12 +65,132 @@ impl CenterLeftWidget {
             KeyCode::Up if app_state.state == AppState::NoConfigFound => {
                 Some(Message::SetFocusedWidget(FocusedWidget::Top))
             }
+            KeyCode::Char('g') if
+                app_state.state == AppState::CreateNewVault &&
+                key_event.modifiers.contains(KeyModifiers::CONTROL)
+            => {
+                Some(Message::SuggestPassphrase)
+            }
+            KeyCode::Char(c) if
+                Self::asks_password(app_state.state) &&
+                !key_event.modifiers.contains(KeyModifiers::CONTROL)
+            => {
+                Some(Message::PasswordInput(c))
+            }
+            KeyCode::Backspace if Self::asks_password(app_state.state) => {
+                Some(Message::PasswordBackspace)
+            }
+            KeyCode::Enter if Self::asks_password(app_state.state) => {
+                Some(Message::SubmitPassword)
+            }
+            KeyCode::Esc if app_state.state == AppState::CreateNewVault => {
+                Some(Message::UpdateState(AppState::NoConfigFound))
+            }
+            KeyCode::Char('n') if
+                app_state.state == AppState::VaultUnlocked &&
+                key_event.modifiers.contains(KeyModifiers::CONTROL)
+            => {
+                Some(Message::OpenEntryForm)
+            }
+            KeyCode::Char('e') if
+                app_state.state == AppState::VaultUnlocked &&
+                key_event.modifiers.contains(KeyModifiers::CONTROL)
+            => {
+                Some(Message::EditEntry)
+            }
+            KeyCode::Char('g') if
+                app_state.state == AppState::VaultUnlocked &&
+                key_event.modifiers.contains(KeyModifiers::CONTROL)
+            => {
+                Some(Message::OpenGenerator)
+            }
+            KeyCode::Char('x') if
+                app_state.state == AppState::VaultUnlocked &&
+                key_event.modifiers.contains(KeyModifiers::CONTROL)
+            => {
+                Some(Message::ToggleExpiringFilter)
+            }
+            KeyCode::Char(c) if
+                app_state.state == AppState::VaultUnlocked &&
+                !key_event.modifiers.contains(KeyModifiers::CONTROL)
+            => {
+                Some(Message::SearchInput(c))
+            }
+            KeyCode::Backspace if app_state.state == AppState::VaultUnlocked => {
+                Some(Message::SearchBackspace)
+            }
+            KeyCode::Esc if app_state.state == AppState::VaultUnlocked => {
+                Some(Message::ClearSearch)
+            }
+            KeyCode::Down if app_state.state == AppState::VaultUnlocked => {
+                Some(Message::SelectNextEntry)
+            }
+            KeyCode::Up if app_state.state == AppState::VaultUnlocked => {
+                Some(Message::SelectPreviousEntry)
+            }
             // KeyCode::Enter if app_state.state == AppState::CreateNewVault => {
             //     Some(Message::EnterDir(app_state.center_left_state.current_dir.clone().join(path)))
             // }
             _ => None,
         }
     }
+
+    fn asks_password(state: AppState) -> bool {
+        state == AppState::AskMasterPassword || state == AppState::CreateNewVault
+    }
+}
+
+/// Splits `text` into spans, highlighting the chars at `positions`.
+fn highlighted<'a>(text: &'a str, positions: &[usize], style: Style) -> Vec<Span<'a>> {
+    if positions.is_empty() {
+        return vec![Span::styled(text, style)];
+    }
+
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if positions.contains(&i) {
+                Span::styled(c.to_string(), style.yellow().bold())
+            } else {
+                Span::styled(c.to_string(), style)
+            }
+        })
+        .collect()
+}
+
+fn entry_line<'a>(entry: &'a VaultEntry, result: &SearchResult, show_expiry: bool) -> Line<'a> {
+    let mut spans = highlighted(&entry.service, result.positions(SearchField::Service), Style::new());
+
+    if let Some(username) = &entry.username {
+        spans.push(Span::styled(" · ", Style::new().dark_gray()));
+        spans.extend(
+            highlighted(username, result.positions(SearchField::Username), Style::new().dark_gray())
+        );
+    }
+
+    // Matches outside the displayed fields are hinted so the ranking is not surprising.
+    let hidden: Vec<&str> = result.matches
+        .iter()
+        .filter_map(|m| {
+            match m.field {
+                SearchField::Url => Some("url"),
+                SearchField::Tag(_) => Some("tag"),
+                SearchField::Notes => Some("notes"),
+                _ => None,
+            }
+        })
+        .collect();
+
+    if !hidden.is_empty() {
+        spans.push(Span::styled(format!(" ~{}", hidden.join(",")), Style::new().dark_gray().italic()));
+    }
+
+    if show_expiry && let Some(days_left) = expiry::days_left(entry) {
+        let style = if days_left < 0 { Style::new().red() } else { Style::new().yellow() };
+        spans.push(Span::styled(format!(" {}", expiry::describe(days_left)), style));
+    }
+
+    Line::from(spans)
 }
 
 impl StatefulWidget for CenterLeftWidget {
@@ -78,10 +220,104 @@ impl StatefulWidget for CenterLeftWidget {
 
                 StatefulWidget::render(list, area, buf, &mut state.center_left_state.list_state);
             }
-            AppState::CreateNewVault => {
+            AppState::AskMasterPassword | AppState::CreateNewVault => {
+                let title = if state.state == AppState::CreateNewVault {
+                    "New master password"
+                } else {
+                    "Master password"
+                };
+
+                let [input_area, suggestion_area] = Layout::vertical([
+                    Constraint::Length(3),
+                    Constraint::Fill(1),
+                ]).areas(area);
+
+                let mut block = Block::bordered().title(title).border_style(style);
+                if state.state == AppState::CreateNewVault {
+                    block = block.title_bottom("^G suggest a passphrase");
+                }
+
+                Paragraph::new("*".repeat(state.center_left_state.password_input.chars().count()))
+                    .block(block)
+                    .render(input_area, buf);
+
+                let mut lines = Vec::new();
+                let password = &state.center_left_state.password_input;
+
+                if state.state == AppState::CreateNewVault && !password.is_empty() {
+                    let estimate = strength::estimate(password, &[]);
+                    if estimate.score < state.min_master_score() {
+                        lines.push(
+                            Line::styled(
+                                format!("At least {} is required", SCORE_LABELS[(state.min_master_score() as usize).min(4)]),
+                                Style::new().red()
+                            )
+                        );
+                    }
+                    lines.extend(strength_lines(&estimate));
+                }
+
+                if let Some(passphrase) = &state.center_left_state.suggested_passphrase {
+                    if !lines.is_empty() {
+                        lines.push(Line::default());
+                    }
+                    lines.push(Line::from("Suggested passphrase, write it down before you press Enter:"));
+                    lines.push(Line::styled(passphrase.clone(), Style::new().bold()));
+                }
+
+                Paragraph::new(lines).wrap(Wrap { trim: false }).render(suggestion_area, buf);
+            }
+            AppState::OpenExistingVault => {
                 let file_selector = FileSelector;
                 file_selector.render(area, buf, state);
             }
+            AppState::VaultUnlocked => {
+                let [search_area, list_area] = Layout::vertical([
+                    Constraint::Length(3),
+                    Constraint::Fill(1),
+                ]).areas(area);
+
+                let (results, search_block) = match state.search_results() {
+                    Ok(results) => (results, Block::bordered().title("Search")),
+                    Err(e) => {
+                        let title = Line::styled(e.message, Style::new().red());
+                        (Vec::new(), Block::bordered().title("Search").title_bottom(title))
+                    }
+                };
+
+                Paragraph::new(format!("/{}", state.center_left_state.search_query))
+                    .block(
+                        search_block
+                            .title_bottom(Line::from("^X expiring").right_aligned())
+                            .border_style(style)
+                    )
+                    .render(search_area, buf);
+
+                let entries = state.vault.as_ref().map_or(&[][..], |v| v.get_entries().as_slice());
+
+                let items: Vec<ListItem> = results
+                    .iter()
+                    .map(|result| {
+                        ListItem::new(entry_line(&entries[result.index], result, state.center_left_state.expiring_only))
+                    })
+                    .collect();
+
+                let block = if state.center_left_state.expiring_only {
+                    center_left_block.title(Line::styled("Expiring", Style::new().yellow()))
+                } else {
+                    center_left_block
+                };
+
+                let list = List::new(items)
+                    .block(
+                        block
+                            .title_bottom(format!("{}/{}", results.len(), entries.len()))
+                            .title_bottom(Line::from("^N new • ^E edit • ^G generate").right_aligned())
+                    )
+                    .highlight_style(Style::new().reversed());
+
+                StatefulWidget::render(list, list_area, buf, &mut state.center_left_state.list_state);
+            }
             _ => {}
         }
     }
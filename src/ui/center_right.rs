@@ -0,0 +1,253 @@
+use crossterm::event::{ KeyCode, KeyModifiers };
+use ratatui::{
+    layout::{ Constraint, Layout },
+    style::Style,
+    text::{ Line, Span },
+    widgets::{ Block, List, ListState, Paragraph, StatefulWidget, Widget, Wrap },
+};
+
+use crate::{
+    app::{ App, FocusedWidget, Message },
+    expiry,
+    merge::{ Conflict, Side },
+    timestamp,
+    vault_entry::VaultEntry,
+};
+
+#[derive(Debug)]
+pub struct CenterRightWidgetState {
+    pub conflict_state: ListState,
+    /// Id of the entry whose password is shown in clear.
+    pub revealed: Option<String>,
+    /// Id of the entry to delete on a second `d`.
+    pub pending_delete: Option<String>,
+}
+
+impl CenterRightWidgetState {
+    pub fn new() -> Self {
+        Self {
+            conflict_state: ListState::default().with_selected(Some(0)),
+            revealed: None,
+            pending_delete: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CenterRightWidget;
+
+impl CenterRightWidget {
+    pub fn handle_key_event(
+        key_event: crossterm::event::KeyEvent,
+        app_state: &App
+    ) -> Option<Message> {
+        let has_conflicts = app_state.vault.as_ref().is_some_and(|v| !v.conflicts().is_empty());
+
+        match key_event.code {
+            KeyCode::Left if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Message::SetFocusedWidget(FocusedWidget::CenterLeft))
+            }
+            KeyCode::Up if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Message::SetFocusedWidget(FocusedWidget::Top))
+            }
+            KeyCode::Down if has_conflicts => Some(Message::SelectNextConflict),
+            KeyCode::Up if has_conflicts => Some(Message::SelectPreviousConflict),
+            KeyCode::Char('l') if has_conflicts => Some(Message::ResolveConflict(Side::Local)),
+            KeyCode::Char('r') if has_conflicts => Some(Message::ResolveConflict(Side::Remote)),
+            KeyCode::Char('v') => Some(Message::ToggleReveal),
+            KeyCode::Char('c') => Some(Message::CopyPassword),
+            KeyCode::Char('e') => Some(Message::EditEntry),
+            KeyCode::Char('d') => Some(Message::DeleteEntry),
+            _ => None,
+        }
+    }
+}
+
+fn side_label(entry: &VaultEntry, name: &str) -> String {
+    if entry.modified == 0 {
+        name.to_string()
+    } else {
+        format!("{name} (modified {})", timestamp::format_date(entry.modified))
+    }
+}
+
+fn field_value(entry: &VaultEntry, field: &str) -> String {
+    if field == "password" {
+        return "********".to_string();
+    }
+    if field == "expires" {
+        return entry.expires.map_or_else(|| "-".to_string(), timestamp::format_date);
+    }
+
+    let value = serde_json::to_value(entry).unwrap_or_default();
+
+    match value.get(field) {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(serde_json::Value::Array(items)) => {
+            items
+                .iter()
+                .map(|i| i.as_str().map_or_else(|| i.to_string(), str::to_string))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        Some(serde_json::Value::Null) | None => "-".to_string(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Details of `entry`, its expiry highlighted from `warning_days` before the date.
+fn entry_details(entry: &VaultEntry, revealed: bool, warning_days: u64) -> Vec<Line<'static>> {
+    let password = if revealed {
+        String::from_utf8_lossy(&entry.password).into_owned()
+    } else {
+        "********".to_string()
+    };
+
+    let mut lines = Vec::new();
+    if !entry.kind.is_login() {
+        lines.push(Line::from(vec![Span::styled(format!("{:<9}", "kind"), Style::new().bold()), Span::raw(entry.kind.to_string())]));
+    }
+    for field in ["username", "password", "url", "folder", "tags", "notes"] {
+        let value = if field == "password" { password.clone() } else { field_value(entry, field) };
+        lines.push(Line::from(vec![Span::styled(format!("{field:<9}"), Style::new().bold()), Span::raw(value)]));
+    }
+
+    if let Some(params) = &entry.derivation {
+        lines.push(
+            Line::from(vec![
+                Span::styled(format!("{:<9}", "derived"), Style::new().bold()),
+                Span::raw(format!("from {} {}, {}", params.site, params.login, params.summary())),
+            ])
+        );
+    }
+
+    if let (Some(expires), Some(days_left)) = (entry.expires, expiry::days_left(entry)) {
+        let style = if days_left < 0 {
+            Style::new().red()
+        } else if days_left <= warning_days as i64 {
+            Style::new().yellow()
+        } else {
+            Style::new()
+        };
+
+        lines.push(
+            Line::from(vec![
+                Span::styled(format!("{:<9}", "expires"), Style::new().bold()),
+                Span::styled(format!("{} ({})", timestamp::format_date(expires), expiry::describe(days_left)), style),
+            ])
+        );
+    }
+
+    if entry.totp.is_some() {
+        lines.push(Line::from(vec![Span::styled(format!("{:<9}", "totp"), Style::new().bold()), Span::raw("set up")]));
+    }
+
+    // Protected fields are hidden with the password.
+    for field in &entry.custom_fields {
+        let value = if field.protected && !revealed { "********" } else { field.value.as_str() };
+        lines.push(Line::from(vec![Span::styled(format!("{}: ", field.name), Style::new().bold()), Span::raw(value.to_string())]));
+    }
+
+    for attachment in &entry.attachments {
+        lines.push(
+            Line::from(vec![
+                Span::styled(format!("{:<9}", "file"), Style::new().bold()),
+                Span::raw(format!("{} ({} bytes)", attachment.name, attachment.data.len())),
+            ])
+        );
+    }
+
+    if entry.modified != 0 {
+        lines.push(Line::default());
+        lines.push(Line::from(format!("modified {}", timestamp::format_date(entry.modified))));
+    }
+
+    lines
+}
+
+fn conflict_details(conflict: &Conflict) -> Vec<Line<'static>> {
+    let default_side = conflict.default_side();
+    let marker = |side: Side| if side == default_side { " [applied]" } else { "" };
+
+    let mut lines = vec![
+        Line::from(format!("l: {}{}", side_label(&conflict.local, "local"), marker(Side::Local))),
+        Line::from(format!("r: {}{}", side_label(&conflict.remote, "remote"), marker(Side::Remote))),
+        Line::default()
+    ];
+
+    for field in conflict.changed_fields() {
+        lines.push(Line::from(Span::styled(field.clone(), Style::new().bold())));
+        lines.push(Line::from(format!("  local:  {}", field_value(&conflict.local, &field))));
+        lines.push(Line::from(format!("  remote: {}", field_value(&conflict.remote, &field))));
+    }
+
+    lines
+}
+
+impl StatefulWidget for CenterRightWidget {
+    type State = App;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State
+    )
+        where Self: Sized
+    {
+        let style = if state.focused_widget == FocusedWidget::CenterRight {
+            Style::new().blue()
+        } else {
+            Style::new()
+        };
+
+        let conflicts = state.vault.as_ref().map_or(&[][..], |v| v.conflicts());
+
+        if conflicts.is_empty() {
+            let block = Block::bordered().border_style(style);
+
+            match state.selected_entry() {
+                Some(entry) => {
+                    let revealed = state.center_right_state.revealed.as_deref() == Some(entry.id.as_str());
+
+                    Paragraph::new(entry_details(entry, revealed, state.expiry_warning_days()))
+                        .wrap(Wrap { trim: false })
+                        .block(
+                            block
+                                .title(entry.service.clone())
+                                .title_bottom(if revealed { "v hide • c copy • e edit • d delete" } else { "v reveal • c copy • e edit • d delete" })
+                        )
+                        .render(area, buf);
+                }
+                None => block.title("center right").render(area, buf),
+            }
+            return;
+        }
+
+        let [list_area, details_area] = Layout::vertical([
+            Constraint::Length((conflicts.len() as u16).min(6) + 2),
+            Constraint::Fill(1),
+        ]).areas(area);
+
+        let list = List::new(conflicts.iter().map(|c| c.local.service.clone()))
+            .block(Block::bordered().title("Merge conflicts").border_style(style))
+            .highlight_style(Style::new().reversed());
+
+        StatefulWidget::render(list, list_area, buf, &mut state.center_right_state.conflict_state);
+
+        let selected = state.center_right_state.conflict_state
+            .selected()
+            .unwrap_or(0)
+            .min(conflicts.len() - 1);
+
+        Paragraph::new(conflict_details(&conflicts[selected]))
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::bordered()
+                    .title("Conflict")
+                    .title_bottom("l keep local • r keep remote")
+                    .border_style(style)
+            )
+            .render(details_area, buf);
+    }
+}
@@ -0,0 +1,307 @@
+use crossterm::event::{ KeyCode, KeyModifiers };
+use ratatui::{
+    layout::{ Constraint, Layout },
+    style::Style,
+    text::Line,
+    widgets::{ Block, Paragraph, StatefulWidget, Widget },
+};
+use strum::{ Display, EnumCount, EnumIter, FromRepr, IntoEnumIterator };
+
+use crate::{
+    app::{ App, Message },
+    derived::{ self, DerivationParams },
+    generator::PasswordPolicy,
+    password_rules::PasswordRules,
+    strength::{ self, Estimate },
+    timestamp,
+    ui::strength_meter::strength_lines,
+    vault_entry::{ self, VaultEntry },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumCount, EnumIter, FromRepr)]
+pub enum FormField {
+    #[strum(to_string = "Service")]
+    Service,
+    #[strum(to_string = "Username")]
+    Username,
+    #[strum(to_string = "Password")]
+    Password,
+    #[strum(to_string = "URL")]
+    Url,
+    #[strum(to_string = "Password rules")]
+    Rules,
+    #[strum(to_string = "Expires (YYYY-MM-DD)")]
+    Expires,
+    #[strum(to_string = "Folder")]
+    Folder,
+    #[strum(to_string = "Tags")]
+    Tags,
+    #[strum(to_string = "Notes")]
+    Notes,
+}
+
+#[derive(Debug)]
+pub struct EntryFormState {
+    /// The entry being edited, `None` when adding one.
+    pub original: Option<VaultEntry>,
+    values: [String; FormField::COUNT],
+    pub field: FormField,
+    pub show_password: bool,
+    /// Set when the password is derived rather than typed, see `derived`.
+    pub derivation: Option<DerivationParams>,
+    pub error: Option<String>,
+}
+
+impl EntryFormState {
+    pub fn new() -> Self {
+        Self {
+            original: None,
+            values: Default::default(),
+            field: FormField::Service,
+            show_password: false,
+            derivation: None,
+            error: None,
+        }
+    }
+
+    pub fn edit(entry: &VaultEntry) -> Self {
+        let mut form = Self::new();
+
+        form.set_value(FormField::Service, entry.service.clone());
+        form.set_value(FormField::Username, entry.username.clone().unwrap_or_default());
+        form.set_value(FormField::Password, String::from_utf8_lossy(&entry.password).into_owned());
+        form.set_value(FormField::Url, entry.url.clone().unwrap_or_default());
+        form.set_value(FormField::Rules, entry.password_rules.clone().unwrap_or_default());
+        form.set_value(FormField::Expires, entry.expires.map(timestamp::format_date).unwrap_or_default());
+        form.set_value(FormField::Folder, entry.folder.clone().unwrap_or_default());
+        form.set_value(FormField::Tags, entry.tags.join(", "));
+        form.set_value(FormField::Notes, entry.notes.clone().unwrap_or_default());
+        form.derivation = entry.derivation.clone();
+        form.original = Some(entry.clone());
+
+        form
+    }
+
+    /// Strength of the typed password, the service and username counting as guessable.
+    pub fn password_strength(&self) -> Estimate {
+        strength::estimate(
+            self.value(FormField::Password),
+            &[self.value(FormField::Service), self.value(FormField::Username), self.value(FormField::Url)]
+        )
+    }
+
+    /// Rules the password must follow: the typed descriptor, or else the bundled rules of
+    /// the site of the typed URL.
+    pub fn password_rules(&self) -> Option<PasswordRules> {
+        let descriptor = self.value(FormField::Rules).trim();
+        if !descriptor.is_empty() {
+            return PasswordRules::parse(descriptor).ok();
+        }
+
+        PasswordRules::for_host(&vault_entry::host_of(self.value(FormField::Url))?)
+    }
+
+    pub fn value(&self, field: FormField) -> &str {
+        &self.values[field as usize]
+    }
+
+    pub fn set_value(&mut self, field: FormField, value: String) {
+        self.values[field as usize] = value;
+    }
+
+    /// Whether the selected field takes typing, a derived password being computed.
+    fn editable(&self) -> bool {
+        self.field != FormField::Password || self.derivation.is_none()
+    }
+
+    pub fn input(&mut self, c: char) {
+        if self.editable() {
+            self.values[self.field as usize].push(c);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if self.editable() {
+            self.values[self.field as usize].pop();
+        }
+    }
+
+    /// Switches between a typed password and one derived from the master password, the
+    /// length and classes of `policy` making up the profile of a new derivation.
+    pub fn toggle_derivation(&mut self, policy: &PasswordPolicy) {
+        if self.derivation.take().is_some() {
+            return;
+        }
+
+        let mut params = DerivationParams::new(String::new(), String::new());
+        params.length = policy.length.clamp(derived::MIN_LENGTH, derived::MAX_LENGTH);
+        params.lowercase = policy.lowercase.enabled;
+        params.uppercase = policy.uppercase.enabled;
+        params.digits = policy.digits.enabled;
+        params.symbols = policy.symbols.enabled;
+        self.derivation = Some(params);
+        self.sync_derivation();
+    }
+
+    /// Derives from the site of the typed URL, or else the service, and the username.
+    pub fn sync_derivation(&mut self) {
+        let site = vault_entry::host_of(self.value(FormField::Url))
+            .unwrap_or_else(|| self.value(FormField::Service).trim().to_lowercase());
+        let login = self.value(FormField::Username).trim().to_string();
+
+        if let Some(params) = self.derivation.as_mut() {
+            params.site = site;
+            params.login = login;
+        }
+    }
+
+    pub fn next_field(&mut self) {
+        self.field = FormField::from_repr((self.field as usize + 1) % FormField::COUNT).unwrap();
+    }
+
+    pub fn previous_field(&mut self) {
+        self.field = FormField::from_repr(
+            (self.field as usize + FormField::COUNT - 1) % FormField::COUNT
+        ).unwrap();
+    }
+
+    /// Builds the entry typed in the form, the service being the only required field. An
+    /// edited entry keeps its id and history.
+    pub fn to_entry(&self) -> Result<VaultEntry, String> {
+        let optional = |field| Some(self.value(field).trim().to_string()).filter(|v| !v.is_empty());
+
+        let service = self.value(FormField::Service).trim();
+        if service.is_empty() {
+            return Err("the service is required".to_string());
+        }
+
+        let mut entry = match &self.original {
+            Some(original) => {
+                let mut entry = original.clone();
+                entry.service = service.to_string();
+                entry.username = optional(FormField::Username);
+                entry.password = self.value(FormField::Password).as_bytes().to_vec();
+                entry
+            }
+            None => {
+                VaultEntry::new(
+                    service.to_string(),
+                    optional(FormField::Username),
+                    self.value(FormField::Password).as_bytes().to_vec()
+                )
+            }
+        };
+        entry.url = optional(FormField::Url);
+        entry.password_rules = optional(FormField::Rules);
+        entry.derivation = self.derivation.clone();
+        if let Some(descriptor) = &entry.password_rules {
+            PasswordRules::parse(descriptor).map_err(|e| format!("invalid password rules: {e}"))?;
+        }
+        entry.expires = match optional(FormField::Expires) {
+            Some(date) => Some(timestamp::parse_date(&date).ok_or("the expiry date must be a YYYY-MM-DD date")?),
+            None => None,
+        };
+        entry.folder = optional(FormField::Folder).map(|f| f.trim_matches('/').to_string());
+        entry.notes = optional(FormField::Notes);
+        entry.tags = self
+            .value(FormField::Tags)
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        Ok(entry)
+    }
+}
+
+#[derive(Debug)]
+pub struct EntryForm;
+
+impl EntryForm {
+    pub fn handle_key_event(key_event: crossterm::event::KeyEvent) -> Option<Message> {
+        let control = key_event.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key_event.code {
+            KeyCode::Esc => Some(Message::CloseEntryForm),
+            KeyCode::Enter => Some(Message::SubmitEntryForm),
+            KeyCode::Tab | KeyCode::Down => Some(Message::FormNextField),
+            KeyCode::BackTab | KeyCode::Up => Some(Message::FormPreviousField),
+            KeyCode::Char('g') if control => Some(Message::OpenGenerator),
+            KeyCode::Char('r') if control => Some(Message::FormTogglePassword),
+            KeyCode::Char('d') if control => Some(Message::FormToggleDerivation),
+            KeyCode::Char('u') if control => Some(Message::FormBumpCounter),
+            KeyCode::Char(c) if !control => Some(Message::FormInput(c)),
+            KeyCode::Backspace => Some(Message::FormBackspace),
+            _ => None,
+        }
+    }
+}
+
+impl StatefulWidget for EntryForm {
+    type State = App;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State
+    )
+        where Self: Sized
+    {
+        let Some(form) = state.entry_form.as_ref() else {
+            return;
+        };
+
+        let mut block = Block::bordered()
+            .title(if form.original.is_some() { "Edit entry" } else { "New entry" })
+            .title_bottom("Tab next • Enter save • Esc cancel • ^G generate • ^R show password • ^D derive")
+            .border_style(Style::new().blue());
+        if let Some(params) = &form.derivation {
+            block = block.title(
+                Line::styled(format!("derived: {} • ^U next counter", params.summary()), Style::new().dark_gray())
+            );
+        }
+        if let Some(error) = &form.error {
+            block = block.title(Line::styled(error.clone(), Style::new().red()).right_aligned());
+        }
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let rows = Layout::vertical(
+            FormField::iter()
+                .map(|_| Constraint::Length(3))
+                .chain([Constraint::Fill(1)])
+        ).split(inner);
+
+        for (field, row) in FormField::iter().zip(rows.iter()) {
+            let value = form.value(field);
+            let shown = if field == FormField::Password && !form.show_password {
+                "*".repeat(value.chars().count())
+            } else {
+                value.to_string()
+            };
+
+            let style = if field == form.field { Style::new().blue() } else { Style::new() };
+
+            Paragraph::new(shown)
+                .block(Block::bordered().title(field.to_string()).border_style(style))
+                .render(*row, buf);
+        }
+
+        if !form.value(FormField::Password).is_empty() {
+            let mut lines = strength_lines(&form.password_strength());
+            if
+                let Some(rules) = form.password_rules() &&
+                let Err(e) = rules.check(form.value(FormField::Password))
+            {
+                lines.insert(0, Line::styled(format!("Breaks the site's password rules: {e}"), Style::new().red()));
+            }
+
+            Paragraph::new(lines)
+                .block(Block::bordered().title("Password strength"))
+                .render(rows[FormField::COUNT], buf);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use ratatui::{
+    style::Style,
+    text::{ Line, Span },
+    widgets::{ Block, Paragraph, StatefulWidget, Widget },
+};
+
+use crate::{ app::App, expiry::{ self, ExpiringEntry } };
+
+/// Entries listed before the rest is summed up.
+const MAX_LINES: usize = 4;
+
+#[derive(Debug)]
+pub struct ExpiryBanner;
+
+impl ExpiryBanner {
+    /// Rows taken by the banner for `expiring`, borders included.
+    pub fn height(expiring: &[ExpiringEntry]) -> u16 {
+        let lines = if expiring.len() > MAX_LINES { MAX_LINES + 1 } else { expiring.len() };
+        lines as u16 + 2
+    }
+}
+
+impl StatefulWidget for ExpiryBanner {
+    type State = App;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State
+    )
+        where Self: Sized
+    {
+        let Some(vault) = state.vault.as_ref() else {
+            return;
+        };
+
+        let entries = vault.get_entries();
+        let mut lines: Vec<Line> = state.expiry_banner
+            .iter()
+            .take(MAX_LINES)
+            .map(|e| {
+                let entry = &entries[e.index];
+                let style = if e.is_expired() { Style::new().red() } else { Style::new().yellow() };
+
+                Line::from(vec![
+                    Span::styled(format!("{:<20}", e.describe()), style),
+                    Span::raw(entry.service.clone()),
+                    Span::styled(
+                        entry.username.as_ref().map_or(String::new(), |u| format!(" · {u}")),
+                        Style::new().dark_gray()
+                    ),
+                ])
+            })
+            .collect();
+
+        if state.expiry_banner.len() > MAX_LINES {
+            lines.push(
+                Line::styled(
+                    format!("and {} more, ^X lists them", state.expiry_banner.len() - MAX_LINES),
+                    Style::new().dark_gray()
+                )
+            );
+        }
+
+        let block = Block::bordered()
+            .title(Line::styled(expiry::counts(&state.expiry_banner), Style::new().yellow().bold()))
+            .title_bottom("^X show expiring entries • Esc dismiss")
+            .border_style(Style::new().yellow());
+
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
@@ -0,0 +1,364 @@
+use crossterm::event::{ KeyCode, KeyModifiers };
+use ratatui::{
+    layout::{ Constraint, Flex, Layout, Rect },
+    style::Style,
+    text::{ Line, Span },
+    widgets::{ Block, Clear, List, ListState, Paragraph, StatefulWidget, Widget },
+};
+use strum::IntoEnumIterator;
+
+use crate::{
+    app::{ App, Message },
+    generator::{ CharClass, MAX_LENGTH, PasswordPolicy },
+    passphrase::{ self, BuiltinList, PassphrasePolicy, WordList },
+    password_rules::PasswordRules,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorMode {
+    Password,
+    Passphrase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorOption {
+    Mode,
+    Length,
+    Lowercase,
+    Uppercase,
+    Digits,
+    Symbols,
+    SymbolSet,
+    LookAlikes,
+    Words,
+    Separator,
+    Capitalization,
+    Digit,
+    WordList,
+}
+
+const PASSWORD_OPTIONS: [GeneratorOption; 8] = [
+    GeneratorOption::Mode,
+    GeneratorOption::Length,
+    GeneratorOption::Lowercase,
+    GeneratorOption::Uppercase,
+    GeneratorOption::Digits,
+    GeneratorOption::Symbols,
+    GeneratorOption::SymbolSet,
+    GeneratorOption::LookAlikes,
+];
+
+const PASSPHRASE_OPTIONS: [GeneratorOption; 6] = [
+    GeneratorOption::Mode,
+    GeneratorOption::Words,
+    GeneratorOption::Separator,
+    GeneratorOption::Capitalization,
+    GeneratorOption::Digit,
+    GeneratorOption::WordList,
+];
+
+/// Next or previous variant of an enum, wrapping around.
+fn cycle<T: IntoEnumIterator + PartialEq + Copy>(value: T, delta: isize) -> T {
+    let all: Vec<T> = T::iter().collect();
+    let index = all.iter().position(|v| *v == value).unwrap_or(0) as isize;
+    all[(index + delta).rem_euclid(all.len() as isize) as usize]
+}
+
+impl GeneratorOption {
+    fn class(self) -> Option<CharClass> {
+        match self {
+            GeneratorOption::Lowercase => Some(CharClass::Lowercase),
+            GeneratorOption::Uppercase => Some(CharClass::Uppercase),
+            GeneratorOption::Digits => Some(CharClass::Digits),
+            GeneratorOption::Symbols => Some(CharClass::Symbols),
+            _ => None,
+        }
+    }
+}
+
+/// Policy and preview of the generator dialog, kept while the application runs so the
+/// next password follows the same policy.
+#[derive(Debug)]
+pub struct GeneratorDialogState {
+    pub open: bool,
+    pub mode: GeneratorMode,
+    pub policy: PasswordPolicy,
+    pub passphrase: PassphrasePolicy,
+    pub word_list: BuiltinList,
+    /// Rules of the site of the entry being edited, which generated passwords follow.
+    pub rules: Option<PasswordRules>,
+    pub list_state: ListState,
+    /// Last generated password, or why the policy cannot generate one.
+    pub preview: Result<String, String>,
+}
+
+impl GeneratorDialogState {
+    pub fn new() -> Self {
+        let policy = PasswordPolicy::default();
+
+        Self {
+            open: false,
+            mode: GeneratorMode::Password,
+            preview: policy.generate(),
+            policy,
+            passphrase: PassphrasePolicy::default(),
+            word_list: BuiltinList::default(),
+            rules: None,
+            list_state: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    fn options(&self) -> &'static [GeneratorOption] {
+        match self.mode {
+            GeneratorMode::Password => &PASSWORD_OPTIONS,
+            GeneratorMode::Passphrase => &PASSPHRASE_OPTIONS,
+        }
+    }
+
+    pub fn option(&self) -> GeneratorOption {
+        let options = self.options();
+        options[self.list_state.selected().unwrap_or(0).min(options.len() - 1)]
+    }
+
+    pub fn regenerate(&mut self) {
+        self.preview = match (self.mode, &self.rules) {
+            (GeneratorMode::Password, Some(rules)) => rules.generate(&self.policy),
+            (GeneratorMode::Password, None) => self.policy.generate(),
+            (GeneratorMode::Passphrase, _) => self.passphrase.generate(WordList::builtin(self.word_list)),
+        };
+
+        if
+            let (Ok(password), Some(rules)) = (&self.preview, &self.rules) &&
+            let Err(e) = rules.check(password)
+        {
+            self.preview = Err(format!("breaks the site's password rules: {e}"));
+        }
+    }
+
+    /// Entropy of the generated passwords, left slightly overestimated by the site's rules
+    /// that only reject draws.
+    pub fn entropy(&self) -> Result<f64, String> {
+        match self.mode {
+            GeneratorMode::Password => {
+                match &self.rules {
+                    Some(rules) => rules.restrict(&self.policy).entropy(),
+                    None => self.policy.entropy(),
+                }
+            }
+            GeneratorMode::Passphrase => self.passphrase.entropy(WordList::builtin(self.word_list)),
+        }
+    }
+
+    /// Whether typed characters edit the selected option rather than being shortcuts.
+    pub fn takes_text(&self) -> bool {
+        matches!(self.option(), GeneratorOption::SymbolSet | GeneratorOption::Separator)
+    }
+
+    /// Changes the selected number or choice by `delta`.
+    pub fn adjust(&mut self, delta: isize) {
+        let option = self.option();
+
+        match option {
+            GeneratorOption::Mode => {
+                self.mode = match self.mode {
+                    GeneratorMode::Password => GeneratorMode::Passphrase,
+                    GeneratorMode::Passphrase => GeneratorMode::Password,
+                };
+            }
+            GeneratorOption::Length => {
+                self.policy.length = self.policy.length.saturating_add_signed(delta).clamp(1, MAX_LENGTH);
+            }
+            GeneratorOption::Words => {
+                self.passphrase.words = self.passphrase.words
+                    .saturating_add_signed(delta)
+                    .clamp(1, passphrase::MAX_WORDS);
+            }
+            GeneratorOption::Capitalization => {
+                self.passphrase.capitalization = cycle(self.passphrase.capitalization, delta);
+            }
+            GeneratorOption::WordList => {
+                self.word_list = cycle(self.word_list, delta);
+            }
+            _ => {
+                let Some(class) = option.class() else {
+                    return;
+                };
+                let rule = self.policy.rule_mut(class);
+                rule.min = rule.min.saturating_add_signed(delta).min(MAX_LENGTH);
+            }
+        }
+
+        self.regenerate();
+    }
+
+    pub fn toggle(&mut self) {
+        match self.option() {
+            GeneratorOption::LookAlikes => {
+                self.policy.exclude_look_alikes = !self.policy.exclude_look_alikes;
+            }
+            GeneratorOption::Digit => {
+                self.passphrase.insert_digit = !self.passphrase.insert_digit;
+            }
+            GeneratorOption::Mode | GeneratorOption::Capitalization | GeneratorOption::WordList => {
+                self.adjust(1);
+                return;
+            }
+            option => {
+                let Some(class) = option.class() else {
+                    return;
+                };
+                let rule = self.policy.rule_mut(class);
+                rule.enabled = !rule.enabled;
+            }
+        }
+
+        self.regenerate();
+    }
+
+    /// Edits the symbol set or the separator, `None` removing the last character.
+    pub fn edit_text(&mut self, input: Option<char>) {
+        let text = match self.option() {
+            GeneratorOption::SymbolSet => &mut self.policy.symbol_set,
+            GeneratorOption::Separator => &mut self.passphrase.separator,
+            _ => {
+                return;
+            }
+        };
+
+        match input {
+            Some(c) => text.push(c),
+            None => {
+                text.pop();
+            }
+        }
+
+        self.regenerate();
+    }
+
+    fn option_line(&self, option: GeneratorOption) -> Line<'static> {
+        let check = |enabled: bool| if enabled { "[x]" } else { "[ ]" };
+
+        match option {
+            GeneratorOption::Mode => {
+                Line::from(
+                    match self.mode {
+                        GeneratorMode::Password => "Kind          ‹ password ›",
+                        GeneratorMode::Passphrase => "Kind          ‹ passphrase ›",
+                    }
+                )
+            }
+            GeneratorOption::Length => Line::from(format!("Length        ‹ {} ›", self.policy.length)),
+            GeneratorOption::Words => Line::from(format!("Words         ‹ {} ›", self.passphrase.words)),
+            GeneratorOption::Separator => Line::from(format!("Separator     {}", self.passphrase.separator)),
+            GeneratorOption::Capitalization => {
+                Line::from(format!("Case          ‹ {} ›", self.passphrase.capitalization))
+            }
+            GeneratorOption::Digit => {
+                Line::from(format!("{} Append a digit to a word", check(self.passphrase.insert_digit)))
+            }
+            GeneratorOption::WordList => {
+                let list = WordList::builtin(self.word_list);
+                Line::from(format!("Word list     ‹ {} › {} words", self.word_list, list.len()))
+            }
+            GeneratorOption::SymbolSet => Line::from(format!("Symbol set    {}", self.policy.symbol_set)),
+            GeneratorOption::LookAlikes => {
+                Line::from(format!("{} Exclude look-alikes (0 O o 1 l I |)", check(self.policy.exclude_look_alikes)))
+            }
+            _ => {
+                let class = option.class().unwrap();
+                let rule = self.policy.rule(class);
+                Line::from(format!("{} {:<10} at least ‹ {} ›", check(rule.enabled), class.to_string(), rule.min))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GeneratorDialog;
+
+impl GeneratorDialog {
+    pub fn handle_key_event(
+        key_event: crossterm::event::KeyEvent,
+        app_state: &App
+    ) -> Option<Message> {
+        let control = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        let takes_text = app_state.generator_state.takes_text();
+
+        match key_event.code {
+            KeyCode::Esc => Some(Message::CloseGenerator),
+            KeyCode::Enter => Some(Message::AcceptGenerated),
+            KeyCode::Char('r') if control => Some(Message::Regenerate),
+            KeyCode::Down | KeyCode::Tab => Some(Message::GeneratorNextOption),
+            KeyCode::Up | KeyCode::BackTab => Some(Message::GeneratorPreviousOption),
+            KeyCode::Right => Some(Message::GeneratorAdjust(1)),
+            KeyCode::Left => Some(Message::GeneratorAdjust(-1)),
+            KeyCode::Char(c) if takes_text && !control => Some(Message::GeneratorTextInput(Some(c))),
+            KeyCode::Backspace if takes_text => Some(Message::GeneratorTextInput(None)),
+            KeyCode::Char(' ') => Some(Message::GeneratorToggle),
+            _ => None,
+        }
+    }
+
+    /// Centered area for the dialog within `area`.
+    pub fn area(area: Rect) -> Rect {
+        let [area] = Layout::vertical([Constraint::Length(16)]).flex(Flex::Center).areas(area);
+        let [area] = Layout::horizontal([Constraint::Max(64)]).flex(Flex::Center).areas(area);
+        area
+    }
+}
+
+impl StatefulWidget for GeneratorDialog {
+    type State = App;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State
+    )
+        where Self: Sized
+    {
+        let generator = &mut state.generator_state;
+
+        Clear.render(area, buf);
+
+        let entropy = match generator.entropy() {
+            Ok(bits) => format!("{bits:.1} bits"),
+            Err(_) => "-".to_string(),
+        };
+
+        let block = Block::bordered()
+            .title(match generator.mode {
+                GeneratorMode::Password => "Generate password",
+                GeneratorMode::Passphrase => "Generate passphrase",
+            })
+            .title(Line::from(entropy).right_aligned())
+            .title_bottom("←→ adjust • space toggle • ^R again • Enter use • Esc close")
+            .border_style(Style::new().blue());
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let [preview_area, rules_area, options_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(if generator.rules.is_some() { 1 } else { 0 }),
+            Constraint::Fill(1),
+        ]).areas(inner);
+
+        let preview = match &generator.preview {
+            Ok(password) => Line::from(Span::styled(password.clone(), Style::new().bold())),
+            Err(e) => Line::styled(e.clone(), Style::new().red()),
+        };
+        Paragraph::new(preview).block(Block::bordered()).render(preview_area, buf);
+
+        if let Some(rules) = &generator.rules {
+            Paragraph::new(Line::styled(format!("Site rules: {rules}"), Style::new().dark_gray()))
+                .render(rules_area, buf);
+        }
+
+        let lines: Vec<Line> = generator.options().iter().map(|o| generator.option_line(*o)).collect();
+        let list = List::new(lines).highlight_style(Style::new().reversed());
+
+        StatefulWidget::render(list, options_area, buf, &mut generator.list_state);
+    }
+}
@@ -1,3 +1,11 @@
 pub mod top;
+pub mod audit_log;
+pub mod rotation;
+pub mod security_report;
 pub mod center_left;
+pub mod center_right;
+pub mod entry_form;
+pub mod expiry_banner;
+pub mod generator_dialog;
+pub mod strength_meter;
 pub mod file_selector;
@@ -0,0 +1,133 @@
+use crossterm::event::{ KeyCode, KeyModifiers };
+use ratatui::{
+    layout::{ Constraint, Layout },
+    style::Style,
+    text::{ Line, Span },
+    widgets::{ Block, List, ListState, Paragraph, StatefulWidget, Widget },
+};
+
+use crate::{
+    app::{ App, FocusedWidget, Message },
+    timestamp,
+};
+
+#[derive(Debug)]
+pub struct RotationWidgetState {
+    pub list_state: ListState,
+}
+
+impl RotationWidgetState {
+    pub fn new() -> Self {
+        Self {
+            list_state: ListState::default().with_selected(Some(0)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RotationWidget;
+
+impl RotationWidget {
+    pub fn handle_key_event(
+        key_event: crossterm::event::KeyEvent,
+        _app_state: &App
+    ) -> Option<Message> {
+        match key_event.code {
+            KeyCode::Up if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Message::SetFocusedWidget(FocusedWidget::Top))
+            }
+            KeyCode::Down => Some(Message::SelectNextDueEntry),
+            KeyCode::Up => Some(Message::SelectPreviousDueEntry),
+            KeyCode::Char('t') => Some(Message::StartRotation),
+            KeyCode::Char('y') => Some(Message::ConfirmRotation),
+            KeyCode::Char('n') => Some(Message::CancelRotation),
+            KeyCode::Char('c') => Some(Message::CopyPendingPassword),
+            _ => None,
+        }
+    }
+}
+
+impl StatefulWidget for RotationWidget {
+    type State = App;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State
+    )
+        where Self: Sized
+    {
+        let style = if state.focused_widget == FocusedWidget::Top {
+            Style::new()
+        } else {
+            Style::new().blue()
+        };
+
+        let block = Block::bordered().title("Due for rotation").border_style(style);
+
+        let Some(vault) = state.vault.as_ref() else {
+            Paragraph::new("Unlock a vault to see the passwords due for rotation").block(block).render(area, buf);
+            return;
+        };
+
+        let due = state.due_entries();
+        if due.is_empty() {
+            Paragraph::new("No password is due for rotation").block(block).render(area, buf);
+            return;
+        }
+
+        let [list_area, details_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(5),
+        ]).areas(area);
+
+        let entries = vault.get_entries();
+        let lines: Vec<Line> = due
+            .iter()
+            .map(|d| {
+                let entry = &entries[d.index];
+                let name = match &entry.username {
+                    Some(username) => format!("{} ({username})", entry.service),
+                    None => entry.service.clone(),
+                };
+
+                Line::from(vec![
+                    Span::styled(if d.pending { "pending  " } else { "due      " }, if d.pending {
+                        Style::new().yellow()
+                    } else {
+                        Style::new().red()
+                    }),
+                    Span::raw(format!("{name:<40} ")),
+                    Span::styled(d.describe(), Style::new().dark_gray()),
+                ])
+            })
+            .collect();
+
+        let list = List::new(lines)
+            .block(block.title_bottom("t rotate • c copy new password • y confirm • n cancel"))
+            .highlight_style(Style::new().reversed());
+
+        let selected = state.rotation_state.list_state.selected().unwrap_or(0).min(due.len() - 1);
+        let entry = &entries[due[selected].index];
+
+        let details = match &entry.pending_rotation {
+            Some(pending) => {
+                vec![
+                    Line::from(format!("New password generated on {}", timestamp::format_date(pending.time))),
+                    Line::from("Change it on the site with the current password, then confirm with y.")
+                ]
+            }
+            None => {
+                vec![
+                    Line::from(format!("Password set on {}", timestamp::format_date(entry.password_changed()))),
+                    Line::from(format!("{} previous passwords kept", entry.password_history.len()))
+                ]
+            }
+        };
+        let details = Paragraph::new(details).block(Block::bordered().title(entry.service.clone()));
+
+        StatefulWidget::render(list, list_area, buf, &mut state.rotation_state.list_state);
+        details.render(details_area, buf);
+    }
+}
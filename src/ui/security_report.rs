@@ -0,0 +1,132 @@
+use crossterm::event::{ KeyCode, KeyModifiers };
+use ratatui::{
+    layout::{ Constraint, Layout },
+    style::{ Color, Style },
+    text::{ Line, Span },
+    widgets::{ Block, List, ListState, Paragraph, StatefulWidget, Widget },
+};
+use strum::IntoEnumIterator;
+
+use crate::{
+    app::{ App, FocusedWidget, Message },
+    security_report::{ EntryRef, Finding, FindingKind, SecurityReport },
+};
+
+#[derive(Debug)]
+pub struct SecurityReportWidgetState {
+    /// Built when the tab is opened, `None` while no vault is unlocked.
+    pub report: Option<SecurityReport>,
+    pub list_state: ListState,
+}
+
+impl SecurityReportWidgetState {
+    pub fn new() -> Self {
+        Self {
+            report: None,
+            list_state: ListState::default().with_selected(Some(0)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SecurityReportWidget;
+
+impl SecurityReportWidget {
+    pub fn handle_key_event(
+        key_event: crossterm::event::KeyEvent,
+        _app_state: &App
+    ) -> Option<Message> {
+        match key_event.code {
+            KeyCode::Up if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Message::SetFocusedWidget(FocusedWidget::Top))
+            }
+            KeyCode::Down => Some(Message::SelectNextFinding),
+            KeyCode::Up => Some(Message::SelectPreviousFinding),
+            KeyCode::Char('r') => Some(Message::ReloadSecurityReport),
+            _ => None,
+        }
+    }
+}
+
+fn kind_color(kind: FindingKind) -> Color {
+    match kind {
+        FindingKind::Reused | FindingKind::Weak => Color::Red,
+        FindingKind::Old | FindingKind::MissingTwoFactor => Color::Yellow,
+        FindingKind::Duplicate => Color::Cyan,
+        FindingKind::PlaintextExport => Color::Magenta,
+    }
+}
+
+fn finding_line(finding: &Finding) -> Line<'static> {
+    let names: Vec<String> = finding.entries.iter().map(EntryRef::label).collect();
+
+    Line::from(vec![
+        Span::styled(format!("{:<16}", finding.kind.to_string()), Style::new().fg(kind_color(finding.kind))),
+        Span::raw(names.join(", ")),
+        Span::styled(
+            if names.is_empty() { finding.detail.clone() } else { format!("  {}", finding.detail) },
+            Style::new().dark_gray()
+        ),
+    ])
+}
+
+impl StatefulWidget for SecurityReportWidget {
+    type State = App;
+
+    fn render(
+        self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        state: &mut Self::State
+    )
+        where Self: Sized
+    {
+        let style = if state.focused_widget == FocusedWidget::Top {
+            Style::new()
+        } else {
+            Style::new().blue()
+        };
+
+        let block = Block::bordered().title("Security audit").border_style(style);
+
+        let Some(report) = &state.security_report_state.report else {
+            Paragraph::new("Unlock a vault to audit its entries").block(block).render(area, buf);
+            return;
+        };
+
+        let [summary_area, list_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Fill(1),
+        ]).areas(area);
+
+        let mut summary = vec![Span::raw(format!("{} entries  ", report.entries))];
+        for kind in FindingKind::iter() {
+            let count = report.count(kind);
+            let style = if count == 0 { Style::new().dark_gray() } else { Style::new().fg(kind_color(kind)) };
+            summary.push(Span::styled(format!("  {kind}: {count}"), style));
+        }
+
+        Paragraph::new(Line::from(summary))
+            .block(Block::bordered().title("Summary"))
+            .render(summary_area, buf);
+
+        if report.findings.is_empty() {
+            Paragraph::new("No problems found").block(block).render(list_area, buf);
+            return;
+        }
+
+        let list = List::new(report.findings.iter().map(finding_line))
+            .block(
+                block.title_bottom(
+                    format!(
+                        "weak below {}/4 • old after {} days • r reload",
+                        report.min_score,
+                        report.max_age_days
+                    )
+                )
+            )
+            .highlight_style(Style::new().reversed());
+
+        StatefulWidget::render(list, list_area, buf, &mut state.security_report_state.list_state);
+    }
+}
@@ -0,0 +1,40 @@
+use ratatui::{ style::{ Color, Style }, text::{ Line, Span } };
+
+use crate::strength::{ AttackScenario, Estimate };
+
+fn score_color(score: u8) -> Color {
+    match score {
+        0 => Color::Red,
+        1 => Color::LightRed,
+        2 => Color::Yellow,
+        3 => Color::LightGreen,
+        _ => Color::Green,
+    }
+}
+
+/// Score bar, crack time and the main feedback of a strength estimate.
+pub fn strength_lines(estimate: &Estimate) -> Vec<Line<'static>> {
+    let color = score_color(estimate.score);
+    let filled = (estimate.score as usize) + 1;
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("█".repeat(filled * 3), Style::new().fg(color)),
+            Span::styled("░".repeat((5 - filled) * 3), Style::new().dark_gray()),
+            Span::raw(format!(" {}", estimate.label())),
+            Span::styled(
+                format!(" · cracked offline in {}", estimate.crack_time(AttackScenario::OfflineSlowHash)),
+                Style::new().dark_gray()
+            )
+        ])
+    ];
+
+    if let Some(warning) = &estimate.warning {
+        lines.push(Line::styled(warning.clone(), Style::new().fg(color)));
+    }
+    if let Some(suggestion) = estimate.suggestions.first() {
+        lines.push(Line::styled(suggestion.clone(), Style::new().dark_gray()));
+    }
+
+    lines
+}
@@ -49,7 +49,7 @@ impl StatefulWidget for TopWidget {
 
         let top_block = Block::bordered().title("Menu").border_style(style);
 
-        Tabs::new(["Vault", "Config"])
+        Tabs::new(["Vault", "Config", "Audit", "Rotation", "Log"])
             .select(state.top_state.selected_tab)
             .block(top_block)
             .render(area, buf);
@@ -1,195 +1,831 @@
-use std::env;
 use std::fs;
 use std::fs::File;
-use std::io::Read;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::OnceLock;
 
 use aes_gcm::Aes256Gcm;
 use aes_gcm::KeyInit;
 use aes_gcm::Nonce;
 use aes_gcm::aead::Aead;
 use rand::Rng;
-
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+
+use crate::audit_log::{ self, AuditEvent, AuditEventKind, AuditLog, AuditReport, Checkpoint };
+use crate::config::ensure_parents_exist;
+use crate::derived::DerivationParams;
+use crate::generator::PasswordPolicy;
+use crate::password_rules::PasswordRules;
+use crate::plaintext_export::PlaintextExport;
 use crate::key_derivation;
-use crate::vault_entry::VaultEntry;
-use crate::vault_header::VaultHeader;
-
-static DEFAULT_VAULT_PATH: OnceLock<PathBuf> = OnceLock::new();
-
-fn vault_path() -> &'static PathBuf {
-    DEFAULT_VAULT_PATH.get_or_init(|| {
-        #[cfg(target_os = "windows")]
-        {
-            PathBuf::from(env::var("LOCALAPPDATA").expect("Could not get LOCALAPPDATA"))
-                .join("PasswordManager")
-                .join("vault.bin")
-        }
-
-        #[cfg(target_os = "macos")]
-        {
-            PathBuf::from(env::var("HOME").expect("Could not get HOME"))
-                .join("Library")
-                .join("Application Support")
-                .join("PasswordManager")
-                .join("vault.bin")
-        }
-
-        #[cfg(target_os = "linux")]
-        {
-            PathBuf::from(env::var("HOME").expect("Could not get HOME"))
-                .join(".local")
-                .join("share")
-                .join("PasswordManager")
-                .join("vault.bin")
-        }
-    })
+use crate::lock::VaultLock;
+use crate::merge::{ self, Conflict, Deletion, MergeOutcome, Side };
+use crate::strength;
+use crate::timestamp;
+use crate::vault_entry::{ PasswordVersion, VaultEntry };
+use crate::vault_header::{ self, VaultHeader };
+
+/// Secret of the audit log kept next to the vault, see `audit_log`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct AuditState {
+    secret: String,
+    /// Last record known to this vault, so a log cut short can be told apart from a log
+    /// with fewer events.
+    checkpoint: Checkpoint,
+    /// Restarts of the log after it was found missing or damaged, each a gap in its
+    /// history.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    gaps: Vec<AuditGap>,
 }
 
-fn ensure_parents_exist() -> std::io::Result<()> {
-    let path = Path::new(vault_path());
-
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct AuditGap {
+    time: u64,
+    reason: String,
+}
 
-    Ok(())
+/// Everything stored encrypted after the header.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct VaultData {
+    entries: Vec<VaultEntry>,
+    /// Merge conflicts waiting for the user to pick a side.
+    #[serde(default)]
+    conflicts: Vec<Conflict>,
+    /// Deleted entries, so merging an older copy does not bring them back.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deleted: Vec<Deletion>,
+    #[serde(default)]
+    audit: Option<AuditState>,
+    /// Exports written unencrypted, reported until the vault is gone.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    plaintext_exports: Vec<PlaintextExport>,
 }
 
 #[derive(Debug)]
 pub struct Vault {
+    path: PathBuf,
     header: VaultHeader,
-    entries: Vec<VaultEntry>,
+    /// Derived from the master password, see `key_derivation::unlock`.
+    key: [u8; 32],
+    /// Derived from the master password alone, for the derived passwords of entries.
+    stateless_secret: [u8; 32],
+    data: VaultData,
+    /// `None` when another process held the lock at open time: the vault is read-only.
+    lock: Option<VaultLock>,
+    /// SHA-256 of the file as last read or written, `None` before the first save.
+    fingerprint: Option<[u8; 32]>,
 }
 
 impl Vault {
-    pub fn new(master_password: &str) -> Result<Self, std::io::Error> {
+    /// Creates an empty vault at `path`. Fails if a file already exists there or if the
+    /// master password scores below `min_score`.
+    pub fn create_at(path: &Path, master_password: &str, min_score: u8) -> Result<Self, std::io::Error> {
+        strength
+            ::check_master_password(master_password, min_score)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        let lock = VaultLock::try_acquire(path)?.ok_or_else(|| Self::locked_error(path))?;
+
+        if path.exists() {
+            return Err(
+                std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", path.display())
+                )
+            );
+        }
+
         let (derive_key, salt) = key_derivation::derive_key(master_password);
         let verifier = key_derivation::create_verifier(&derive_key);
 
         let mut nonce = [0u8; 12];
         rand::thread_rng().fill(&mut nonce);
 
-        let vault = Vault {
+        let mut vault = Vault {
+            path: path.to_path_buf(),
             header: VaultHeader::new(salt, verifier, nonce),
-            entries: Vec::new(),
+            key: derive_key,
+            stateless_secret: key_derivation::stateless_secret(master_password),
+            data: VaultData::default(),
+            lock: Some(lock),
+            fingerprint: None,
         };
 
+        vault.start_audit_log()?;
         vault.save()?;
+        vault.record(AuditEventKind::Unlock, None, Some("created".to_string()))?;
         Ok(vault)
     }
 
-    pub fn open_existing(path: &PathBuf, master_password: &str) -> Result<Self, std::io::Error> {
-        let mut file = File::open(path)?;
-        let vault_header = VaultHeader::read(&file)?;
+    /// Opens and decrypts the vault at `path`.
+    ///
+    /// The vault is locked against other writers while it is open. If another process
+    /// already holds the lock, it is opened read-only instead and every save fails.
+    ///
+    /// Failed attempts are recorded in the audit log, and successful ones unless the vault
+    /// is opened read-only. A missing or damaged log never keeps the vault closed: a new
+    /// one is started and the gap is reported by `read_audit_log`.
+    pub fn open_existing(path: &Path, master_password: &str) -> Result<Self, std::io::Error> {
+        let lock = VaultLock::try_acquire(path)?;
+        let bytes = fs::read(path)?;
+
+        let mut vault = Self::from_bytes(path, &bytes, master_password, lock, true)?;
+        vault.fingerprint = Some(Sha256::digest(&bytes).into());
+
+        if !vault.is_read_only() {
+            // Vaults from before the audit log get one the first time they are opened for
+            // writing.
+            let problem = match vault.audit_secret() {
+                None if vault.data.audit.is_none() => None,
+                None => Some("the audit log secret in the vault is unreadable".to_string()),
+                Some(secret) => AuditLog::for_vault(path).check(&secret).err().map(|e| e.to_string()),
+            };
+
+            if vault.data.audit.is_none() {
+                vault.start_audit_log()?;
+                vault.save()?;
+            } else if let Some(problem) = problem {
+                // Should the restart fail too, the next read of the log reports it.
+                let _ = vault.restart_audit_log(problem);
+            }
+
+            // A log that cannot be written to is reported by `read_audit_log`. Read-only,
+            // the log is left to the process holding the lock.
+            let _ = vault.record(AuditEventKind::Unlock, None, None);
+        }
+
+        Ok(vault)
+    }
+
+    /// Opens the vault at `path` only to read it, e.g. as the source of a merge. Unlike a
+    /// read-only `open_existing`, it leaves no trace: no lock file, and nothing, not even
+    /// a failed attempt, is recorded in its audit log.
+    pub fn open_read_only(path: &Path, master_password: &str) -> Result<Self, std::io::Error> {
+        let bytes = fs::read(path)?;
+
+        let mut vault = Self::from_bytes(path, &bytes, master_password, None, false)?;
+        vault.fingerprint = Some(Sha256::digest(&bytes).into());
+        Ok(vault)
+    }
+
+    /// Writes the vault file `bytes`, e.g. from a paper backup, to `path` and opens it.
+    /// The audit log stayed with the lost file, a new one is started. Fails if a vault
+    /// or an audit log already exists there.
+    pub fn restore_at(path: &Path, bytes: &[u8], master_password: &str) -> Result<Self, std::io::Error> {
+        let lock = VaultLock::try_acquire(path)?.ok_or_else(|| Self::locked_error(path))?;
+
+        for existing in [path.to_path_buf(), audit_log::log_path(path)] {
+            if existing.exists() {
+                return Err(
+                    std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", existing.display())
+                    )
+                );
+            }
+        }
+
+        let mut vault = Self::from_bytes(path, bytes, master_password, Some(lock), true)?;
+        vault.start_audit_log()?;
+        vault.save()?;
+        vault.record(AuditEventKind::Unlock, None, Some("restored".to_string()))?;
+        Ok(vault)
+    }
+
+    /// Decrypts the content of a vault file meant to live at `path`. A wrong password is
+    /// recorded in the audit log next to it when `record_failure` is set.
+    fn from_bytes(
+        path: &Path,
+        bytes: &[u8],
+        master_password: &str,
+        lock: Option<VaultLock>,
+        record_failure: bool
+    ) -> Result<Self, std::io::Error> {
+        let mut reader = bytes;
+        let vault_header = VaultHeader::read(&mut reader)?;
+
+        let Some(key) = key_derivation::unlock(
+            master_password,
+            &vault_header.salt,
+            &vault_header.verifier
+        ) else {
+            // Appending only needs the public key in the log, a failure here must not hide
+            // the wrong password.
+            let log = AuditLog::for_vault(path);
+            if record_failure && log.exists() {
+                let _ = log.append(&AuditEvent::new(AuditEventKind::FailedUnlock, None, None));
+            }
 
-        if
-            !key_derivation::verify_password(
-                master_password,
-                &vault_header.salt,
-                &vault_header.verifier
-            )
-        {
             return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid password"));
+        };
+
+        let decrypted_data = Self::decrypt(&key, &vault_header, reader)?;
+        let mut data = Self::deserialize(vault_header.version(), &decrypted_data)?;
+
+        for (index, entry) in data.entries.iter_mut().enumerate().filter(|(_, e)| e.id.is_empty()) {
+            entry.id = entry.legacy_id(index);
         }
 
-        let mut data = Vec::<u8>::new();
-        file.read_to_end(&mut data)?;
+        // Older layouts are rewritten in the current format on the next save.
+        let header = VaultHeader::new(vault_header.salt, vault_header.verifier, vault_header.none);
+
+        let mut vault = Vault {
+            path: path.to_path_buf(),
+            header,
+            key,
+            stateless_secret: key_derivation::stateless_secret(master_password),
+            data,
+            lock,
+            fingerprint: None,
+        };
+        vault.fill_derived_passwords();
+
+        Ok(vault)
+    }
+
+    pub fn file_exists(path: &Path) -> bool {
+        path.exists() && path.is_file()
+    }
 
-        let decrypted_data = Self::decrypt(&vault_header.none, &data)?;
-        let entries = Self::deserialize(&decrypted_data)?;
+    /// The file this vault was created at or opened from, where `save` writes it back.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.lock.is_none()
+    }
 
-        Ok(Vault {
-            header: vault_header,
-            entries: entries,
-        })
+    fn locked_error(path: &Path) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::ResourceBusy,
+            format!("{} is locked by another process, it can only be opened read-only", path.display())
+        )
     }
 
-    pub fn file_exists() -> bool {
-        let path = Path::new(vault_path());
-        if path.exists() && path.is_file() {
-            return true;
+    fn ensure_writable(&self) -> Result<(), std::io::Error> {
+        if self.is_read_only() {
+            return Err(Self::locked_error(&self.path));
         }
 
-        false
+        Ok(())
     }
 
-    pub fn save(&self) -> Result<(), std::io::Error> {
-        ensure_parents_exist()?;
-        let mut file = File::create(vault_path())?;
-        self.header.write(&file)?;
-        let data = self.serialize();
+    /// Whether the file was modified by someone else since it was opened or last saved.
+    pub fn changed_on_disk(&self) -> Result<bool, std::io::Error> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(self.fingerprint != Some(Sha256::digest(&bytes).into())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(self.fingerprint.is_some()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the vault back to its file.
+    ///
+    /// Refuses to overwrite changes made by another process since the vault was opened.
+    /// The new content goes to a temporary file first, so an interrupted save leaves the
+    /// previous vault intact.
+    pub fn save(&mut self) -> Result<(), std::io::Error> {
+        self.ensure_writable()?;
+
+        if self.changed_on_disk()? {
+            return Err(
+                std::io::Error::other(
+                    format!("{} changed on disk since it was opened, reopen it before saving", self.path.display())
+                )
+            );
+        }
+
+        let log = AuditLog::for_vault(&self.path);
+        if let Some(audit) = self.data.audit.as_mut() && log.exists() {
+            audit.checkpoint = log.checkpoint()?;
+        }
+
+        rand::thread_rng().fill(&mut self.header.none);
+
+        let mut bytes = Vec::<u8>::new();
+        self.header.write(&mut bytes)?;
+        let data = self.serialize()?;
         let encrypted_data = self.encrypt(&data)?;
-        file.write_all(&encrypted_data)?;
+        bytes.extend_from_slice(&encrypted_data);
+
+        ensure_parents_exist(&self.path)?;
+        let mut temp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = self.path.with_file_name(temp_name);
 
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+
+        self.fingerprint = Some(Sha256::digest(&bytes).into());
         Ok(())
     }
 
-    fn serialize(&self) -> Vec<u8> {
-        let mut data = Vec::<u8>::new();
+    /// Serializes the data without the passwords of derived entries, which are computed
+    /// again when the vault is opened.
+    fn serialize(&self) -> Result<Vec<u8>, std::io::Error> {
+        let mut data = self.data.clone();
+        let conflicts = data.conflicts.iter_mut().flat_map(|c| [&mut c.local, &mut c.remote]);
+
+        for entry in data.entries.iter_mut().chain(conflicts) {
+            entry.for_each_derived(|_, password| password.clear());
+        }
+
+        serde_json::to_vec(&data).map_err(std::io::Error::other)
+    }
+
+    /// The password of derived entries, for the master password this vault was opened with.
+    pub fn derive_password(&self, params: &DerivationParams) -> Result<String, String> {
+        params.derive(&self.stateless_secret)
+    }
 
-        data.push(self.entries.len() as u8);
-        let mut data = Vec::<u8>::new();
-        data.push(self.entries.len() as u8);
+    /// Computes the passwords of derived entries, which are not stored.
+    fn fill_derived_passwords(&mut self) {
+        let secret = self.stateless_secret;
+        let conflicts = self.data.conflicts.iter_mut().flat_map(|c| [&mut c.local, &mut c.remote]);
+
+        for entry in self.data.entries.iter_mut().chain(conflicts) {
+            entry.for_each_derived(|params, password| {
+                if let Ok(derived) = params.derive(&secret) {
+                    *password = derived.into_bytes();
+                }
+            });
+        }
+    }
 
-        for entry in &self.entries {
-            let serialized_entry = entry.serialize();
-            data.extend_from_slice(&serialized_entry);
+    /// Sets the password of a derived entry from its parameters.
+    fn derive_entry_password(&self, entry: &mut VaultEntry) -> Result<(), std::io::Error> {
+        if let Some(params) = &entry.derivation {
+            let password = self
+                .derive_password(params)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+            entry.password = password.into_bytes();
         }
 
-        data
+        Ok(())
+    }
+
+    fn deserialize(version: u16, data: &[u8]) -> Result<VaultData, std::io::Error> {
+        let invalid_data = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+
+        match version {
+            1 => Ok(VaultData { entries: Self::deserialize_legacy(data), ..VaultData::default() }),
+            2 => {
+                let entries = serde_json::from_slice(data).map_err(invalid_data)?;
+                Ok(VaultData { entries, ..VaultData::default() })
+            }
+            _ => serde_json::from_slice(data).map_err(invalid_data),
+        }
     }
 
-    fn deserialize(_data: &[u8]) -> Result<Vec<VaultEntry>, std::io::Error> {
+    fn deserialize_legacy(data: &[u8]) -> Vec<VaultEntry> {
         let mut index = 0;
-        let entries_count = _data[index] as usize;
+        let entries_count = data[index] as usize;
         index += 1;
 
         let mut entries = Vec::<VaultEntry>::new();
 
         for _ in 0..entries_count {
-            let entry = VaultEntry::deserialize(&_data[index..]);
-            index +=
-                1 +
-                entry.service.len() +
-                1 +
-                entry.username.as_ref().map_or(0, |u| u.len()) +
-                1 +
-                entry.password.len();
+            let (entry, consumed) = VaultEntry::deserialize_legacy(&data[index..]);
+            index += consumed;
             entries.push(entry);
         }
 
-        Ok(entries)
+        entries
     }
 
     fn encrypt(&self, _data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
-        let cipher = Aes256Gcm::new_from_slice(b"ma_cle_secrete012345678915478963").unwrap();
+        let cipher = Aes256Gcm::new_from_slice(&key_derivation::subkey(&self.key, b"vault")).unwrap();
         let nonce = Nonce::from_slice(&self.header.none);
         cipher
             .encrypt(nonce, _data)
             .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Encryption error"))
     }
 
-    fn decrypt(nonce_bytes: &[u8; 12], _data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
-        let cipher = Aes256Gcm::new_from_slice(b"ma_cle_secrete012345678915478963").unwrap();
-        let nonce = Nonce::from_slice(nonce_bytes.as_slice());
+    fn decrypt(key: &[u8; 32], header: &VaultHeader, _data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+        let cipher = if header.version() < vault_header::CURRENT_VERSION {
+            Aes256Gcm::new_from_slice(b"ma_cle_secrete012345678915478963").unwrap()
+        } else {
+            Aes256Gcm::new_from_slice(&key_derivation::subkey(key, b"vault")).unwrap()
+        };
+        let nonce = Nonce::from_slice(header.none.as_slice());
         cipher
             .decrypt(nonce, _data)
             .map_err(|_e| std::io::Error::new(std::io::ErrorKind::Other, "Decryption error"))
     }
 
-    pub fn add_entry(&mut self, entry: VaultEntry) {
-        self.entries.push(entry);
-        self.save().expect("Error saving vault after adding entry");
+    /// Starts a new audit log and keeps its secret in the vault. The vault must be saved
+    /// afterwards.
+    fn start_audit_log(&mut self) -> Result<(), std::io::Error> {
+        let secret = audit_log::generate_secret();
+        AuditLog::for_vault(&self.path).create(&secret)?;
+
+        self.data.audit = Some(AuditState {
+            secret: hex::encode(secret),
+            checkpoint: Checkpoint::default(),
+            gaps: Vec::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Starts a new audit log in place of one that is missing or damaged, which is kept
+    /// aside, and records the gap in the vault.
+    fn restart_audit_log(&mut self, problem: String) -> Result<(), std::io::Error> {
+        let log = AuditLog::for_vault(&self.path);
+        let reason = if log.exists() {
+            format!("{problem}, the log was moved to {}", log.set_aside()?.display())
+        } else {
+            problem
+        };
+
+        let mut gaps = self.data.audit.as_ref().map(|audit| audit.gaps.clone()).unwrap_or_default();
+        gaps.push(AuditGap { time: timestamp::now(), reason });
+
+        self.start_audit_log()?;
+        if let Some(audit) = self.data.audit.as_mut() {
+            audit.gaps = gaps;
+        }
+        self.save()
+    }
+
+    fn missing_audit_log(path: &Path) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("the audit log {} is missing", audit_log::log_path(path).display())
+        )
+    }
+
+    fn audit_secret(&self) -> Option<[u8; 32]> {
+        let audit = self.data.audit.as_ref()?;
+        hex::decode(&audit.secret).ok()?.try_into().ok()
+    }
+
+    /// Appends an event to the audit log of this vault.
+    ///
+    /// Does nothing for a vault opened read-only before it ever had an audit log.
+    pub fn record(
+        &self,
+        kind: AuditEventKind,
+        entry: Option<&VaultEntry>,
+        detail: Option<String>
+    ) -> Result<(), std::io::Error> {
+        if self.data.audit.is_none() {
+            return Ok(());
+        }
+
+        let log = AuditLog::for_vault(&self.path);
+        if !log.exists() {
+            return Err(Self::missing_audit_log(&self.path));
+        }
+
+        log.append(&AuditEvent::new(kind, entry, detail))?;
+        Ok(())
+    }
+
+    /// Decrypts the audit log and checks it was neither tampered with nor truncated. A
+    /// missing or unreadable log, and the restarts of the log, are reported as problems.
+    pub fn read_audit_log(&self) -> Result<AuditReport, std::io::Error> {
+        let (Some(audit), Some(secret)) = (self.data.audit.as_ref(), self.audit_secret()) else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "this vault has no audit log"));
+        };
+
+        let log = AuditLog::for_vault(&self.path);
+        let mut report = if log.exists() {
+            log.read(&secret, Some(&audit.checkpoint)).unwrap_or_else(|e| AuditReport {
+                problems: vec![format!("the audit log is unreadable: {e}")],
+                ..AuditReport::default()
+            })
+        } else {
+            AuditReport { problems: vec![Self::missing_audit_log(&self.path).to_string()], ..AuditReport::default() }
+        };
+
+        let gaps = audit.gaps
+            .iter()
+            .map(|gap| format!("the log was restarted on {}: {}", timestamp::format_datetime(gap.time), gap.reason));
+        report.problems.splice(0..0, gaps);
+
+        Ok(report)
+    }
+
+    /// Writes the decrypted audit log and its integrity problems to `path` as JSON. The
+    /// export itself is recorded in the log.
+    pub fn export_audit_log(&self, path: &Path) -> Result<AuditReport, std::io::Error> {
+        let report = self.read_audit_log()?;
+        let json = serde_json::to_vec_pretty(&report).map_err(std::io::Error::other)?;
+        fs::write(path, json)?;
+
+        self.record(AuditEventKind::Export, None, Some(format!("audit log to {}", path.display())))?;
+        Ok(report)
+    }
+
+    /// Whether `master_password` is the one the vault was opened with, to confirm
+    /// sensitive operations.
+    pub fn verify_master_password(&self, master_password: &str) -> bool {
+        key_derivation::unlock(master_password, &self.header.salt, &self.header.verifier).is_some()
+    }
+
+    /// Records an unencrypted export of `entries` entries both in the audit log and in
+    /// the vault, where the security report finds it, and saves the vault.
+    pub fn record_plaintext_export(&mut self, format: &str, destination: &str, entries: usize) -> Result<(), std::io::Error> {
+        self.ensure_writable()?;
+        self.record(
+            AuditEventKind::Export,
+            None,
+            Some(format!("{entries} entries unencrypted as {format} to {destination}"))
+        )?;
+
+        self.data.plaintext_exports.push(PlaintextExport {
+            time: timestamp::now(),
+            format: format.to_string(),
+            destination: destination.to_string(),
+            entries,
+        });
+        self.save()
+    }
+
+    pub fn plaintext_exports(&self) -> &[PlaintextExport] {
+        &self.data.plaintext_exports
+    }
+
+    pub fn add_entry(&mut self, mut entry: VaultEntry) -> Result<(), std::io::Error> {
+        self.ensure_writable()?;
+        self.derive_entry_password(&mut entry)?;
+        self.record(AuditEventKind::Add, Some(&entry), None)?;
+        self.data.entries.push(entry);
+        self.save()
+    }
+
+    /// Adds entries read from another password manager and saves the vault once. Each
+    /// entry is audited with `source`, e.g. the path of the export.
+    pub fn import_entries(&mut self, entries: Vec<VaultEntry>, source: &str) -> Result<(), std::io::Error> {
+        self.ensure_writable()?;
+
+        for mut entry in entries {
+            self.derive_entry_password(&mut entry)?;
+            self.record(AuditEventKind::Add, Some(&entry), Some(format!("imported from {source}")))?;
+            self.data.entries.push(entry);
+        }
+
+        self.save()
+    }
+
+    /// Replaces the entry with the same id and saves the vault. The previous version is
+    /// kept in the entry revisions so merges recognise it, and a replaced password in the
+    /// entry's password history.
+    pub fn update_entry(&mut self, entry: VaultEntry) -> Result<(), std::io::Error> {
+        self.replace_entry(entry, None)
+    }
+
+    fn replace_entry(&mut self, mut entry: VaultEntry, detail: Option<String>) -> Result<(), std::io::Error> {
+        self.ensure_writable()?;
+        let Some(index) = self.data.entries.iter().position(|e| e.id == entry.id) else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no such entry"));
+        };
+
+        self.derive_entry_password(&mut entry)?;
+
+        let current = &self.data.entries[index];
+        let password_replaced = entry.password != current.password || entry.derivation != current.derivation;
+        if password_replaced && entry.password_history == current.password_history {
+            let mut retired = current.clone();
+            retired.retire_password();
+            entry.password_history = retired.password_history;
+        }
+
+        entry.revisions = current.revisions.clone();
+        entry.record_revision(current.revision());
+        entry.modified = timestamp::now();
+
+        self.record(AuditEventKind::Edit, Some(&entry), detail)?;
+        self.data.entries[index] = entry;
+        self.save()
+    }
+
+    fn entry_by_id(&self, id: &str) -> Result<VaultEntry, std::io::Error> {
+        self.data.entries
+            .iter()
+            .find(|e| e.id == id)
+            .cloned()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no such entry"))
+    }
+
+    /// Generates the next password of an entry: the next counter of a derived entry, or
+    /// else a password of `policy` following the site's rules.
+    pub fn next_password(&self, entry: &VaultEntry, policy: &PasswordPolicy) -> Result<PasswordVersion, String> {
+        let (password, derivation) = match &entry.derivation {
+            Some(params) => {
+                let mut next = params.clone();
+                next.counter += 1;
+                (self.derive_password(&next)?, Some(next))
+            }
+            None => {
+                let password = match PasswordRules::for_entry(entry) {
+                    Some(rules) => rules.generate(policy)?,
+                    None => policy.generate()?,
+                };
+                (password, None)
+            }
+        };
+
+        Ok(PasswordVersion { password: password.into_bytes(), derivation, time: timestamp::now() })
+    }
+
+    /// Generates a new password for the entry and keeps it pending, the current password
+    /// staying in use until the change is confirmed.
+    pub fn start_rotation(&mut self, id: &str, policy: &PasswordPolicy) -> Result<(), std::io::Error> {
+        let mut entry = self.entry_by_id(id)?;
+        let next = self
+            .next_password(&entry, policy)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        entry.pending_rotation = Some(next);
+        self.replace_entry(entry, Some("rotation started".to_string()))
+    }
+
+    /// Makes the pending password of the entry current once the site accepted it.
+    pub fn confirm_rotation(&mut self, id: &str) -> Result<(), std::io::Error> {
+        let mut entry = self.entry_by_id(id)?;
+        if !entry.confirm_rotation() {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no rotation is pending"));
+        }
+
+        self.replace_entry(entry, Some("rotation confirmed".to_string()))
+    }
+
+    /// Forgets the pending password, for a site that refused it.
+    pub fn cancel_rotation(&mut self, id: &str) -> Result<(), std::io::Error> {
+        let mut entry = self.entry_by_id(id)?;
+        if entry.pending_rotation.take().is_none() {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no rotation is pending"));
+        }
+
+        self.replace_entry(entry, Some("rotation cancelled".to_string()))
+    }
+
+    pub fn delete_entry(&mut self, id: &str) -> Result<(), std::io::Error> {
+        self.ensure_writable()?;
+        let Some(index) = self.data.entries.iter().position(|e| e.id == id) else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no such entry"));
+        };
+
+        self.record(AuditEventKind::Delete, Some(&self.data.entries[index]), None)?;
+        let entry = self.data.entries.remove(index);
+        self.data.deleted.retain(|deletion| deletion.id != entry.id);
+        self.data.deleted.push(Deletion::of(&entry, timestamp::now()));
+        self.data.conflicts.retain(|conflict| conflict.id() != entry.id);
+        self.save()
     }
 
     pub fn get_entries(&self) -> &Vec<VaultEntry> {
-        &self.entries
+        &self.data.entries
+    }
+
+    /// Entries deleted from this vault, skipped when merging other copies.
+    pub fn deletions(&self) -> &[Deletion] {
+        &self.data.deleted
+    }
+
+    /// Merges the entries of another copy of this vault into this one and saves it.
+    ///
+    /// Conflicting entries get the most recently modified version for now and are kept
+    /// in `conflicts` until resolved. Conflicts already pending for the same entry are
+    /// replaced. Entries deleted from either copy stay deleted unless changed since.
+    pub fn merge_from(&mut self, other: &Vault) -> Result<MergeOutcome, std::io::Error> {
+        self.merge_entries(&other.data.entries, &other.data.deleted, &other.path.display().to_string())
+    }
+
+    /// Merges `entries` of another copy of this vault, e.g. from an archive, like
+    /// `merge_from`, `deletions` being the entries deleted from that copy. `source` names
+    /// them in the audit log.
+    pub fn merge_entries(
+        &mut self,
+        entries: &[VaultEntry],
+        deletions: &[Deletion],
+        source: &str
+    ) -> Result<MergeOutcome, std::io::Error> {
+        self.ensure_writable()?;
+        for deletion in deletions {
+            if !self.data.deleted.contains(deletion) {
+                self.data.deleted.push(deletion.clone());
+            }
+        }
+        let mut outcome = merge::merge(&self.data.entries, entries, &self.data.deleted);
+
+        self.data.entries = std::mem::take(&mut outcome.entries);
+        let entries = &self.data.entries;
+        self.data.conflicts.retain(|pending| {
+            entries.iter().any(|e| e.id == pending.id()) && !outcome.conflicts.iter().any(|c| c.id() == pending.id())
+        });
+        self.data.conflicts.extend(outcome.conflicts.iter().cloned());
+        self.fill_derived_passwords();
+        self.record(
+            AuditEventKind::Edit,
+            None,
+            Some(
+                format!(
+                    "merged {source}: {} added, {} updated, {} deleted, {} conflicts",
+                    outcome.added,
+                    outcome.updated,
+                    outcome.deleted,
+                    outcome.conflicts.len()
+                )
+            )
+        )?;
+        self.save()?;
+
+        Ok(outcome)
+    }
+
+    /// Replaces every entry with `entries`, e.g. from an archive, drops the pending
+    /// conflicts and saves the vault. `source` names the entries in the audit log.
+    pub fn replace_entries(&mut self, entries: Vec<VaultEntry>, source: &str) -> Result<(), std::io::Error> {
+        self.ensure_writable()?;
+        self.record(
+            AuditEventKind::Edit,
+            None,
+            Some(format!("replaced {} entries with {} from {source}", self.data.entries.len(), entries.len()))
+        )?;
+
+        self.data.entries = entries;
+        self.data.conflicts.clear();
+        self.fill_derived_passwords();
+        self.save()
+    }
+
+    /// Puts the entries with the given ids in the order of `ids`, in the places they
+    /// already take among the others, e.g. the variables of a `.env` file in the order of
+    /// the file. Saves the vault if the order changed.
+    pub fn order_entries(&mut self, ids: &[String]) -> Result<(), std::io::Error> {
+        self.ensure_writable()?;
+        let rank = |entry: &VaultEntry| ids.iter().position(|id| *id == entry.id);
+
+        let slots: Vec<usize> = (0..self.data.entries.len()).filter(|&i| rank(&self.data.entries[i]).is_some()).collect();
+        let mut ordered: Vec<VaultEntry> = slots.iter().map(|&i| self.data.entries[i].clone()).collect();
+        ordered.sort_by_key(|entry| rank(entry));
+        if slots.iter().zip(&ordered).all(|(&i, entry)| self.data.entries[i].id == entry.id) {
+            return Ok(());
+        }
+
+        for (i, entry) in slots.into_iter().zip(ordered) {
+            self.data.entries[i] = entry;
+        }
+        self.save()
     }
 
-    pub fn get_entry(&self, index: usize) -> Option<&VaultEntry> {
-        self.entries.get(index).to_owned()
+    /// Prepares entries coming from a vault with another master password: the passwords
+    /// derived from that master password are kept as stored passwords, since this vault
+    /// would derive other ones. Returns how many entries had such passwords.
+    pub fn adopt_entries(&self, entries: &mut [VaultEntry]) -> usize {
+        let mut adopted = 0;
+
+        for entry in entries.iter_mut() {
+            let versions = std::iter::once((&mut entry.derivation, &entry.password)).chain(
+                entry.pending_rotation
+                    .iter_mut()
+                    .chain(entry.password_history.iter_mut())
+                    .map(|version| (&mut version.derivation, &version.password))
+            );
+
+            let mut foreign = false;
+            for (derivation, password) in versions {
+                let derived = derivation.as_ref().map(|params| self.derive_password(params).map(String::into_bytes));
+                if derived.is_some_and(|derived| derived.as_ref() != Ok(password)) {
+                    *derivation = None;
+                    foreign = true;
+                }
+            }
+
+            if foreign {
+                adopted += 1;
+            }
+        }
+
+        adopted
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.data.conflicts
+    }
+
+    /// Keeps the `side` version of a pending merge conflict and saves the vault.
+    pub fn resolve_conflict(&mut self, index: usize, side: Side) -> Result<(), std::io::Error> {
+        self.ensure_writable()?;
+        let Some(conflict) = self.data.conflicts.get(index) else {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "no such conflict"));
+        };
+        let resolved = conflict.resolve(side);
+        self.record(AuditEventKind::Edit, Some(&resolved), Some(format!("kept the {} version", format!("{side:?}").to_lowercase())))?;
+        self.data.conflicts.remove(index);
+
+        match self.data.entries.iter_mut().find(|e| e.id == resolved.id) {
+            Some(entry) => *entry = resolved,
+            None => self.data.entries.push(resolved),
+        }
+
+        self.save()
     }
 }
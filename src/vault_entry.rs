@@ -1,30 +1,320 @@
-#[derive(Debug)]
+use rand::RngCore;
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+use strum::Display;
+
+use crate::derived::DerivationParams;
+use crate::timestamp;
+
+/// How many previous revisions an entry remembers for merging.
+const MAX_REVISIONS: usize = 32;
+/// How many retired passwords an entry keeps.
+const MAX_PASSWORD_HISTORY: usize = 20;
+
+/// What an entry holds. Entries other than logins keep their details, e.g. a card
+/// number, in custom fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    #[default]
+    #[strum(to_string = "login")]
+    Login,
+    #[strum(to_string = "secure note")]
+    SecureNote,
+    #[strum(to_string = "card")]
+    Card,
+    #[strum(to_string = "identity")]
+    Identity,
+}
+
+impl EntryKind {
+    pub fn is_login(&self) -> bool {
+        *self == EntryKind::Login
+    }
+}
+
+/// A named value besides the standard fields, e.g. a PIN or a security answer.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CustomField {
+    pub name: String,
+    pub value: String,
+    /// Hidden like the password until revealed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub protected: bool,
+}
+
+/// A file kept with an entry, e.g. a recovery codes document or a key.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Attachment {
+    pub name: String,
+    #[serde(with = "base64_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// Stores bytes as a base64 string rather than an array of numbers.
+mod base64_bytes {
+    use base64::{ Engine, engine::general_purpose::STANDARD };
+    use serde::{ Deserialize, Deserializer, Serializer };
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A password besides the current one: retired, or generated by a rotation waiting for
+/// confirmation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PasswordVersion {
+    /// Empty in the stored vault for derived passwords, like the entry password.
+    #[serde(default)]
+    pub password: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation: Option<DerivationParams>,
+    /// When the password was retired, or when a pending one was generated.
+    pub time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct VaultEntry {
+    /// Stable identifier, kept across edits and shared by copies of the same vault.
+    #[serde(default)]
+    pub id: String,
     pub service: String,
     pub username: Option<String>,
     pub password: Vec<u8>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Slash separated folder path, e.g. `work/infra`.
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Creation and last modification times in seconds since the Unix epoch, `0` when unknown.
+    #[serde(default)]
+    pub created: u64,
+    #[serde(default)]
+    pub modified: u64,
+    /// Revisions this entry went through, oldest first. A merge uses them to tell an
+    /// entry that was edited on one side from one that changed on both.
+    #[serde(default)]
+    pub revisions: Vec<String>,
+    /// Site requirements on the password in the `passwordrules` syntax, overriding the
+    /// bundled rules of the site. Left out of the serialized entry when unset so that
+    /// revisions of older entries keep their hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_rules: Option<String>,
+    /// Set for an entry whose password is derived from the master password rather than
+    /// stored, see `derived`. Its password is only kept in memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation: Option<DerivationParams>,
+    /// Previous passwords, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub password_history: Vec<PasswordVersion>,
+    /// New password of a started rotation, which replaces the current one once the user
+    /// confirms the site accepted it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_rotation: Option<PasswordVersion>,
+    /// When the credential stops working, e.g. an API token or a certificate, as midnight
+    /// UTC of the expiry date.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+    /// One-time password secret, an `otpauth://` URI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_fields: Vec<CustomField>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    #[serde(default, skip_serializing_if = "EntryKind::is_login")]
+    pub kind: EntryKind,
+}
+
+/// Random identifier for a new entry.
+pub fn generate_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// `otpauth://` URI of a base32 TOTP `secret`, labelled `service:username`.
+pub fn totp_uri(secret: &str, service: &str, username: &str) -> String {
+    let label = if username.is_empty() { service.to_string() } else { format!("{service}:{username}") };
+    let label: String = label
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~@:".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect();
+
+    format!("otpauth://totp/{label}?secret={secret}")
+}
+
+/// Lowercased host name of a URL, without scheme, credentials, port or path.
+pub fn host_of(url: &str) -> Option<String> {
+    let url = url.trim();
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = url.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = authority.split(':').next()?;
+
+    if host.is_empty() { None } else { Some(host.to_lowercase()) }
 }
 
 impl VaultEntry {
-    pub fn serialize(&self) -> Vec<u8> {
-        let mut data = Vec::new();
-        data.push(self.service.as_bytes().len() as u8);
-        data.extend_from_slice(self.service.as_bytes());
-
-        if let Some(username) = &self.username {
-            data.push(username.as_bytes().len() as u8);
-            data.extend_from_slice(username.as_bytes());
-        } else {
-            data.push(0u8);
+    pub fn new(service: String, username: Option<String>, password: Vec<u8>) -> Self {
+        let now = timestamp::now();
+
+        Self {
+            id: generate_id(),
+            service,
+            username,
+            password,
+            url: None,
+            tags: Vec::new(),
+            notes: None,
+            folder: None,
+            created: now,
+            modified: now,
+            revisions: Vec::new(),
+            password_rules: None,
+            derivation: None,
+            password_history: Vec::new(),
+            pending_rotation: None,
+            expires: None,
+            totp: None,
+            custom_fields: Vec::new(),
+            attachments: Vec::new(),
+            kind: EntryKind::Login,
+        }
+    }
+
+    /// Identifier for the entry at `index` of a vault stored before ids existed, derived
+    /// from its place and content so that two copies of the same old vault agree on it,
+    /// while entries with the same service and username do not.
+    pub fn legacy_id(&self, index: usize) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update((index as u64).to_le_bytes());
+        hasher.update(self.service.as_bytes());
+        hasher.update([0]);
+        hasher.update(self.username.as_deref().unwrap_or("").as_bytes());
+        hasher.update([0]);
+        hasher.update(&self.password);
+        hasher.update([0]);
+        hasher.update(self.created.to_le_bytes());
+
+        hex::encode(&hasher.finalize()[..16])
+    }
+
+    /// Hash of the user visible content, ignoring timestamps and revision bookkeeping.
+    pub fn revision(&self) -> String {
+        let mut content = self.clone();
+        content.created = 0;
+        content.modified = 0;
+        content.revisions.clear();
+
+        let json = serde_json::to_vec(&content).expect("entries always serialize");
+        hex::encode(&Sha256::digest(json)[..16])
+    }
+
+    /// Whether `other` is a previous version of this entry.
+    pub fn descends_from(&self, other: &VaultEntry) -> bool {
+        self.revisions.contains(&other.revision())
+    }
+
+    /// Remembers `revision` as a previous version of this entry.
+    pub fn record_revision(&mut self, revision: String) {
+        if revision == self.revision() || self.revisions.contains(&revision) {
+            return;
         }
 
-        data.push(self.password.len() as u8);
-        data.extend_from_slice(&self.password);
+        self.revisions.push(revision);
+        if self.revisions.len() > MAX_REVISIONS {
+            self.revisions.remove(0);
+        }
+    }
 
-        data
+    /// When the current password was set, `0` when unknown.
+    pub fn password_changed(&self) -> u64 {
+        match self.password_history.last() {
+            Some(retired) => retired.time,
+            None if self.created != 0 => self.created,
+            None => self.modified,
+        }
     }
 
-    pub fn deserialize(data: &[u8]) -> Self {
+    /// Moves the current password to the history.
+    pub fn retire_password(&mut self) {
+        self.password_history.push(PasswordVersion {
+            password: self.password.clone(),
+            derivation: self.derivation.clone(),
+            time: timestamp::now(),
+        });
+
+        if self.password_history.len() > MAX_PASSWORD_HISTORY {
+            self.password_history.remove(0);
+        }
+    }
+
+    /// Makes the pending rotation the current password, the old one going to the history.
+    pub fn confirm_rotation(&mut self) -> bool {
+        let Some(pending) = self.pending_rotation.take() else {
+            return false;
+        };
+
+        self.retire_password();
+        self.password = pending.password;
+        self.derivation = pending.derivation;
+        true
+    }
+
+    /// Calls `f` on every derived password of the entry, current, pending and retired.
+    pub fn for_each_derived(&mut self, mut f: impl FnMut(&DerivationParams, &mut Vec<u8>)) {
+        if let Some(params) = &self.derivation {
+            f(params, &mut self.password);
+        }
+
+        let others = self.pending_rotation.iter_mut().chain(self.password_history.iter_mut());
+        for version in others {
+            if let Some(params) = &version.derivation {
+                f(params, &mut version.password);
+            }
+        }
+    }
+
+    /// Lowercased host name of `url`, without scheme, credentials, port or path.
+    pub fn host(&self) -> Option<String> {
+        host_of(self.url.as_deref()?)
+    }
+
+    /// What makes two entries the same account: the site name, e.g. `github` for
+    /// `https://www.github.com/login` or for a service named `GitHub`, and the username.
+    pub fn account_key(&self) -> (String, String) {
+        let site = match self.host() {
+            Some(host) => {
+                let mut labels = host.rsplit('.');
+                let top = labels.next().unwrap_or_default();
+                labels.next().unwrap_or(top).to_string()
+            }
+            None => self.service.trim().to_lowercase(),
+        };
+
+        (site, self.username.as_deref().unwrap_or("").trim().to_lowercase())
+    }
+
+    /// Reads one entry in the version 1 binary layout and returns it together with
+    /// the number of bytes consumed.
+    pub fn deserialize_legacy(data: &[u8]) -> (Self, usize) {
         let mut index = 0;
 
         let service_len = data[index] as usize;
@@ -47,11 +337,31 @@ impl VaultEntry {
         index += 1;
 
         let password = data[index..index + password_len].to_vec();
+        index += password_len;
 
-        VaultEntry {
+        let entry = VaultEntry {
+            id: String::new(),
             service,
             username,
             password,
-        }
+            url: None,
+            tags: Vec::new(),
+            notes: None,
+            folder: None,
+            created: 0,
+            modified: 0,
+            revisions: Vec::new(),
+            password_rules: None,
+            derivation: None,
+            password_history: Vec::new(),
+            pending_rotation: None,
+            expires: None,
+            totp: None,
+            custom_fields: Vec::new(),
+            attachments: Vec::new(),
+            kind: EntryKind::Login,
+        };
+
+        (entry, index)
     }
 }
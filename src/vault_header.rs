@@ -2,6 +2,12 @@ use std::io::{ Read, Write };
 
 use subtle::ConstantTimeEq;
 
+/// Version 1 stored entries as length-prefixed binary records, version 2 as a JSON array
+/// and version 3 as a JSON object holding the entries next to other vault data. Version 4
+/// encrypts that object with a key derived from the master password and a new nonce on
+/// every save, earlier versions used a fixed key.
+pub const CURRENT_VERSION: u16 = 4;
+
 #[derive(Debug)]
 pub struct VaultHeader {
     magic: [u8; 4],
@@ -20,13 +26,17 @@ impl VaultHeader {
     pub fn new(salt: [u8; 22], verifier: [u8; 32], none: [u8; 12]) -> Self {
         Self {
             magic: b"PMGR".to_owned(),
-            version: 1,
-            salt: salt,
-            verifier: verifier,
-            none: none,
+            version: CURRENT_VERSION,
+            salt,
+            verifier,
+            none,
         }
     }
 
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
         writer.write_all(self.magic.as_slice())?;
         writer.write_all(self.version.to_le_bytes().as_slice())?;
@@ -48,7 +58,7 @@ impl VaultHeader {
         let mut version = [0u8; 2];
         reader.read_exact(&mut version)?;
 
-        if u16::from_le_bytes(version) != 1 {
+        if !(1..=CURRENT_VERSION).contains(&u16::from_le_bytes(version)) {
             return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid version"));
         }
 